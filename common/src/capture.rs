@@ -0,0 +1,210 @@
+use serde::Serialize;
+use std::fmt::Display;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use time::OffsetDateTime;
+
+#[cfg(test)]
+#[path = "./capture.tests.rs"]
+mod capture_tests;
+
+/// Magic bytes at the beginning of every capture file.
+pub const CAPTURE_MAGIC: &[u8; 8] = b"DC09CAP1";
+
+/// Maximum length of a captured frame, longer records are rejected as invalid data.
+pub const MAX_CAPTURE_FRAME_SIZE: usize = 1024 * 1024;
+
+/// Transport used to exchange a captured frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Transport {
+    Tcp,
+    Udp,
+}
+
+impl Display for Transport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Tcp => write!(f, "TCP"),
+            Self::Udp => write!(f, "UDP"),
+        }
+    }
+}
+
+/// Direction of a captured frame as seen by the receiver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// Frame sent by a dialler to the receiver.
+    Inbound,
+    /// Response sent by the receiver to a dialler.
+    Outbound,
+}
+
+impl Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Inbound => write!(f, "->"),
+            Self::Outbound => write!(f, "<-"),
+        }
+    }
+}
+
+/// Single frame stored in a capture file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureRecord {
+    pub timestamp: OffsetDateTime,
+    pub transport: Transport,
+    pub direction: Direction,
+    pub peer: SocketAddr,
+    pub frame: Vec<u8>,
+}
+
+impl CaptureRecord {
+    /// Creates new [`CaptureRecord`] instance timestamped with the current time.
+    pub fn new(transport: Transport, direction: Direction, peer: SocketAddr, frame: &[u8]) -> Self {
+        Self {
+            timestamp: OffsetDateTime::now_utc(),
+            transport,
+            direction,
+            peer,
+            frame: frame.to_vec(),
+        }
+    }
+}
+
+/// Writes capture records in a compact binary format.\
+/// **Note** that each record is stored (little-endian) as: timestamp in microseconds since the Unix epoch (`i64`),
+/// transport (`u8`), direction (`u8`), IP version (`u8`), peer address (4 or 16 bytes), peer port (`u16`),
+/// frame length (`u32`) and raw frame bytes.
+pub struct CaptureWriter<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> CaptureWriter<W> {
+    /// Creates new [`CaptureWriter`] instance and writes the capture file header.
+    pub fn new(mut inner: W) -> io::Result<Self> {
+        inner.write_all(CAPTURE_MAGIC)?;
+        Ok(Self { inner })
+    }
+
+    /// Appends a single record to the capture.
+    pub fn write(&mut self, record: &CaptureRecord) -> io::Result<()> {
+        let micros = (record.timestamp.unix_timestamp_nanos() / 1_000) as i64;
+        self.inner.write_all(&micros.to_le_bytes())?;
+        self.inner.write_all(&[
+            match record.transport {
+                Transport::Tcp => 0,
+                Transport::Udp => 1,
+            },
+            match record.direction {
+                Direction::Inbound => 0,
+                Direction::Outbound => 1,
+            },
+        ])?;
+
+        match record.peer.ip() {
+            IpAddr::V4(ip) => {
+                self.inner.write_all(&[4])?;
+                self.inner.write_all(&ip.octets())?;
+            },
+            IpAddr::V6(ip) => {
+                self.inner.write_all(&[6])?;
+                self.inner.write_all(&ip.octets())?;
+            },
+        }
+
+        if record.frame.len() > MAX_CAPTURE_FRAME_SIZE {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "frame too large"));
+        }
+
+        let len = record.frame.len() as u32;
+        self.inner.write_all(&record.peer.port().to_le_bytes())?;
+        self.inner.write_all(&len.to_le_bytes())?;
+        self.inner.write_all(&record.frame)
+    }
+
+    /// Flushes buffered records to the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reads capture records written by [`CaptureWriter`].
+pub struct CaptureReader<R: Read> {
+    inner: R,
+}
+
+impl<R: Read> CaptureReader<R> {
+    /// Creates new [`CaptureReader`] instance, returns an error if the capture file header is invalid.
+    pub fn new(mut inner: R) -> io::Result<Self> {
+        let mut magic = [0; CAPTURE_MAGIC.len()];
+        inner.read_exact(&mut magic)?;
+        if &magic != CAPTURE_MAGIC {
+            return Err(io::Error::new(ErrorKind::InvalidData, "not a DC09 capture file"));
+        }
+
+        Ok(Self { inner })
+    }
+
+    fn read_record(&mut self) -> io::Result<Option<CaptureRecord>> {
+        let mut timestamp = [0; 8];
+        match self.inner.read_exact(&mut timestamp) {
+            Ok(()) => {},
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+
+        let micros = i64::from_le_bytes(timestamp);
+        let timestamp = OffsetDateTime::from_unix_timestamp_nanos(i128::from(micros) * 1_000)
+            .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+
+        let [transport, direction, version] = self.read_array()?;
+        let transport = match transport {
+            0 => Transport::Tcp,
+            1 => Transport::Udp,
+            _ => return Err(io::Error::new(ErrorKind::InvalidData, "invalid transport")),
+        };
+        let direction = match direction {
+            0 => Direction::Inbound,
+            1 => Direction::Outbound,
+            _ => return Err(io::Error::new(ErrorKind::InvalidData, "invalid direction")),
+        };
+        let ip = match version {
+            4 => IpAddr::V4(Ipv4Addr::from(self.read_array::<4>()?)),
+            6 => IpAddr::V6(Ipv6Addr::from(self.read_array::<16>()?)),
+            _ => return Err(io::Error::new(ErrorKind::InvalidData, "invalid IP version")),
+        };
+
+        let port = u16::from_le_bytes(self.read_array()?);
+        let len = u32::from_le_bytes(self.read_array()?) as usize;
+        if len > MAX_CAPTURE_FRAME_SIZE {
+            return Err(io::Error::new(ErrorKind::InvalidData, "frame too large"));
+        }
+
+        let mut frame = vec![0; len];
+        self.inner.read_exact(&mut frame)?;
+
+        Ok(Some(CaptureRecord {
+            timestamp,
+            transport,
+            direction,
+            peer: SocketAddr::new(ip, port),
+            frame,
+        }))
+    }
+
+    fn read_array<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut buffer = [0; N];
+        self.inner.read_exact(&mut buffer)?;
+        Ok(buffer)
+    }
+}
+
+impl<R: Read> Iterator for CaptureReader<R> {
+    type Item = io::Result<CaptureRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
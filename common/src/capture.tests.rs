@@ -0,0 +1,68 @@
+use super::*;
+
+#[test]
+fn write_read_test() {
+    let records = vec![
+        CaptureRecord::new(
+            Transport::Tcp,
+            Direction::Inbound,
+            "127.0.0.1:5000".parse().unwrap(),
+            b"\nDBA30013\"NULL\"0001L0#1234[]\r",
+        ),
+        CaptureRecord::new(Transport::Udp, Direction::Outbound, "[::1]:6000".parse().unwrap(), b""),
+    ];
+
+    let mut writer = CaptureWriter::new(Vec::new()).unwrap();
+    for record in &records {
+        writer.write(record).unwrap();
+    }
+
+    let reader = CaptureReader::new(writer.inner.as_slice()).unwrap();
+    let read = reader.collect::<io::Result<Vec<_>>>().unwrap();
+    assert_eq!(records.len(), read.len());
+    for (expected, actual) in records.iter().zip(&read) {
+        assert_eq!(expected.transport, actual.transport);
+        assert_eq!(expected.direction, actual.direction);
+        assert_eq!(expected.peer, actual.peer);
+        assert_eq!(expected.frame, actual.frame);
+        assert_eq!(
+            expected.timestamp.unix_timestamp_nanos() / 1_000,
+            actual.timestamp.unix_timestamp_nanos() / 1_000
+        );
+    }
+}
+
+#[test]
+fn invalid_capture_test() {
+    assert!(CaptureReader::new(b"PCAPFILE".as_slice()).is_err());
+
+    let mut writer = CaptureWriter::new(Vec::new()).unwrap();
+    writer
+        .write(&CaptureRecord::new(
+            Transport::Udp,
+            Direction::Inbound,
+            "127.0.0.1:1".parse().unwrap(),
+            b"frame",
+        ))
+        .unwrap();
+    let truncated = &writer.inner[..writer.inner.len() - 2];
+    let mut reader = CaptureReader::new(truncated).unwrap();
+    assert!(reader.next().unwrap().is_err());
+}
+
+#[test]
+fn frame_size_limit_test() {
+    let peer = "127.0.0.1:1".parse().unwrap();
+    let mut writer = CaptureWriter::new(Vec::new()).unwrap();
+    let oversized = CaptureRecord::new(Transport::Tcp, Direction::Inbound, peer, &vec![0; MAX_CAPTURE_FRAME_SIZE + 1]);
+    assert_eq!(ErrorKind::InvalidInput, writer.write(&oversized).unwrap_err().kind());
+
+    // A corrupted length must not allocate the declared size.
+    writer
+        .write(&CaptureRecord::new(Transport::Tcp, Direction::Inbound, peer, b"frame"))
+        .unwrap();
+    let len_at = writer.inner.len() - 9;
+    writer.inner[len_at..len_at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+    let mut reader = CaptureReader::new(writer.inner.as_slice()).unwrap();
+    assert_eq!(ErrorKind::InvalidData, reader.next().unwrap().unwrap_err().kind());
+}
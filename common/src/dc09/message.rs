@@ -1,5 +1,6 @@
 use std::{fmt::Display, str};
-use time::{OffsetDateTime, macros::format_description};
+use time::format_description::BorrowedFormatItem;
+use time::{OffsetDateTime, PrimitiveDateTime, macros::format_description};
 
 use super::{DC09Error, calculate_crc, encrypt, parse_dc09};
 
@@ -7,8 +8,11 @@ use super::{DC09Error, calculate_crc, encrypt, parse_dc09};
 #[path = "./message.tests.rs"]
 mod message_tests;
 
+/// Format of the DC09 message timestamp (always UTC).
+const TIMESTAMP_FORMAT: &[BorrowedFormatItem<'_>] = format_description!("[hour]:[minute]:[second],[month]-[day]-[year]");
+
 /// Represents a DC09 message.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct DC09Message {
     pub token: String,
     pub sequence: u16,
@@ -55,11 +59,18 @@ impl DC09Message {
 
     // Adds UTC timestamp to the DC09 message.
     pub fn with_timestamp(mut self, timestamp: OffsetDateTime) -> Self {
-        let format = format_description!("[hour]:[minute]:[second],[month]-[day]-[year]");
-        self.timestamp = Some(timestamp.format(&format).expect("Failed to format timestamp"));
+        self.timestamp = Some(timestamp.format(TIMESTAMP_FORMAT).expect("Failed to format timestamp"));
         self
     }
 
+    /// Returns UTC timestamp of the DC09 message, `None` if it is missing or invalid.
+    pub fn parsed_timestamp(&self) -> Option<OffsetDateTime> {
+        let timestamp = self.timestamp.as_deref()?;
+        PrimitiveDateTime::parse(timestamp, TIMESTAMP_FORMAT)
+            .ok()
+            .map(PrimitiveDateTime::assume_utc)
+    }
+
     /// Adds Receiver to the DC09 message.\
     /// **Note** that it should contain `R` as a prefix.
     pub fn with_receiver(mut self, receiver: Option<String>) -> Self {
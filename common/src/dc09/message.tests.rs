@@ -1,4 +1,5 @@
 use super::*;
+use crate::dc09::{DC09Error, calculate_crc, parse_dc09_with_keys};
 
 #[test]
 fn to_string_test() {
@@ -54,3 +55,60 @@ fn encryption_test() {
 
     assert_eq!(msg, decrypted);
 }
+
+#[test]
+fn parse_with_keys_test() {
+    let key = "aaaaaaaaaaaaaaaabbbbbbbbbbbbbbbb";
+    let wrong_keys = [
+        "0000000000000000",
+        "111111111111111111111111",
+        "cccccccccccccccccccccccccccccccc",
+    ];
+    let msg = DC09Message::new(
+        "*SIA-DCS".to_owned(),
+        "1234".to_owned(),
+        1,
+        Some("#1234|NRR|Atest".to_owned()),
+    )
+    .with_line_prefix(Some("L0".to_owned()));
+    let encrypted = msg.to_encrypted(key).unwrap();
+
+    let (decrypted, index) = parse_dc09_with_keys(&encrypted, &[wrong_keys[0], wrong_keys[1], key]).unwrap();
+    assert_eq!(msg, decrypted);
+    assert_eq!(Some(2), index);
+
+    let error = parse_dc09_with_keys(&encrypted, &wrong_keys).unwrap_err();
+    assert!(matches!(error, DC09Error::DecryptError), "{error}");
+
+    let plain = DC09Message::new("NULL".to_owned(), "1234".to_owned(), 1, None).with_line_prefix(Some("L0".to_owned()));
+    let (parsed, index) = parse_dc09_with_keys(&plain.to_string(), &[key]).unwrap();
+    assert_eq!(plain, parsed);
+    assert_eq!(None, index);
+}
+
+#[test]
+fn empty_encrypted_payload_test() {
+    let body = "\"*SIA-DCS\"0001#1234";
+    let frame = format!("\n{:04X}{:04X}{body}\r", calculate_crc(body), body.len());
+
+    let error = DC09Message::try_from(&frame, Some("aaaaaaaaaaaaaaaa")).unwrap_err();
+    assert!(matches!(error, DC09Error::ParsePayloadError), "{error}");
+
+    let error = parse_dc09_with_keys(&frame, &[]).unwrap_err();
+    assert!(matches!(error, DC09Error::ParsePayloadError), "{error}");
+}
+
+#[test]
+fn parsed_timestamp_test() {
+    let timestamp = time::macros::datetime!(2024-03-15 10:20:30 UTC);
+    let msg = DC09Message::new("NULL".to_owned(), "1234".to_owned(), 1, None).with_timestamp(timestamp);
+
+    assert_eq!(Some("10:20:30,03-15-2024"), msg.timestamp.as_deref());
+    assert_eq!(Some(timestamp), msg.parsed_timestamp());
+
+    let invalid = DC09Message {
+        timestamp: Some("invalid".to_owned()),
+        ..msg
+    };
+    assert_eq!(None, invalid.parsed_timestamp());
+}
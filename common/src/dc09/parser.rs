@@ -41,6 +41,10 @@ pub enum DC09Error {
     /// Invalid account number for received DC09 message.
     #[error("invalid account number")]
     InvalidAccountNumber,
+
+    /// Received DC09 frame exceeds the maximum allowed size.
+    #[error("DC09 frame too large")]
+    FrameTooLarge,
 }
 
 impl DC09Error {
@@ -54,6 +58,7 @@ impl DC09Error {
             DC09Error::InvalidCrc => "InvalidCrc",
             DC09Error::InvalidSequenceNumber => "InvalidSequenceNumber",
             DC09Error::InvalidAccountNumber => "InvalidAccountNumber",
+            DC09Error::FrameTooLarge => "FrameTooLarge",
         }
     }
 }
@@ -68,6 +73,30 @@ pub fn parse_dc09_account_name(input: &str) -> Result<String, DC09Error> {
     Ok(header.account.to_owned())
 }
 
+/// Parses an account name and a sequence number from the DC09 message.\
+/// **Note** that this function does not validate CRC of the message.
+pub fn parse_dc09_account_and_sequence(input: &str) -> Result<(String, u16), DC09Error> {
+    let Ok((_, header)) = parse_dc09_header(input) else {
+        return Err(DC09Error::ParseHeaderError);
+    };
+
+    Ok((header.account.to_owned(), header.sequence))
+}
+
+/// Parses a receiver number, a line prefix (both with their `R` and `L` prefixes) and an account name from the DC09 message.\
+/// **Note** that this function does not validate CRC of the message.
+pub fn parse_dc09_receiver_line_and_account(input: &str) -> Result<(Option<String>, Option<String>, String), DC09Error> {
+    let Ok((_, header)) = parse_dc09_header(input) else {
+        return Err(DC09Error::ParseHeaderError);
+    };
+
+    Ok((
+        header.receiver.map(|r| format!("R{r}")),
+        header.line_prefix.map(|l| format!("L{l}")),
+        header.account.to_owned(),
+    ))
+}
+
 /// Parses a complete DC09 message.\
 /// Format example: `3BAC0029"SIA-DCS"0002#0123[#0123|Nti20:50:26RP99]`
 pub fn parse_dc09(input: &str, key: Option<&str>) -> Result<DC09Message, DC09Error> {
@@ -80,7 +109,7 @@ pub fn parse_dc09(input: &str, key: Option<&str>) -> Result<DC09Message, DC09Err
     let is_encrypted = header.token.chars().next().is_some_and(|ch| ch == '*');
     let decrypted = if is_encrypted {
         if let Some(key) = key {
-            decrypt(&payload[1..payload.len() - 1], key.as_bytes())
+            decrypt(encrypted_payload(payload)?, key.as_bytes())
         } else {
             return Err(DC09Error::DecryptError);
         }
@@ -114,6 +143,42 @@ pub fn parse_dc09(input: &str, key: Option<&str>) -> Result<DC09Message, DC09Err
     }
 }
 
+/// Parses a complete DC09 message trying the `keys` in order until one decrypts a payload with a valid structure.\
+/// Returns the message and the index of the key that decrypted it, `None` if the message is not encrypted.\
+/// **Note** that a wrong key is detected only by the structure of the decrypted payload, so the check is probabilistic.
+pub fn parse_dc09_with_keys(input: &str, keys: &[&str]) -> Result<(DC09Message, Option<usize>), DC09Error> {
+    let Ok((payload, header)) = parse_dc09_header(input) else {
+        return Err(DC09Error::ParseHeaderError);
+    };
+
+    validate(input, header.len, header.crc)?;
+
+    if !header.token.starts_with('*') {
+        return parse_dc09(input, None).map(|message| (message, None));
+    }
+
+    let encrypted = encrypted_payload(payload)?;
+    let index = keys
+        .iter()
+        .position(|key| decrypt(encrypted, key.as_bytes()).is_some_and(|d| is_decrypted_payload_valid(&d)))
+        .ok_or(DC09Error::DecryptError)?;
+
+    parse_dc09(input, Some(keys[index])).map(|message| (message, Some(index)))
+}
+
+/// Returns the encrypted payload without the enclosing brackets.
+fn encrypted_payload(payload: &str) -> Result<&str, DC09Error> {
+    payload
+        .get(1..payload.len().saturating_sub(1))
+        .ok_or(DC09Error::ParsePayloadError)
+}
+
+/// Returns `true` if the decrypted payload consists of printable characters only and has a valid structure.
+fn is_decrypted_payload_valid(decrypted: &str) -> bool {
+    let printable = decrypted.bytes().all(|b| b == b'\r' || (b' '..=b'~').contains(&b));
+    printable && parse_dc09_payload(decrypted).is_ok_and(|(rest, _)| rest.is_empty())
+}
+
 /// Validates account prefix (receiver line number) and returns `true` on success.
 pub fn is_account_prefix_valid(input: &str) -> bool {
     if let Ok((_, line)) = parse_account_prefix(input)
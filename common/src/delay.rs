@@ -0,0 +1,132 @@
+use rand::RngExt;
+use serde::{Deserialize, Serialize};
+use std::{fs::File, io::Read, path::Path, str::FromStr, time::Duration};
+
+#[cfg(test)]
+#[path = "./delay.tests.rs"]
+mod delay_tests;
+
+/// Holds a single histogram bucket: delay in milliseconds and its relative weight.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DelayBucket {
+    pub delay: u64,
+    pub weight: u32,
+}
+
+/// Latency distribution used to delay responses (all values in milliseconds).
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum DelayProfile {
+    Fixed { delay: u64 },
+    Uniform { min: u64, max: u64 },
+    Normal { mean: u64, std_dev: u64 },
+    Histogram { buckets: Vec<DelayBucket> },
+}
+
+impl DelayProfile {
+    /// Returns a random delay drawn from the profile distribution.
+    pub fn sample(&self) -> Duration {
+        let mut rng = rand::rng();
+        let millis = match self {
+            Self::Fixed { delay } => *delay,
+            Self::Uniform { min, max } => rng.random_range(*min..=*max),
+            Self::Normal { mean, std_dev } => {
+                // Box-Muller transform, negative samples are clamped to zero.
+                let u1 = 1.0 - rng.random::<f64>();
+                let u2 = rng.random::<f64>();
+                let z = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+                (*mean as f64 + z * *std_dev as f64).max(0.0).round() as u64
+            },
+            Self::Histogram { buckets } => {
+                let total = buckets.iter().map(|b| u64::from(b.weight)).sum::<u64>();
+                let mut pick = rng.random_range(0..total.max(1));
+                buckets
+                    .iter()
+                    .find(|b| {
+                        let weight = u64::from(b.weight);
+                        if pick < weight {
+                            true
+                        } else {
+                            pick -= weight;
+                            false
+                        }
+                    })
+                    .map_or(0, |b| b.delay)
+            },
+        };
+
+        Duration::from_millis(millis)
+    }
+
+    /// Checks whether [`DelayProfile`] contains valid and meaningful data.
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Self::Uniform { min, max } if min > max => Err("uniform delay: min must not be greater than max".to_owned()),
+            Self::Histogram { buckets } if buckets.iter().all(|b| b.weight == 0) => {
+                Err("histogram delay: at least one bucket with non-zero weight is required".to_owned())
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+impl FromStr for DelayProfile {
+    type Err = String;
+
+    /// Parses delay profile in one of the formats: `fixed:MS`, `uniform:MIN:MAX`, `normal:MEAN:STD_DEV`
+    /// or `histogram:FILE` (JSON array of `{"delay": MS, "weight": N}` objects).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, params) = s.split_once(':').unwrap_or((s, ""));
+        let profile = match kind.to_ascii_lowercase().as_str() {
+            "fixed" => Self::Fixed {
+                delay: parse_millis(params)?,
+            },
+            "uniform" => {
+                let (min, max) = params.split_once(':').ok_or("uniform delay expects MIN:MAX")?;
+                Self::Uniform {
+                    min: parse_millis(min)?,
+                    max: parse_millis(max)?,
+                }
+            },
+            "normal" => {
+                let (mean, std_dev) = params.split_once(':').ok_or("normal delay expects MEAN:STD_DEV")?;
+                Self::Normal {
+                    mean: parse_millis(mean)?,
+                    std_dev: parse_millis(std_dev)?,
+                }
+            },
+            "histogram" => Self::Histogram {
+                buckets: load_histogram(params)?,
+            },
+            other => {
+                return Err(format!(
+                    "unknown delay profile '{other}', expected: fixed, uniform, normal, histogram"
+                ));
+            },
+        };
+
+        profile.validate()?;
+        Ok(profile)
+    }
+}
+
+fn parse_millis(s: &str) -> Result<u64, String> {
+    s.trim()
+        .parse::<u64>()
+        .map_err(|_| format!("invalid delay '{s}', expected milliseconds"))
+}
+
+fn load_histogram(s: &str) -> Result<Vec<DelayBucket>, String> {
+    let path = Path::new(s);
+    if !path.exists() {
+        return Err("the provided histogram file does not exist".to_owned());
+    }
+
+    let mut histogram = String::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut histogram))
+        .map_err(|e| format!("unable to read the histogram file: {e}"))?;
+
+    serde_json::from_str::<Vec<DelayBucket>>(&histogram)
+        .map_err(|_| "unable to deserialize the provided file into histogram buckets".to_owned())
+}
@@ -0,0 +1,33 @@
+use super::*;
+
+#[test]
+fn parse_profiles_test() {
+    assert_eq!(DelayProfile::Fixed { delay: 200 }, "fixed:200".parse().unwrap());
+    assert_eq!(DelayProfile::Uniform { min: 10, max: 50 }, "uniform:10:50".parse().unwrap());
+    assert_eq!(
+        DelayProfile::Normal { mean: 100, std_dev: 20 },
+        "normal:100:20".parse().unwrap()
+    );
+    assert!("uniform:50:10".parse::<DelayProfile>().is_err());
+    assert!("random:10".parse::<DelayProfile>().is_err());
+}
+
+#[test]
+fn sample_within_bounds_test() {
+    let profile = DelayProfile::Uniform { min: 10, max: 20 };
+    for _ in 0..100 {
+        let delay = profile.sample();
+        assert!(delay >= Duration::from_millis(10) && delay <= Duration::from_millis(20));
+    }
+
+    let profile = DelayProfile::Histogram {
+        buckets: vec![DelayBucket { delay: 5, weight: 0 }, DelayBucket { delay: 30, weight: 1 }],
+    };
+    assert_eq!(Duration::from_millis(30), profile.sample());
+}
+
+#[test]
+fn deserialize_profile_test() {
+    let profile = serde_json::from_str::<DelayProfile>(r#"{"type":"normal","mean":100,"std_dev":0}"#).unwrap();
+    assert_eq!(Duration::from_millis(100), profile.sample());
+}
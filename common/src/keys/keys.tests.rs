@@ -0,0 +1,226 @@
+use super::source::KeySource;
+use super::*;
+use crate::dc09::DC09Message;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+const DEFAULT_KEY: &str = "0000000000000000";
+const ACCOUNT_KEY: &str = "1111111111111111";
+const LINE_KEY: &str = "2222222222222222";
+
+fn query(account: &str, receiver: Option<&str>, line: Option<&str>) -> KeyQuery {
+    KeyQuery::new(account, receiver, line)
+}
+
+#[test]
+fn parse_key_set_test() {
+    let keys = KeySet::parse(&format!(
+        "# default key\n* {DEFAULT_KEY}\n1234 {ACCOUNT_KEY}\nR1 L2 1234 {LINE_KEY} # line key\n"
+    ))
+    .unwrap();
+
+    assert_eq!(3, keys.len());
+    assert_eq!(
+        Some(LINE_KEY),
+        keys.get(&query("1234", Some("R1"), Some("L2"))).map(SecretKey::expose)
+    );
+    assert_eq!(
+        Some(ACCOUNT_KEY),
+        keys.get(&query("1234", Some("R1"), Some("L3"))).map(SecretKey::expose)
+    );
+    assert_eq!(Some(ACCOUNT_KEY), keys.get(&query("1234", None, None)).map(SecretKey::expose));
+    assert_eq!(
+        Some(DEFAULT_KEY),
+        keys.get(&query("5678", Some("R1"), Some("L2"))).map(SecretKey::expose)
+    );
+    assert_eq!(None, KeySet::default().get(&query("1234", None, None)));
+}
+
+#[test]
+fn invalid_key_set_test() {
+    let error = KeySet::parse(&format!("1234 {ACCOUNT_KEY}\n1234 short")).unwrap_err();
+    assert_eq!("line 2: key length must be 16, 24 or 32 bytes", error);
+
+    let error = KeySet::parse(ACCOUNT_KEY).unwrap_err();
+    assert!(error.starts_with("line 1: entry must be"), "{error}");
+
+    let error = KeySet::parse(&format!("R1 R2 1234 {ACCOUNT_KEY}")).unwrap_err();
+    assert_eq!("line 1: duplicated selector 'R2'", error);
+}
+
+#[test]
+fn account_selector_test() {
+    let selector = "R1 L2 1234".parse::<AccountSelector>().unwrap();
+    assert_eq!(AccountSelector::new(Some("1234"), Some("R1"), Some("L2")), selector);
+    assert_eq!("R1 L2 1234", selector.to_string());
+    assert_eq!("L2 *", AccountSelector::new(None, None, Some("L2")).to_string());
+
+    assert_eq!(Some(7), selector.rank(&query("1234", Some("R1"), Some("L2"))));
+    assert_eq!(None, selector.rank(&query("1234", Some("R1"), Some("L3"))));
+    assert_eq!(None, selector.rank(&query("1234", None, None)));
+    assert_eq!(
+        Some(2),
+        "R1 *"
+            .parse::<AccountSelector>()
+            .unwrap()
+            .rank(&query("5678", Some("R1"), None))
+    );
+    assert_eq!(Some(0), AccountSelector::default().rank(&query("5678", None, None)));
+
+    assert!("R1 L2".parse::<AccountSelector>().unwrap().account.is_none());
+    assert_eq!("invalid selector 'RX'", "RX 1234".parse::<AccountSelector>().unwrap_err());
+    assert_eq!(
+        "invalid selector 'L1234567'",
+        "L1234567 1234".parse::<AccountSelector>().unwrap_err()
+    );
+    assert_eq!(
+        "duplicated selector '5678'",
+        "1234 5678".parse::<AccountSelector>().unwrap_err()
+    );
+}
+
+#[test]
+fn account_selector_candidates_test() {
+    let candidates = AccountSelector::candidates(&query("1234", Some("R1"), Some("L2")))
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec!["R1 L2 1234", "R1 1234", "L2 1234", "1234", "R1 L2 *", "R1 *", "L2 *", "*"],
+        candidates
+    );
+
+    let candidates = AccountSelector::candidates(&query("1234", None, Some("L2")))
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(vec!["L2 1234", "1234", "L2 *", "*"], candidates);
+}
+
+#[test]
+fn query_from_frame_test() {
+    let message = DC09Message::new("NULL".to_owned(), "1234".to_owned(), 1, None)
+        .with_receiver(Some("R12".to_owned()))
+        .with_line_prefix(Some("L3".to_owned()));
+
+    assert_eq!(
+        query("1234", Some("R12"), Some("L3")),
+        KeyQuery::from_frame(&message.to_string()).unwrap()
+    );
+    assert!(KeyQuery::from_frame("invalid").is_err());
+}
+
+#[test]
+fn load_env_test() {
+    let variables = [
+        ("DC09_KEY", DEFAULT_KEY),
+        ("DC09_KEY_1234", ACCOUNT_KEY),
+        ("DC09_KEY_R1_L2_1234", LINE_KEY),
+        ("OTHER_KEY_1234", LINE_KEY),
+    ]
+    .map(|(n, v)| (n.to_owned(), v.to_owned()));
+
+    let keys = source::load_env("DC09_KEY", variables.into_iter()).unwrap();
+    assert_eq!(3, keys.len());
+    assert_eq!(
+        Some(LINE_KEY),
+        keys.get(&query("1234", Some("R1"), Some("L2"))).map(SecretKey::expose)
+    );
+    assert_eq!(Some(ACCOUNT_KEY), keys.get(&query("1234", None, None)).map(SecretKey::expose));
+    assert_eq!(Some(DEFAULT_KEY), keys.get(&query("5678", None, None)).map(SecretKey::expose));
+
+    let variables = [
+        ("DC09_1234", ACCOUNT_KEY),
+        ("DC09_LOG_FORMAT", "json"),
+        ("DC09_UDP_WORKERS", "4"),
+        ("DC09_R1_L2_1234_5678", "other"),
+    ]
+    .map(|(n, v)| (n.to_owned(), v.to_owned()));
+    let keys = source::load_env("DC09", variables.into_iter()).unwrap();
+    assert_eq!(1, keys.len());
+    assert_eq!(Some(ACCOUNT_KEY), keys.get(&query("1234", None, None)).map(SecretKey::expose));
+
+    let variables = [("DC09_KEY_1234".to_owned(), "short".to_owned())];
+    let error = source::load_env("DC09_KEY", variables.into_iter()).unwrap_err();
+    assert!(error.starts_with("DC09_KEY_1234: "), "{error}");
+}
+
+#[test]
+fn parse_key_source_test() {
+    assert_eq!(KeySource::Env("DC09_KEY".to_owned()), "env:DC09_KEY".parse().unwrap());
+    assert_eq!(KeySource::File("keys.txt".into()), "keys.txt".parse().unwrap());
+    assert_eq!(
+        KeySource::Http("http://127.0.0.1:8200/keys".to_owned()),
+        "http://127.0.0.1:8200/keys".parse().unwrap()
+    );
+    assert_eq!("env:DC09_KEY", KeySource::Env("DC09_KEY".to_owned()).to_string());
+
+    assert!("env:".parse::<KeySource>().is_err());
+    assert!("https://vault/keys".parse::<KeySource>().is_err());
+    assert!("http:///keys".parse::<KeySource>().is_err());
+}
+
+#[tokio::test]
+async fn http_source_test() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/keys", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        for body in [format!("1234 {ACCOUNT_KEY}\n"), format!("1234 {LINE_KEY}\n")] {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0; 1024];
+            let n = stream.read(&mut request).await.unwrap();
+            assert!(String::from_utf8_lossy(&request[..n]).starts_with("GET /keys HTTP/1.0\r\n"));
+
+            let response = format!("HTTP/1.0 200 OK\r\nContent-Length: {}\r\n\r\n{body}", body.len());
+            stream.write_all(response.as_bytes()).await.unwrap();
+        }
+    });
+
+    let provider = CachedKeyProvider::load(vec![url.parse().unwrap()]).await.unwrap();
+    assert_eq!(Some(SecretKey::from(ACCOUNT_KEY)), provider.key(&query("1234", None, None)));
+
+    assert_eq!(Ok(1), provider.refresh().await);
+    assert_eq!(Some(SecretKey::from(LINE_KEY)), provider.key(&query("1234", None, None)));
+
+    // The stub is gone, so the refresh fails and the cached keys keep serving.
+    assert!(provider.refresh().await.is_err());
+    assert_eq!(Some(SecretKey::from(LINE_KEY)), provider.key(&query("1234", None, None)));
+}
+
+#[tokio::test]
+async fn http_source_too_large_test() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/keys", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut request = [0; 1024];
+        assert!(stream.read(&mut request).await.unwrap() > 0);
+
+        let body = format!("1234 {ACCOUNT_KEY}\n").repeat(64 * 1024);
+        let response = format!("HTTP/1.0 200 OK\r\nContent-Length: {}\r\n\r\n{body}", body.len());
+        let _ = stream.write_all(response.as_bytes()).await;
+    });
+
+    let error = CachedKeyProvider::load(vec![url.parse().unwrap()]).await.err().unwrap();
+    assert!(error.contains("response too large"), "{error}");
+}
+
+#[tokio::test]
+async fn cached_provider_precedence_test() {
+    let directory = std::env::temp_dir();
+    let first = directory.join(format!("keys-first-{}.txt", std::process::id()));
+    let second = directory.join(format!("keys-second-{}.txt", std::process::id()));
+    std::fs::write(&first, format!("1234 {LINE_KEY}\n")).unwrap();
+    std::fs::write(&second, format!("* {DEFAULT_KEY}\n1234 {ACCOUNT_KEY}\n")).unwrap();
+
+    let sources = vec![KeySource::File(first.clone()), KeySource::File(second.clone())];
+    let provider = CachedKeyProvider::load(sources).await.unwrap();
+    std::fs::remove_file(&first).unwrap();
+    std::fs::remove_file(&second).unwrap();
+
+    assert_eq!(3, provider.keys().len());
+    assert_eq!(Some(SecretKey::from(LINE_KEY)), provider.key(&query("1234", None, None)));
+    assert_eq!(Some(SecretKey::from(DEFAULT_KEY)), provider.key(&query("5678", None, None)));
+
+    let error = provider.refresh().await.unwrap_err();
+    assert!(error.starts_with(&first.display().to_string()), "{error}");
+    assert_eq!(3, provider.keys().len());
+}
@@ -0,0 +1,331 @@
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::dc09::{DC09Error, DC09Message, is_account_prefix_valid, is_receiver_valid, parse_dc09_receiver_line_and_account};
+use crate::utils::parse_key;
+
+pub use self::secret::SecretKey;
+pub use self::source::KeySource;
+
+mod secret;
+mod source;
+
+#[cfg(test)]
+#[path = "./keys.tests.rs"]
+mod keys_tests;
+
+/// Default interval of refreshing keys from the key sources, in seconds.
+pub const DEFAULT_KEY_REFRESH: u64 = 60;
+
+/// Identifies the panel whose key is looked up.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct KeyQuery {
+    pub account: String,
+    /// Receiver number with the `R` prefix.
+    pub receiver: Option<String>,
+    /// Receiver line number (account prefix) with the `L` prefix.
+    pub line: Option<String>,
+}
+
+impl KeyQuery {
+    /// Creates new [`KeyQuery`] instance.
+    pub fn new(account: &str, receiver: Option<&str>, line: Option<&str>) -> Self {
+        Self {
+            account: account.to_owned(),
+            receiver: receiver.map(str::to_owned),
+            line: line.map(str::to_owned),
+        }
+    }
+
+    /// Creates [`KeyQuery`] identifying sender of the `message`.
+    pub fn from_message(message: &DC09Message) -> Self {
+        Self::new(&message.account, message.receiver.as_deref(), message.line_prefix.as_deref())
+    }
+
+    /// Creates [`KeyQuery`] from the unencrypted header of the DC09 frame.\
+    /// **Note** that this function does not validate CRC of the frame.
+    pub fn from_frame(frame: &str) -> Result<Self, DC09Error> {
+        let (receiver, line, account) = parse_dc09_receiver_line_and_account(frame)?;
+        Ok(Self { account, receiver, line })
+    }
+}
+
+/// Provides keys to decrypt and encrypt DC09 messages.
+pub trait KeyProvider: Send + Sync {
+    /// Returns key for messages of the panel identified by the `query`, `None` if they are not encrypted.
+    fn key(&self, query: &KeyQuery) -> Option<SecretKey>;
+
+    /// Returns all keys accepted for messages of the panel identified by the `query`, the current key first.\
+    /// **Note** that keys after the first one are accepted only while the panel switches to the current key.
+    fn keys(&self, query: &KeyQuery) -> Vec<SecretKey> {
+        self.key(query).into_iter().collect()
+    }
+}
+
+/// Selects panels by receiver number, line prefix and account.\
+/// **Note** that `None` matches any value.
+#[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AccountSelector {
+    /// Receiver number with the `R` prefix.
+    pub receiver: Option<String>,
+    /// Receiver line number (account prefix) with the `L` prefix.
+    pub line: Option<String>,
+    pub account: Option<String>,
+}
+
+impl AccountSelector {
+    /// Creates new [`AccountSelector`] instance.
+    pub fn new(account: Option<&str>, receiver: Option<&str>, line: Option<&str>) -> Self {
+        Self {
+            receiver: receiver.map(str::to_owned),
+            line: line.map(str::to_owned),
+            account: account.map(str::to_owned),
+        }
+    }
+
+    /// Parses selector from the `tokens`: `R<receiver>`, `L<line>` and the account, `*` matches any account.
+    pub fn from_tokens<'a>(tokens: impl IntoIterator<Item = &'a str>) -> Result<Self, String> {
+        let mut selector = Self::default();
+        let mut account = false;
+        for token in tokens {
+            let slot = match token.chars().next() {
+                Some('R') => &mut selector.receiver,
+                Some('L') => &mut selector.line,
+                _ if account => return Err(format!("duplicated selector '{token}'")),
+                _ => {
+                    account = true;
+                    &mut selector.account
+                },
+            };
+
+            if slot.is_some() {
+                return Err(format!("duplicated selector '{token}'"));
+            }
+
+            let valid = match token.chars().next() {
+                Some('R') => is_receiver_valid(token),
+                Some('L') => is_account_prefix_valid(token),
+                _ => token == "*" || (!token.is_empty() && token.chars().all(char::is_alphanumeric)),
+            };
+            if !valid {
+                return Err(format!("invalid selector '{token}'"));
+            }
+
+            if token != "*" {
+                *slot = Some(token.to_owned());
+            }
+        }
+
+        Ok(selector)
+    }
+
+    /// Returns rank of the selector for the `query`, `None` if it does not match.\
+    /// **Note** that a matching account outranks matching receiver and line, and a matching receiver outranks the line.
+    pub fn rank(&self, query: &KeyQuery) -> Option<u8> {
+        let selectors = [
+            (self.account.as_deref(), Some(query.account.as_str()), 4),
+            (self.receiver.as_deref(), query.receiver.as_deref(), 2),
+            (self.line.as_deref(), query.line.as_deref(), 1),
+        ];
+
+        let mut rank = 0;
+        for (selector, value, weight) in selectors {
+            match selector {
+                None => {},
+                Some(selector) if Some(selector) == value => rank += weight,
+                Some(_) => return None,
+            }
+        }
+
+        Some(rank)
+    }
+
+    /// Returns all selectors matching the `query`, from the highest to the lowest rank.
+    pub fn candidates(query: &KeyQuery) -> impl Iterator<Item = Self> + '_ {
+        (0..8_u8).rev().filter_map(move |rank| {
+            Some(Self::new(
+                pick(Some(&query.account), rank & 4 != 0)?,
+                pick(query.receiver.as_deref(), rank & 2 != 0)?,
+                pick(query.line.as_deref(), rank & 1 != 0)?,
+            ))
+        })
+    }
+}
+
+/// Returns `value` if it is `selected`, `Some(None)` (any value) otherwise and `None` if a missing value is `selected`.
+fn pick(value: Option<&str>, selected: bool) -> Option<Option<&str>> {
+    match (value, selected) {
+        (_, false) => Some(None),
+        (Some(value), true) => Some(Some(value)),
+        (None, true) => None,
+    }
+}
+
+impl FromStr for AccountSelector {
+    type Err = String;
+
+    /// Parses whitespace separated `[R<receiver>] [L<line>] ACCOUNT` selector.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_tokens(s.split_whitespace())
+    }
+}
+
+impl Display for AccountSelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for value in [&self.receiver, &self.line].into_iter().flatten() {
+            write!(f, "{value} ")?;
+        }
+
+        write!(f, "{}", self.account.as_deref().unwrap_or("*"))
+    }
+}
+
+/// Key assigned to the panels matching the selector.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyEntry {
+    pub selector: AccountSelector,
+    pub key: SecretKey,
+}
+
+impl KeyEntry {
+    /// Parses entry from the selector `tokens` and the `key`.
+    fn parse<'a>(tokens: impl IntoIterator<Item = &'a str>, key: &str) -> Result<Self, String> {
+        let key = parse_key(key)?;
+        let selector = AccountSelector::from_tokens(tokens)?;
+        Ok(Self { selector, key })
+    }
+}
+
+/// Set of keys loaded from the key sources.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct KeySet {
+    entries: Vec<KeyEntry>,
+}
+
+impl KeySet {
+    /// Parses keys in the key file format: one `[R<receiver>] [L<line>] ACCOUNT KEY` entry per line,
+    /// `*` matches any account and `#` starts a comment.
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let mut entries = Vec::new();
+        for (index, line) in content.lines().enumerate() {
+            let line = line.split_once('#').map_or(line, |(l, _)| l).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut tokens = line.split_whitespace().collect::<Vec<_>>();
+            let key = tokens.pop().unwrap_or_default();
+            if tokens.is_empty() {
+                return Err(format!(
+                    "line {}: entry must be in the [R<receiver>] [L<line>] ACCOUNT KEY format",
+                    index + 1
+                ));
+            }
+
+            let entry = KeyEntry::parse(tokens, key).map_err(|e| format!("line {}: {e}", index + 1))?;
+            entries.push(entry);
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// Returns number of entries in the set.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the set has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Appends entries of the `other` set, they are used only when no entry of this set is ranked higher.
+    pub fn extend(&mut self, other: KeySet) {
+        self.entries.extend(other.entries);
+    }
+
+    /// Returns key of the highest ranked entry matching the `query`, the first one wins for equally ranked entries.
+    pub fn get(&self, query: &KeyQuery) -> Option<&SecretKey> {
+        let mut best: Option<(u8, &KeyEntry)> = None;
+        for entry in &self.entries {
+            if let Some(rank) = entry.selector.rank(query)
+                && best.is_none_or(|(b, _)| rank > b)
+            {
+                best = Some((rank, entry));
+            }
+        }
+
+        best.map(|(_, entry)| &entry.key)
+    }
+}
+
+impl FromIterator<KeyEntry> for KeySet {
+    fn from_iter<T: IntoIterator<Item = KeyEntry>>(iter: T) -> Self {
+        Self {
+            entries: iter.into_iter().collect(),
+        }
+    }
+}
+
+/// Key provider that caches keys loaded from the key sources until they are refreshed.
+#[derive(Debug)]
+pub struct CachedKeyProvider {
+    sources: Vec<KeySource>,
+    keys: RwLock<Arc<KeySet>>,
+}
+
+impl CachedKeyProvider {
+    /// Creates new [`CachedKeyProvider`] instance with keys loaded from all `sources`.\
+    /// **Note** that keys from the earlier sources take precedence over equally specific keys from the later ones.
+    pub async fn load(sources: Vec<KeySource>) -> Result<Self, String> {
+        let keys = load_sources(&sources).await?;
+        Ok(Self {
+            sources,
+            keys: RwLock::new(Arc::new(keys)),
+        })
+    }
+
+    /// Returns currently cached keys.
+    pub fn keys(&self) -> Arc<KeySet> {
+        Arc::clone(&self.keys.read().expect("lock is not poisoned"))
+    }
+
+    /// Reloads keys from all sources and returns number of loaded entries.\
+    /// **Note** that on error the previously cached keys are kept.
+    pub async fn refresh(&self) -> Result<usize, String> {
+        let keys = load_sources(&self.sources).await?;
+        let len = keys.len();
+        *self.keys.write().expect("lock is not poisoned") = Arc::new(keys);
+        Ok(len)
+    }
+
+    /// Refreshes keys every `interval`, failures are logged and the previous keys keep serving.
+    pub async fn refresh_every(self: Arc<Self>, interval: Duration) {
+        let mut interval = tokio::time::interval(interval);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            match self.refresh().await {
+                Ok(len) => log::debug!("keys refreshed, {len} entries"),
+                Err(e) => log::error!("keys refresh failed, keeping previous keys: {e}"),
+            }
+        }
+    }
+}
+
+impl KeyProvider for CachedKeyProvider {
+    fn key(&self, query: &KeyQuery) -> Option<SecretKey> {
+        self.keys.read().expect("lock is not poisoned").get(query).cloned()
+    }
+}
+
+async fn load_sources(sources: &[KeySource]) -> Result<KeySet, String> {
+    let mut keys = KeySet::default();
+    for source in sources {
+        keys.extend(source.load().await.map_err(|e| format!("{source}: {e}"))?);
+    }
+
+    Ok(keys)
+}
@@ -0,0 +1,99 @@
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{Debug, Display};
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
+
+#[cfg(test)]
+#[path = "./secret.tests.rs"]
+mod secret_tests;
+
+/// Placeholder shown instead of the key.
+const REDACTED: &str = "<redacted>";
+
+/// Encryption key redacted in debug output, display and serialization, and zeroized on drop.\
+/// **Note** that only the final buffer is zeroized, copies made with [`SecretKey::expose`] are not.
+#[derive(Clone, Default)]
+pub struct SecretKey(Zeroizing<String>);
+
+impl SecretKey {
+    /// Creates new [`SecretKey`] instance.
+    pub fn new(key: impl Into<String>) -> Self {
+        Self(Zeroizing::new(key.into()))
+    }
+
+    /// Returns the key in plain text, to be passed to the cipher only.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns `true` if the key is the placeholder serialized instead of keys, e.g. read back from a printed
+    /// configuration.
+    pub fn is_redacted(&self) -> bool {
+        self.expose() == REDACTED
+    }
+}
+
+impl From<String> for SecretKey {
+    fn from(value: String) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<&str> for SecretKey {
+    fn from(value: &str) -> Self {
+        Self::new(value)
+    }
+}
+
+/// Compares keys in constant time, only their lengths may leak through timing.
+impl PartialEq for SecretKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_bytes().ct_eq(other.0.as_bytes()).into()
+    }
+}
+
+impl Eq for SecretKey {}
+
+impl Debug for SecretKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SecretKey({REDACTED})")
+    }
+}
+
+impl Display for SecretKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl Serialize for SecretKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(REDACTED)
+    }
+}
+
+impl<'de> Deserialize<'de> for SecretKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_string(SecretKeyVisitor)
+    }
+}
+
+/// Deserializes the key straight into the zeroized buffer, an owned string is moved without copying.
+struct SecretKeyVisitor;
+
+impl Visitor<'_> for SecretKeyVisitor {
+    type Value = SecretKey;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a key string")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        Ok(SecretKey::new(value))
+    }
+
+    fn visit_string<E: de::Error>(self, value: String) -> Result<Self::Value, E> {
+        Ok(SecretKey::new(value))
+    }
+}
@@ -0,0 +1,42 @@
+use super::*;
+use crate::scenarios::DiallerConfig;
+
+const KEY: &str = "0123456789abcdef";
+
+#[test]
+fn secret_key_redacted_test() {
+    let key = SecretKey::from(KEY);
+    assert_eq!(KEY, key.expose());
+    assert_eq!("SecretKey(<redacted>)", format!("{key:?}"));
+    assert_eq!("<redacted>", key.to_string());
+    assert_eq!("\"<redacted>\"", serde_json::to_string(&key).unwrap());
+    assert!(!key.is_redacted());
+
+    let printed = serde_json::from_str::<SecretKey>(&serde_json::to_string(&key).unwrap()).unwrap();
+    assert!(printed.is_redacted());
+
+    let mut dialler = DiallerConfig::new("1234".to_owned(), 0, false, 1);
+    dialler.key = Some(key);
+    assert!(!format!("{dialler:?}").contains(KEY));
+    assert!(!serde_json::to_string(&dialler).unwrap().contains(KEY));
+}
+
+#[test]
+fn secret_key_deserialize_test() {
+    let key = serde_json::from_str::<SecretKey>(&format!("\"{KEY}\"")).unwrap();
+    assert_eq!(SecretKey::new(KEY), key);
+}
+
+#[test]
+fn secret_key_eq_test() {
+    assert_eq!(SecretKey::from(KEY), SecretKey::from(KEY.to_owned()));
+    assert_ne!(SecretKey::from(KEY), SecretKey::from("0123456789abcdeF"));
+    assert_ne!(SecretKey::from(KEY), SecretKey::from(&KEY[..8]));
+    assert_eq!(SecretKey::default(), SecretKey::from(""));
+}
+
+#[test]
+fn secret_key_deserialize_invalid_test() {
+    assert!(serde_json::from_str::<SecretKey>("16").is_err());
+    assert!(serde_json::from_str::<SecretKey>("null").is_err());
+}
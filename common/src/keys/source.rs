@@ -0,0 +1,190 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use super::{AccountSelector, KeyEntry, KeySet};
+use crate::utils::parse_key;
+
+/// Prefix of the environment variables key source.
+const ENV_PREFIX: &str = "env:";
+
+/// Prefix of the HTTP key service source.
+const HTTP_PREFIX: &str = "http://";
+
+/// Time limit of a single request to the HTTP key service.
+const HTTP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Maximum size of the HTTP key service response.
+const HTTP_MAX_RESPONSE_SIZE: u64 = 1024 * 1024;
+
+/// Source of keys: key file, environment variables or HTTP key service.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum KeySource {
+    /// Key file in the [`KeySet::parse`] format.
+    File(PathBuf),
+    /// Environment variables `PREFIX` (key for any account) and `PREFIX_[R<receiver>_][L<line>_]ACCOUNT`.
+    Env(String),
+    /// URL of the HTTP key service returning keys in the key file format.
+    Http(String),
+}
+
+impl KeySource {
+    /// Loads all keys from the source.
+    pub async fn load(&self) -> Result<KeySet, String> {
+        match self {
+            Self::File(path) => {
+                let content = tokio::fs::read_to_string(path)
+                    .await
+                    .map_err(|e| format!("unable to read the provided key file: {e}"))?;
+                KeySet::parse(&content)
+            },
+            Self::Env(prefix) => load_env(prefix, std::env::vars()),
+            Self::Http(url) => {
+                let content = tokio::time::timeout(HTTP_TIMEOUT, fetch(url))
+                    .await
+                    .map_err(|_| format!("key service did not respond within {HTTP_TIMEOUT:?}"))??;
+                KeySet::parse(&content)
+            },
+        }
+    }
+}
+
+impl FromStr for KeySource {
+    type Err = String;
+
+    /// Parses `env:PREFIX`, `http://HOST[:PORT][/PATH]` or a key file path.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(prefix) = s.strip_prefix(ENV_PREFIX) {
+            if prefix.is_empty() || !prefix.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(format!("invalid environment variables prefix '{prefix}'"));
+            }
+            Ok(Self::Env(prefix.to_owned()))
+        } else if s.starts_with(HTTP_PREFIX) {
+            split_url(s)?;
+            Ok(Self::Http(s.to_owned()))
+        } else if s.contains("://") {
+            Err(format!("unsupported key service URL '{s}', only http:// is supported"))
+        } else if s.is_empty() {
+            Err("key source must not be empty".to_owned())
+        } else {
+            Ok(Self::File(PathBuf::from(s)))
+        }
+    }
+}
+
+impl TryFrom<String> for KeySource {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<KeySource> for String {
+    fn from(value: KeySource) -> Self {
+        value.to_string()
+    }
+}
+
+impl Display for KeySource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::File(path) => write!(f, "{}", path.display()),
+            Self::Env(prefix) => write!(f, "{ENV_PREFIX}{prefix}"),
+            Self::Http(url) => write!(f, "{url}"),
+        }
+    }
+}
+
+/// Loads keys from the environment `variables` starting with the `prefix`.\
+/// **Note** that variables not ending with a key selector are skipped.
+pub(super) fn load_env(prefix: &str, variables: impl Iterator<Item = (String, String)>) -> Result<KeySet, String> {
+    let mut entries = Vec::new();
+    for (name, value) in variables {
+        let tokens = if name == prefix {
+            Vec::new()
+        } else if let Some(selectors) = name.strip_prefix(prefix).and_then(|n| n.strip_prefix('_')) {
+            selectors.split('_').collect()
+        } else {
+            continue;
+        };
+
+        // Other variables may share the prefix (e.g. `DC09_LOG_FORMAT`), only key selectors are loaded.
+        let selector = match AccountSelector::from_tokens(tokens) {
+            Ok(selector) => selector,
+            Err(e) => {
+                log::debug!("{name}: skipped, not a key selector: {e}");
+                continue;
+            },
+        };
+
+        let key = parse_key(&value).map_err(|e| format!("{name}: {e}"))?;
+        entries.push(KeyEntry { selector, key });
+    }
+
+    // Order of environment variables is unspecified, sorting keeps the lookup deterministic.
+    entries.sort_by(|a, b| a.selector.cmp(&b.selector));
+    Ok(entries.into_iter().collect())
+}
+
+/// Splits `http://` URL into the address and the request path.
+fn split_url(url: &str) -> Result<(String, String), String> {
+    let rest = url.strip_prefix(HTTP_PREFIX).unwrap_or(url);
+    let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    if authority.is_empty() || authority.contains('@') {
+        return Err(format!("invalid key service URL '{url}'"));
+    }
+
+    let address = if authority.rsplit_once(':').is_some_and(|(_, p)| p.parse::<u16>().is_ok()) {
+        authority.to_owned()
+    } else {
+        format!("{authority}:80")
+    };
+    let path = if path.is_empty() { "/" } else { path };
+
+    Ok((address, path.to_owned()))
+}
+
+/// Fetches keys from the HTTP key service using a plain `GET` request.
+async fn fetch(url: &str) -> Result<String, String> {
+    let (address, path) = split_url(url)?;
+    let host = address.rsplit_once(':').map_or(address.as_str(), |(h, _)| h);
+    let mut stream = TcpStream::connect(&address)
+        .await
+        .map_err(|e| format!("unable to connect to the key service: {e}"))?;
+
+    let request = format!("GET {path} HTTP/1.0\r\nHost: {host}\r\nAccept: text/plain\r\nConnection: close\r\n\r\n");
+    stream
+        .write_all(request.as_bytes())
+        .await
+        .map_err(|e| format!("unable to send request to the key service: {e}"))?;
+
+    // Reads one byte over the limit to tell a response of the maximum size from a larger one.
+    let mut response = Vec::new();
+    stream
+        .take(HTTP_MAX_RESPONSE_SIZE + 1)
+        .read_to_end(&mut response)
+        .await
+        .map_err(|e| format!("unable to read response of the key service: {e}"))?;
+    if response.len() as u64 > HTTP_MAX_RESPONSE_SIZE {
+        return Err(format!(
+            "key service response too large (over {HTTP_MAX_RESPONSE_SIZE} bytes)"
+        ));
+    }
+
+    let response = String::from_utf8(response).map_err(|_| "key service response is not valid UTF-8".to_owned())?;
+    let Some((head, body)) = response.split_once("\r\n\r\n") else {
+        return Err("invalid key service response".to_owned());
+    };
+
+    let status = head.lines().next().unwrap_or_default();
+    match status.split_whitespace().nth(1) {
+        Some("200") => Ok(body.to_owned()),
+        _ => Err(format!("key service responded with '{status}'")),
+    }
+}
@@ -1,6 +1,10 @@
 pub use time;
 
+pub mod capture;
 pub mod dc09;
+pub mod delay;
+pub mod keys;
 pub mod logging;
 pub mod scenarios;
+pub mod shutdown;
 pub mod utils;
@@ -1,43 +1,217 @@
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
 use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use tracing_appender::non_blocking::{NonBlocking, WorkerGuard};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
 use tracing_error::ErrorLayer;
-use tracing_subscriber::{Layer, layer::SubscriberExt, util::SubscriberInitExt};
+use tracing_subscriber::filter::EnvFilter;
+use tracing_subscriber::fmt::format::debug_fn;
+use tracing_subscriber::fmt::time::OffsetTime;
+use tracing_subscriber::layer::Layered;
+use tracing_subscriber::{Layer, Registry, layer::SubscriberExt, reload, util::SubscriberInitExt};
+
+/// Subscriber with the reloadable level filter that output layers are added to.
+type FilteredRegistry = Layered<reload::Layer<EnvFilter, Registry>, Registry>;
 
 /// Display mode for the logged message.
-#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum DisplayMode {
     Target,
     Plain,
     Both,
 }
 
-/// Initializes new logging to the console and returns worker guard that will flush logs on drop.
-pub fn initialize(app_name: &str) -> Result<tracing_appender::non_blocking::WorkerGuard> {
+/// Format of the logged lines.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines with the message only.
+    Text,
+    /// JSON objects with the message and structured fields.
+    Json,
+}
+
+/// Rotation period of log files.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    Minutely,
+    Hourly,
+    Daily,
+    Never,
+}
+
+impl From<LogRotation> for Rotation {
+    fn from(value: LogRotation) -> Self {
+        match value {
+            LogRotation::Minutely => Rotation::MINUTELY,
+            LogRotation::Hourly => Rotation::HOURLY,
+            LogRotation::Daily => Rotation::DAILY,
+            LogRotation::Never => Rotation::NEVER,
+        }
+    }
+}
+
+/// Logging options shared by all binaries.
+#[derive(clap::Args, Debug, Clone)]
+pub struct LoggingArgs {
+    /// Format of the logged lines.
+    #[arg(long, env = "DC09_LOG_FORMAT", value_enum, value_name = "FORMAT", default_value = "text")]
+    pub log_format: LogFormat,
+
+    /// Write logs also to the specified file, rotated files get a date suffix.
+    #[arg(long, env = "DC09_LOG_FILE", value_name = "FILE")]
+    pub log_file: Option<PathBuf>,
+
+    /// Rotation period of the log file.
+    #[arg(long, env = "DC09_LOG_ROTATION", value_enum, value_name = "PERIOD", default_value = "daily")]
+    pub log_rotation: LogRotation,
+
+    /// Maximum number of rotated log files to keep (0 means unlimited).
+    #[arg(long, env = "DC09_LOG_MAX_FILES", value_name = "COUNT", default_value_t = 0)]
+    pub log_max_files: usize,
+
+    /// Log level directives per component, e.g. `info,receiver::server::udp=debug` (overrides `RUST_LOG`).
+    #[arg(long, env = "DC09_LOG_LEVEL", value_name = "DIRECTIVES", value_parser = parse_log_level)]
+    pub log_level: Option<String>,
+}
+
+/// Parses log level directives in the `RUST_LOG` syntax.
+pub fn parse_log_level(value: &str) -> Result<String, String> {
+    EnvFilter::try_new(value).map_err(|e| e.to_string())?;
+    Ok(value.to_owned())
+}
+
+/// Handle to read and replace log level directives at runtime.
+#[derive(Clone)]
+pub struct LogLevelHandle {
+    handle: reload::Handle<EnvFilter, Registry>,
+}
+
+impl LogLevelHandle {
+    /// Returns the current log level directives.
+    pub fn get(&self) -> String {
+        self.handle.with_current(ToString::to_string).unwrap_or_default()
+    }
+
+    /// Replaces log level directives, returns an error if they are invalid.
+    pub fn set(&self, directives: &str) -> Result<(), String> {
+        let filter = EnvFilter::try_new(directives).map_err(|e| format!("invalid log level '{directives}': {e}"))?;
+        self.handle.reload(filter).map_err(|e| e.to_string())
+    }
+
+    /// Creates handle that is not attached to any subscriber, e.g. for tests of the HTTP API.\
+    /// **Note** that the handle returns no directives and fails to replace them.
+    pub fn detached() -> Self {
+        let (_, handle) = reload::Layer::<EnvFilter, Registry>::new(EnvFilter::default());
+        Self { handle }
+    }
+}
+
+/// Keeps logging running, flushes logs on drop.
+pub struct LoggingGuard {
+    _guards: Vec<WorkerGuard>,
+    level: LogLevelHandle,
+}
+
+impl LoggingGuard {
+    /// Returns handle to change log level directives at runtime.
+    pub fn level_handle(&self) -> LogLevelHandle {
+        self.level.clone()
+    }
+}
+
+/// Initializes new logging to the console (and optionally to log files) and returns guard that will flush logs on drop.\
+/// **Note** that the level directives are taken from `--log-level`, `RUST_LOG` or the default for the `app_name`.
+pub fn initialize(app_name: &str, args: &LoggingArgs) -> Result<LoggingGuard> {
     let stdout = std::io::stdout();
     let is_terminal = stdout.is_terminal();
-    let (non_blocking_appender, guard) = tracing_appender::non_blocking(stdout);
+    initialize_with_console(app_name, args, tracing_appender::non_blocking(stdout), is_terminal)
+}
 
-    let timer = time::format_description::parse("[year]-[month padding:zero]-[day padding:zero] [hour]:[minute]:[second]")?;
-    let time_offset = time::UtcOffset::current_local_offset().unwrap_or(time::UtcOffset::UTC);
-    let timer = tracing_subscriber::fmt::time::OffsetTime::new(time_offset, timer);
+/// Initializes new logging like [`initialize`] but logs to the standard error instead of the standard output.\
+/// **Note** that it is meant for binaries writing their results to the standard output.
+pub fn initialize_stderr(app_name: &str, args: &LoggingArgs) -> Result<LoggingGuard> {
+    let stderr = std::io::stderr();
+    let is_terminal = stderr.is_terminal();
+    initialize_with_console(app_name, args, tracing_appender::non_blocking(stderr), is_terminal)
+}
+
+fn initialize_with_console(
+    app_name: &str,
+    args: &LoggingArgs,
+    (console, console_guard): (NonBlocking, WorkerGuard),
+    is_terminal: bool,
+) -> Result<LoggingGuard> {
+    let mut guards = vec![console_guard];
+    let mut layers = vec![build_layer(console, args.log_format, is_terminal)?];
 
+    if let Some(path) = &args.log_file {
+        let file = build_file_appender(path, args)?;
+        let (file, file_guard) = tracing_appender::non_blocking(file);
+        guards.push(file_guard);
+        layers.push(build_layer(file, args.log_format, false)?);
+    }
+
+    let (filter, handle) = reload::Layer::new(build_filter(app_name, args));
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(layers)
+        .with(ErrorLayer::default())
+        .init();
+
+    Ok(LoggingGuard {
+        _guards: guards,
+        level: LogLevelHandle { handle },
+    })
+}
+
+fn build_filter(app_name: &str, args: &LoggingArgs) -> EnvFilter {
     #[cfg(debug_assertions)]
     let env = format!("warn,{app_name}=info");
 
     #[cfg(not(debug_assertions))]
     let env = format!("none,{app_name}=info");
 
-    let env_filter = tracing_subscriber::filter::EnvFilter::try_from_default_env()
-        .unwrap_or_else(|_| tracing_subscriber::filter::EnvFilter::new(env));
+    match &args.log_level {
+        Some(directives) => EnvFilter::new(directives),
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(env)),
+    }
+}
+
+fn build_layer(writer: NonBlocking, format: LogFormat, ansi: bool) -> Result<Box<dyn Layer<FilteredRegistry> + Send + Sync>> {
+    let timer = time::format_description::parse("[year]-[month padding:zero]-[day padding:zero] [hour]:[minute]:[second]")?;
+    let time_offset = time::UtcOffset::current_local_offset().unwrap_or(time::UtcOffset::UTC);
+    let timer = OffsetTime::new(time_offset, timer);
+    let layer = tracing_subscriber::fmt::layer().with_timer(timer).with_writer(writer);
+
+    Ok(match format {
+        LogFormat::Text => layer
+            .with_ansi(ansi)
+            .fmt_fields(debug_fn(|writer, field, value| match field.name() {
+                "message" => write!(writer, "{value:?}"),
+                _ => Ok(()),
+            }))
+            .boxed(),
+        LogFormat::Json => layer.json().flatten_event(true).with_current_span(false).boxed(),
+    })
+}
 
-    let stdout = tracing_subscriber::fmt::layer()
-        .with_timer(timer)
-        .with_ansi(is_terminal)
-        .with_writer(non_blocking_appender)
-        .with_filter(env_filter);
+fn build_file_appender(path: &Path, args: &LoggingArgs) -> Result<RollingFileAppender> {
+    let directory = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("{}: invalid log file path", path.display()))?;
 
-    tracing_subscriber::registry().with(stdout).with(ErrorLayer::default()).init();
+    let mut builder = RollingFileAppender::builder()
+        .rotation(args.log_rotation.into())
+        .filename_prefix(file_name.to_string_lossy());
+    if args.log_max_files > 0 {
+        builder = builder.max_log_files(args.log_max_files);
+    }
 
-    Ok(guard)
+    Ok(builder.build(directory)?)
 }
@@ -1,12 +1,15 @@
 use serde::{Deserialize, Serialize};
 
+use crate::dc09::{is_account_prefix_valid, is_receiver_valid};
+use crate::delay::DelayProfile;
+use crate::keys::{AccountSelector, SecretKey};
 use crate::utils::VALID_KEY_LENGTHS;
 
 /// Holds dialler configuration.
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct DiallerConfig {
     pub name: String,
-    pub key: Option<String>,
+    pub key: Option<SecretKey>,
     pub receiver: Option<String>,
     pub prefix: Option<String>,
     pub scenarios: Option<Vec<u16>>,
@@ -16,6 +19,7 @@ pub struct DiallerConfig {
     pub udp: bool,
     #[serde(default)]
     pub count: u16,
+    pub delay: Option<DelayProfile>,
 }
 
 impl DiallerConfig {
@@ -30,6 +34,7 @@ impl DiallerConfig {
             sequence,
             udp,
             count,
+            delay: None,
         }
     }
 
@@ -44,6 +49,12 @@ impl DiallerConfig {
         self.receiver = receiver;
         self
     }
+
+    /// Returns selector of the dialler's `account` on its receiver and line.\
+    /// **Note** that missing receiver number or line prefix matches any value.
+    pub fn selector(&self, account: &str) -> AccountSelector {
+        AccountSelector::new(Some(account), self.receiver.as_deref(), self.prefix.as_deref())
+    }
 }
 
 /// Holds scenario configuration.
@@ -104,10 +115,22 @@ impl Scenarios {
     pub fn validate(&self) -> Result<(), String> {
         for dialler in &self.diallers {
             if let Some(key) = &dialler.key
-                && !VALID_KEY_LENGTHS.contains(&key.len())
+                && !VALID_KEY_LENGTHS.contains(&key.expose().len())
             {
                 return Err(format!("{}: key length must be 16, 24 or 32 bytes", dialler.name));
             }
+
+            if dialler.receiver.as_deref().is_some_and(|r| !is_receiver_valid(r)) {
+                return Err(format!("{}: invalid receiver number", dialler.name));
+            }
+
+            if dialler.prefix.as_deref().is_some_and(|l| !is_account_prefix_valid(l)) {
+                return Err(format!("{}: invalid account prefix (receiver line number)", dialler.name));
+            }
+
+            if let Some(delay) = &dialler.delay {
+                delay.validate().map_err(|e| format!("{}: {e}", dialler.name))?;
+            }
         }
 
         Ok(())
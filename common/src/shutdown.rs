@@ -0,0 +1,17 @@
+/// Waits until the process receives `Ctrl-C` or `SIGTERM` (Unix only).\
+/// **Note** that it returns an error immediately if signal handlers cannot be installed.
+pub async fn wait_for_signal() -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result,
+            _ = terminate.recv() => Ok(()),
+        }
+    }
+
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await
+}
@@ -1,18 +1,25 @@
-use std::{collections::HashMap, fs::File, io::Read, path::Path, sync::Arc};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use crate::{
     dc09::{is_account_prefix_valid, is_receiver_valid},
+    keys::{AccountSelector, SecretKey},
     scenarios::Scenarios,
 };
 
-pub type SharedKeysMap = Arc<HashMap<u16, String>>;
+pub type SharedKeysMap = Arc<HashMap<u16, SecretKey>>;
 
 pub const VALID_KEY_LENGTHS: [usize; 3] = [16, 24, 32];
 
 /// Parses key and validates its length.
-pub fn parse_key(s: &str) -> Result<String, String> {
+pub fn parse_key(s: &str) -> Result<SecretKey, String> {
     if VALID_KEY_LENGTHS.contains(&s.len()) {
-        Ok(s.to_owned())
+        Ok(SecretKey::from(s))
     } else {
         Err("key length must be 16, 24 or 32 bytes".to_owned())
     }
@@ -38,7 +45,16 @@ pub fn parse_receiver(s: &str) -> Result<String, String> {
 
 /// Loads [`Scenarios`] from the provided file path.
 pub fn parse_scenarios_path(s: &str) -> Result<Scenarios, String> {
-    let path = Path::new(s);
+    load_scenarios(Path::new(s))
+}
+
+/// Validates [`Scenarios`] file and returns its path, so it can be loaded again later.
+pub fn parse_scenarios_file(s: &str) -> Result<PathBuf, String> {
+    load_scenarios(Path::new(s)).map(|_| PathBuf::from(s))
+}
+
+/// Loads and validates [`Scenarios`] from the provided file path.
+pub fn load_scenarios(path: &Path) -> Result<Scenarios, String> {
     if !path.exists() {
         return Err("the provided file does not exist".to_owned());
     }
@@ -58,18 +74,54 @@ pub fn parse_scenarios_path(s: &str) -> Result<Scenarios, String> {
     Err("unable to deserialize the provided file into a Scenarios object".to_owned())
 }
 
+/// Validates accounts file and returns its path, so it can be loaded again later.
+pub fn parse_accounts_file(s: &str) -> Result<PathBuf, String> {
+    load_accounts(Path::new(s)).map(|_| PathBuf::from(s))
+}
+
+/// Loads account selectors from the provided file path (one `[R<receiver>] [L<line>] ACCOUNT` per line, `*` matches any
+/// account and `#` starts a comment).
+pub fn load_accounts(path: &Path) -> Result<Vec<AccountSelector>, String> {
+    if !path.exists() {
+        return Err("the provided file does not exist".to_owned());
+    }
+
+    let mut accounts_str = String::new();
+    if File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut accounts_str))
+        .is_err()
+    {
+        return Err("unable to read the provided accounts file".to_owned());
+    }
+
+    let mut accounts = Vec::new();
+    for line in accounts_str.lines() {
+        let line = line.split_once('#').map_or(line, |(a, _)| a).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let selector = line
+            .parse::<AccountSelector>()
+            .map_err(|e| format!("invalid account '{line}': {e}"))?;
+        accounts.push(selector);
+    }
+
+    Ok(accounts)
+}
+
 /// Builds a hash map with all keys provided to the app.
-pub fn build_keys_map(scenarios: Option<&Scenarios>, default_key: Option<&str>) -> SharedKeysMap {
+pub fn build_keys_map(scenarios: Option<&Scenarios>, default_key: Option<&SecretKey>) -> SharedKeysMap {
     let mut result = HashMap::new();
 
     if let Some(key) = default_key {
-        result.insert(0, key.to_owned());
+        result.insert(0, key.clone());
     }
 
     if let Some(scenarios) = scenarios {
         for (index, dialler) in scenarios.diallers.iter().enumerate() {
             if let Some(key) = &dialler.key {
-                result.insert((index + 1) as u16, key.to_owned());
+                result.insert((index + 1) as u16, key.clone());
             }
         }
     }
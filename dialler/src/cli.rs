@@ -1,8 +1,9 @@
 use clap::Parser;
-use common::logging::DisplayMode;
+use common::keys::{CachedKeyProvider, DEFAULT_KEY_REFRESH, KeySource, SecretKey};
+use common::logging::{DisplayMode, LoggingArgs};
 use common::scenarios::{Scenarios, SignalConfig};
 use common::utils::{SharedKeysMap, parse_account_prefix, parse_key, parse_receiver, parse_scenarios_path};
-use std::{collections::HashMap, net::IpAddr, sync::Arc};
+use std::{collections::HashMap, net::IpAddr, path::PathBuf, sync::Arc};
 
 pub type SharedSignalsMap = Arc<HashMap<(u16, u16), SignalConfig>>;
 
@@ -56,7 +57,15 @@ pub struct Args {
 
     /// Key to encrypt DC09 messages (16, 24 or 32 bytes long).
     #[arg(long, short, value_parser = parse_key)]
-    pub key: Option<String>,
+    pub key: Option<SecretKey>,
+
+    /// Key source used for diallers without a key: key file, `env:PREFIX` or `http://` URL of a key service.
+    #[arg(long, value_name = "SOURCE", value_delimiter = ',')]
+    pub key_source: Vec<KeySource>,
+
+    /// Interval of reloading keys from the key sources, in seconds (0 disables refreshing).
+    #[arg(long, value_name = "SECS", default_value_t = DEFAULT_KEY_REFRESH)]
+    pub key_refresh: u64,
 
     /// Use a UDP connection instead of a TCP one.
     #[arg(long, short)]
@@ -73,12 +82,33 @@ pub struct Args {
     /// Timeout for waiting for a response, in seconds.
     #[arg(long, default_value_t = 1)]
     pub timeout: u16,
+
+    /// Capture file recorded by the receiver to re-send inbound frames from.
+    #[arg(long, value_name = "FILE")]
+    pub replay: Option<PathBuf>,
+
+    /// Replay speed relative to the original timing (2 is twice as fast, 0 sends without delays).
+    #[arg(long, value_name = "FACTOR", default_value_t = 1.0, value_parser = parse_replay_speed)]
+    pub replay_speed: f64,
+
+    #[command(flatten)]
+    pub logging: LoggingArgs,
 }
 
 impl Args {
     /// Returns a hash map with all keys provided to the app.
     pub fn build_keys_map(&self) -> SharedKeysMap {
-        common::utils::build_keys_map(self.scenarios.as_ref(), self.key.as_deref())
+        common::utils::build_keys_map(self.scenarios.as_ref(), self.key.as_ref())
+    }
+
+    /// Loads keys from the configured key sources, `None` if there are no key sources.
+    pub async fn load_key_sources(&self) -> Result<Option<Arc<CachedKeyProvider>>, String> {
+        if self.key_source.is_empty() {
+            return Ok(None);
+        }
+
+        let provider = CachedKeyProvider::load(self.key_source.clone()).await?;
+        Ok(Some(Arc::new(provider)))
     }
 
     /// Returns a hash map with all signals provided to the app.
@@ -100,3 +130,10 @@ impl Args {
         Arc::new(result)
     }
 }
+
+fn parse_replay_speed(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(speed) if speed.is_finite() && speed >= 0.0 => Ok(speed),
+        _ => Err(format!("invalid replay speed '{s}', expected a non-negative number")),
+    }
+}
@@ -1,11 +1,53 @@
-use anyhow::Result;
+use anyhow::{Result, bail};
+use common::keys::{KeyProvider, KeyQuery, SecretKey};
 use common::{dc09::DC09Message, logging::DisplayMode, scenarios::SignalConfig, time::OffsetDateTime, utils::SharedKeysMap};
-use std::{collections::VecDeque, net::IpAddr, time::Duration};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::{collections::VecDeque, time::Duration};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpStream, UdpSocket};
+use tokio_util::sync::CancellationToken;
 
 use crate::cli::SharedSignalsMap;
 
+/// Holds counters of messages sent by the dialler and received responses.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DiallerStats {
+    pub sent: u32,
+    pub acked: u32,
+    pub rejected: u32,
+    pub timed_out: u32,
+    pub failed: u32,
+}
+
+impl DiallerStats {
+    /// Adds counters from the `other` stats.
+    pub fn merge(&mut self, other: &DiallerStats) {
+        self.sent += other.sent;
+        self.acked += other.acked;
+        self.rejected += other.rejected;
+        self.timed_out += other.timed_out;
+        self.failed += other.failed;
+    }
+
+    fn record(&mut self, outcome: AckOutcome) {
+        match outcome {
+            AckOutcome::Acked => self.acked += 1,
+            AckOutcome::Rejected => self.rejected += 1,
+            AckOutcome::TimedOut => self.timed_out += 1,
+            AckOutcome::Failed => self.failed += 1,
+        }
+    }
+}
+
+/// Outcome of waiting for a response to the sent message.
+enum AckOutcome {
+    Acked,
+    Rejected,
+    TimedOut,
+    Failed,
+}
+
 /// Represents DC09 dialler.
 #[derive(Clone)]
 pub struct Dialler {
@@ -16,11 +58,14 @@ pub struct Dialler {
     account: String,
     sequence: u16,
     key: Option<(SharedKeysMap, u16)>,
+    key_provider: Option<Arc<dyn KeyProvider>>,
     udp: bool,
     signals: SharedSignalsMap,
     queue: VecDeque<(u16, u16)>,
     timeout: Option<Duration>,
     mode: DisplayMode,
+    shutdown: CancellationToken,
+    stats: DiallerStats,
 }
 
 impl Dialler {
@@ -34,11 +79,14 @@ impl Dialler {
             account,
             sequence: 0,
             key: None,
+            key_provider: None,
             udp: use_udp,
             signals,
             queue: VecDeque::new(),
             timeout: None,
             mode: DisplayMode::Target,
+            shutdown: CancellationToken::new(),
+            stats: DiallerStats::default(),
         }
     }
 
@@ -66,23 +114,38 @@ impl Dialler {
         self
     }
 
+    /// Sets provider of keys for diallers without a key set by cli args or the scenarios file.
+    pub fn with_key_provider(mut self, provider: Option<Arc<dyn KeyProvider>>) -> Self {
+        self.key_provider = provider;
+        self
+    }
+
     /// Sets message display flag.
     pub fn with_msg_mode(mut self, mode: DisplayMode) -> Self {
         self.mode = mode;
         self
     }
 
+    /// Sets token that stops sending further signals when cancelled.
+    pub fn with_shutdown(mut self, shutdown: CancellationToken) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
     /// Sets the optional timeout duration for receiving a message.
     pub fn set_timeout(&mut self, timeout: Option<Duration>) {
         self.timeout = timeout;
     }
 
-    /// Returns key that can be used to decrypt and encrypt DC09 messages.
-    pub fn key(&self) -> Option<&str> {
-        self.key
-            .as_ref()
-            .and_then(|(keys, index)| keys.get(index))
-            .map(String::as_str)
+    /// Returns key that can be used to decrypt and encrypt DC09 messages.\
+    /// **Note** that the key provider is asked only if no key is set by cli args or the scenarios file.
+    pub fn key(&self) -> Option<SecretKey> {
+        if let Some(key) = self.key.as_ref().and_then(|(keys, index)| keys.get(index)) {
+            return Some(key.clone());
+        }
+
+        let query = KeyQuery::new(&self.account, self.receiver.as_deref(), self.line_prefix.as_deref());
+        self.key_provider.as_ref().and_then(|p| p.key(&query))
     }
 
     /// Adds default signal to the queue.
@@ -95,25 +158,34 @@ impl Dialler {
         &self.account
     }
 
+    /// Gets dialler's counters of sent messages and received responses.
+    pub fn stats(&self) -> &DiallerStats {
+        &self.stats
+    }
+
     /// Gets dialler's signals queue.
     pub fn queue_mut(&mut self) -> &mut VecDeque<(u16, u16)> {
         &mut self.queue
     }
 
     /// Sends sequence of messages from the queue.\
-    /// **Note** that it will stop draining the queue on error.
+    /// **Note** that it will stop draining the queue on error or shutdown.
     pub async fn run_sequence(&mut self) {
         log::info!("{}    start sending signals", self.account);
         'outer: while let Some(item) = self.queue.pop_front() {
+            if self.shutdown.is_cancelled() {
+                break;
+            }
+
             if let Some(signal) = self.signals.get(&item).cloned() {
                 let repeat = signal.repeat.max(1) - 1;
                 for _ in 0..repeat {
-                    if !self.send_signal(signal.clone()).await {
+                    if self.shutdown.is_cancelled() || !self.send_signal(signal.clone()).await {
                         break 'outer;
                     }
                 }
 
-                if !self.send_signal(signal).await {
+                if self.shutdown.is_cancelled() || !self.send_signal(signal).await {
                     break 'outer;
                 }
             }
@@ -131,17 +203,18 @@ impl Dialler {
             .with_receiver(self.receiver.clone())
             .with_line_prefix(self.line_prefix.clone());
 
-        let plain = if self.key.is_some() && self.mode != DisplayMode::Target {
+        let key = self.key();
+        let plain = if key.is_some() && self.mode != DisplayMode::Target {
             Some(message.to_string())
         } else {
             None
         };
 
-        let message = if let Some(key) = self.key() {
-            message
-                .with_timestamp(OffsetDateTime::now_utc())
-                .to_encrypted(key)
-                .expect("Cannot encrypt DC09 message with provided key")
+        let message = if let Some(key) = &key {
+            match message.with_timestamp(OffsetDateTime::now_utc()).to_encrypted(key.expose()) {
+                Some(message) => message,
+                None => bail!("unable to encrypt message with the provided key"),
+            }
         } else {
             message.to_string()
         };
@@ -158,12 +231,16 @@ impl Dialler {
 
     async fn send_signal(&mut self, signal: SignalConfig) -> bool {
         if signal.delay > 50 {
-            tokio::time::sleep(Duration::from_millis(signal.delay.into())).await;
+            tokio::select! {
+                () = tokio::time::sleep(Duration::from_millis(signal.delay.into())) => {},
+                () = self.shutdown.cancelled() => return false,
+            }
         }
 
         let message = signal.message.map(|m| format!("#{}|{}", self.account, m)).unwrap_or_default();
         if let Err(error) = self.send_message(signal.token, message).await {
             log::error!("{}    {}", self.account, error);
+            self.stats.failed += 1;
             return false;
         }
 
@@ -173,88 +250,136 @@ impl Dialler {
     async fn send_message_tcp(&mut self, message: String, plain: Option<&str>, timeout: Option<Duration>) -> Result<()> {
         let mut stream = TcpStream::connect((self.address, self.port)).await?;
         stream.write_all(message.as_bytes()).await?;
-        log_sent_message(&self.account, &message, plain, self.mode);
+        self.log_sent_message(&message, plain);
+        self.stats.sent += 1;
 
         let mut buffer = [0; 1024];
         let read_future = async {
             match stream.read(&mut buffer).await {
-                Ok(0) => log::error!("{}    connection closed by receiver", self.account),
+                Ok(0) => {
+                    log::error!("{}    connection closed by receiver", self.account);
+                    AckOutcome::Failed
+                },
                 Ok(n) => self.process_ack_buffer(&buffer, n),
-                Err(e) => log::error!("{}    failed to read response: {}", self.account, e),
+                Err(e) => {
+                    log::error!("{}    failed to read response: {}", self.account, e);
+                    AckOutcome::Failed
+                },
             }
         };
 
-        match timeout {
-            Some(timeout) => {
-                if tokio::time::timeout(timeout, read_future).await.is_err() {
-                    log::warn!("{}    response timed out after {:?}", self.account, timeout);
-                }
-            },
+        let outcome = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, read_future).await.unwrap_or_else(|_| {
+                log::warn!("{}    response timed out after {:?}", self.account, timeout);
+                AckOutcome::TimedOut
+            }),
             None => read_future.await,
-        }
+        };
 
+        self.stats.record(outcome);
         stream.shutdown().await?;
         Ok(())
     }
 
-    async fn send_message_udp(&self, message: String, plain: Option<&str>, timeout: Option<Duration>) -> Result<()> {
+    async fn send_message_udp(&mut self, message: String, plain: Option<&str>, timeout: Option<Duration>) -> Result<()> {
         let socket = UdpSocket::bind("0.0.0.0:0").await?;
         socket.connect((self.address, self.port)).await?;
 
         let _ = socket.send(message.as_bytes()).await?;
-        log_sent_message(&self.account, &message, plain, self.mode);
+        self.log_sent_message(&message, plain);
+        self.stats.sent += 1;
 
         let mut buffer = [0; 1024];
         let recv_future = async {
             match socket.recv(&mut buffer).await {
                 Ok(n) => self.process_ack_buffer(&buffer, n),
-                Err(e) => log::error!("{}    failed to read response: {}", self.account, e),
+                Err(e) => {
+                    log::error!("{}    failed to read response: {}", self.account, e);
+                    AckOutcome::Failed
+                },
             }
         };
 
-        match timeout {
-            Some(timeout) => {
-                if tokio::time::timeout(timeout, recv_future).await.is_err() {
-                    log::warn!("{}    response timed out after {:?}", self.account, timeout);
-                }
-            },
+        let outcome = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, recv_future).await.unwrap_or_else(|_| {
+                log::warn!("{}    response timed out after {:?}", self.account, timeout);
+                AckOutcome::TimedOut
+            }),
             None => recv_future.await,
-        }
+        };
 
+        self.stats.record(outcome);
         Ok(())
     }
 
-    fn process_ack_buffer(&self, buffer: &[u8; 1024], n: usize) {
+    fn process_ack_buffer(&self, buffer: &[u8; 1024], n: usize) -> AckOutcome {
         match core::str::from_utf8(&buffer[..n]) {
             Ok(ack) => self.process_ack_message(ack),
-            Err(e) => log::error!("{}    received invalid UTF-8 sequence: {}", self.account, e),
+            Err(e) => {
+                log::error!("{}    received invalid UTF-8 sequence: {}", self.account, e);
+                AckOutcome::Failed
+            },
         }
     }
 
-    fn process_ack_message(&self, message: &str) {
-        match DC09Message::try_from(message, self.key()) {
+    fn process_ack_message(&self, message: &str) -> AckOutcome {
+        match DC09Message::try_from(message, self.key().as_ref().map(SecretKey::expose)) {
             Ok(msg) => match msg.validate(&self.account, self.sequence) {
-                Ok(()) => log::info!("{} << {}", self.account, message.trim()),
-                Err(e) => log::error!("{} << ({}) {}", self.account, e, message.trim()),
+                Ok(()) => {
+                    tracing::info!(
+                        transport = self.transport(),
+                        direction = "inbound",
+                        peer = %self.peer(),
+                        account = %msg.account,
+                        token = %msg.token,
+                        sequence = msg.sequence,
+                        "{} << {}",
+                        self.account,
+                        message.trim()
+                    );
+                    if msg.token.trim_start_matches('*') == "ACK" {
+                        AckOutcome::Acked
+                    } else {
+                        AckOutcome::Rejected
+                    }
+                },
+                Err(e) => {
+                    log::error!("{} << ({}) {}", self.account, e, message.trim());
+                    AckOutcome::Failed
+                },
+            },
+            Err(e) => {
+                log::error!("{} << ({}) {}", self.account, e, message.trim());
+                AckOutcome::Failed
             },
-            Err(e) => log::error!("{} << ({}) {}", self.account, e, message.trim()),
         }
     }
-}
 
-/// Logs sent message in a specified mode.
-pub fn log_sent_message(account: &str, message: &str, plain: Option<&str>, mode: DisplayMode) {
-    let decrypted = if let Some(plain) = plain { plain } else { message };
-
-    match mode {
-        DisplayMode::Target => log::info!("{} >> {}", account, message.trim()),
-        DisplayMode::Plain => log::info!("{} >> {}", account, decrypted.trim()),
-        DisplayMode::Both => {
-            if plain.is_some() {
-                log::info!("{} >> {} → {}", account, decrypted.trim(), message.trim());
-            } else {
-                log::info!("{} >> {}", account, message.trim());
-            }
-        },
+    /// Logs sent message in the configured display mode.
+    fn log_sent_message(&self, message: &str, plain: Option<&str>) {
+        let text = match (self.mode, plain) {
+            (DisplayMode::Plain, Some(plain)) => plain.trim().to_owned(),
+            (DisplayMode::Both, Some(plain)) => format!("{} → {}", plain.trim(), message.trim()),
+            _ => message.trim().to_owned(),
+        };
+
+        tracing::info!(
+            transport = self.transport(),
+            direction = "outbound",
+            peer = %self.peer(),
+            account = %self.account,
+            sequence = self.sequence,
+            "{} >> {}",
+            self.account,
+            text
+        );
+    }
+
+    fn transport(&self) -> &'static str {
+        if self.udp { "UDP" } else { "TCP" }
+    }
+
+    fn peer(&self) -> SocketAddr {
+        SocketAddr::new(self.address, self.port)
     }
 }
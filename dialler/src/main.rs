@@ -1,19 +1,31 @@
 use anyhow::Result;
 use clap::Parser;
+use common::keys::KeyProvider;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
 
+use crate::dialler::DiallerStats;
+use crate::replay::Replay;
 use crate::utils::{create_diallers, setup_message_queues};
 
 mod cli;
 mod dialler;
+mod replay;
 mod utils;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let _logging_guard = common::logging::initialize("dialler")?;
-
     let args = cli::Args::parse();
+    let _logging_guard = common::logging::initialize("dialler", &args.logging)?;
+
+    let result = match &args.replay {
+        Some(path) => run_replay(&args, path).await,
+        None => run_diallers(args).await,
+    };
 
-    if let Err(error) = run_diallers(args).await {
+    if let Err(error) = result {
         log::error!("{error}");
     }
 
@@ -22,18 +34,80 @@ async fn main() -> Result<()> {
 
 async fn run_diallers(args: cli::Args) -> Result<()> {
     let keys = args.build_keys_map();
+    let key_sources = args.load_key_sources().await.map_err(anyhow::Error::msg)?;
+    if let Some(key_sources) = &key_sources
+        && args.key_refresh > 0
+    {
+        tokio::spawn(Arc::clone(key_sources).refresh_every(Duration::from_secs(args.key_refresh)));
+    }
+
     let signals = args.build_signals_map();
-    let mut diallers = create_diallers(&args, &signals, &keys);
+    let key_provider = key_sources.map(|k| k as Arc<dyn KeyProvider>);
+    let mut diallers = create_diallers(&args, &signals, &keys, key_provider);
     setup_message_queues(&mut diallers, &args);
 
+    let shutdown = watch_shutdown();
     let mut tasks = Vec::new();
-    for mut dialler in diallers {
-        tasks.push(tokio::spawn(async move { dialler.run_sequence().await }));
+    for dialler in diallers {
+        let mut dialler = dialler.with_shutdown(shutdown.clone());
+        tasks.push(tokio::spawn(async move {
+            dialler.run_sequence().await;
+            *dialler.stats()
+        }));
     }
 
+    let count = tasks.len();
+    let mut stats = DiallerStats::default();
     for task in tasks {
-        task.await?;
+        stats.merge(&task.await?);
     }
 
+    log::info!(
+        "summary: {} diallers, {} sent, {} acknowledged, {} rejected, {} timed out, {} failed{}",
+        count,
+        stats.sent,
+        stats.acked,
+        stats.rejected,
+        stats.timed_out,
+        stats.failed,
+        if shutdown.is_cancelled() { " (interrupted)" } else { "" },
+    );
+
     Ok(())
 }
+
+async fn run_replay(args: &cli::Args, path: &Path) -> Result<()> {
+    let shutdown = watch_shutdown();
+    let timeout = Duration::from_secs(args.timeout.into());
+    let replay = Replay::new(args.address, args.port, args.replay_speed, timeout, shutdown.clone());
+    let (count, stats) = replay.run(path).await?;
+
+    log::info!(
+        "summary: {} sessions, {} of {} frames sent, {} responses, {} failed{}",
+        count,
+        stats.sent,
+        stats.frames,
+        stats.responses,
+        stats.failed,
+        if shutdown.is_cancelled() { " (interrupted)" } else { "" },
+    );
+
+    Ok(())
+}
+
+/// Returns token that is cancelled on `Ctrl-C` or `SIGTERM`.
+fn watch_shutdown() -> CancellationToken {
+    let shutdown = CancellationToken::new();
+    let token = shutdown.clone();
+    tokio::spawn(async move {
+        match common::shutdown::wait_for_signal().await {
+            Ok(()) => {
+                log::info!("shutdown requested, waiting for outstanding responses");
+                token.cancel();
+            },
+            Err(e) => log::error!("unable to listen for shutdown signals: {e}"),
+        }
+    });
+
+    shutdown
+}
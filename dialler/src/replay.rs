@@ -0,0 +1,268 @@
+use anyhow::{Result, bail};
+use common::capture::{CaptureReader, Direction, Transport};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+/// Holds counters of replayed frames and received responses.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ReplayStats {
+    pub frames: u32,
+    pub sent: u32,
+    pub responses: u32,
+    pub failed: u32,
+}
+
+impl ReplayStats {
+    /// Adds counters from the `other` stats.
+    pub fn merge(&mut self, other: &ReplayStats) {
+        self.frames += other.frames;
+        self.sent += other.sent;
+        self.responses += other.responses;
+        self.failed += other.failed;
+    }
+}
+
+/// Inbound frames sent by a single peer, with offsets from the first captured inbound frame.
+struct Session {
+    transport: Transport,
+    peer: SocketAddr,
+    frames: Vec<(Duration, Vec<u8>)>,
+}
+
+/// Replays inbound frames from a capture file to the receiver.
+pub struct Replay {
+    target: SocketAddr,
+    speed: f64,
+    timeout: Duration,
+    shutdown: CancellationToken,
+}
+
+impl Replay {
+    /// Creates new [`Replay`] instance.\
+    /// **Note** that `speed` scales the original timing, `0` sends all frames without delays.
+    pub fn new(address: IpAddr, port: u16, speed: f64, timeout: Duration, shutdown: CancellationToken) -> Self {
+        Self {
+            target: SocketAddr::new(address, port),
+            speed,
+            timeout,
+            shutdown,
+        }
+    }
+
+    /// Replays all sessions from the capture file concurrently, each one over its original transport.
+    pub async fn run(&self, path: &Path) -> Result<(usize, ReplayStats)> {
+        let sessions = load_sessions(path)?;
+        log::info!(
+            "replaying {} sessions from {} to {}",
+            sessions.len(),
+            path.display(),
+            self.target
+        );
+
+        let start = Instant::now();
+        let mut tasks = Vec::with_capacity(sessions.len());
+        for session in sessions {
+            let replay = Replay {
+                shutdown: self.shutdown.clone(),
+                ..*self
+            };
+            tasks.push(tokio::spawn(async move { replay.run_session(session, start).await }));
+        }
+
+        let count = tasks.len();
+        let mut stats = ReplayStats::default();
+        for task in tasks {
+            stats.merge(&task.await?);
+        }
+
+        Ok((count, stats))
+    }
+
+    async fn run_session(&self, session: Session, start: Instant) -> ReplayStats {
+        let mut stats = ReplayStats {
+            frames: session.frames.len() as u32,
+            ..Default::default()
+        };
+
+        let result = match session.transport {
+            Transport::Tcp => self.replay_tcp(&session, start, &mut stats).await,
+            Transport::Udp => self.replay_udp(&session, start, &mut stats).await,
+        };
+
+        if let Err(e) = result {
+            log::error!("{}    {}", session.peer, e);
+        }
+
+        stats.failed = stats.frames - stats.sent;
+        stats
+    }
+
+    async fn replay_tcp(&self, session: &Session, start: Instant, stats: &mut ReplayStats) -> Result<()> {
+        let stream = TcpStream::connect(self.target).await?;
+        let (mut reader, mut writer) = stream.into_split();
+
+        let mut responses = 0;
+        let reading = async {
+            let mut buffer = [0; 1024];
+            while let Ok(n) = reader.read(&mut buffer).await
+                && n > 0
+            {
+                responses += log_responses(session, &buffer[..n]);
+            }
+        };
+
+        let mut sent = 0;
+        let writing = async {
+            for (offset, frame) in &session.frames {
+                if !self.wait_until(start, *offset).await {
+                    break;
+                }
+
+                if let Err(e) = writer.write_all(frame).await {
+                    log::error!("{}    {}", session.peer, e);
+                    break;
+                }
+
+                log_frame(session, frame);
+                sent += 1;
+            }
+
+            tokio::time::sleep(self.timeout).await;
+        };
+
+        tokio::select! {
+            () = reading => {},
+            () = writing => {},
+        }
+
+        stats.sent = sent;
+        stats.responses = responses;
+        writer.shutdown().await?;
+        Ok(())
+    }
+
+    async fn replay_udp(&self, session: &Session, start: Instant, stats: &mut ReplayStats) -> Result<()> {
+        let local = if self.target.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+        let socket = UdpSocket::bind(local).await?;
+        socket.connect(self.target).await?;
+
+        let mut responses = 0;
+        let reading = async {
+            let mut buffer = [0; 1024];
+            while let Ok(n) = socket.recv(&mut buffer).await {
+                responses += log_responses(session, &buffer[..n]);
+            }
+        };
+
+        let mut sent = 0;
+        let writing = async {
+            for (offset, frame) in &session.frames {
+                if !self.wait_until(start, *offset).await {
+                    break;
+                }
+
+                if let Err(e) = socket.send(frame).await {
+                    log::error!("{}    {}", session.peer, e);
+                    break;
+                }
+
+                log_frame(session, frame);
+                sent += 1;
+            }
+
+            tokio::time::sleep(self.timeout).await;
+        };
+
+        tokio::select! {
+            () = reading => {},
+            () = writing => {},
+        }
+
+        stats.sent = sent;
+        stats.responses = responses;
+        Ok(())
+    }
+
+    /// Waits until the scaled `offset` elapses since `start`, returns `false` on shutdown.
+    async fn wait_until(&self, start: Instant, offset: Duration) -> bool {
+        if self.speed > 0.0 {
+            let deadline = start + offset.div_f64(self.speed);
+            tokio::select! {
+                () = tokio::time::sleep_until(deadline) => {},
+                () = self.shutdown.cancelled() => return false,
+            }
+        }
+
+        !self.shutdown.is_cancelled()
+    }
+}
+
+/// Loads inbound frames from the capture file grouped by transport and peer.
+fn load_sessions(path: &Path) -> Result<Vec<Session>> {
+    let reader = CaptureReader::new(BufReader::new(File::open(path)?))?;
+
+    let mut first = None;
+    let mut sessions = Vec::<Session>::new();
+    let mut index = HashMap::new();
+    for record in reader {
+        let record = record?;
+        if record.direction != Direction::Inbound {
+            continue;
+        }
+
+        let first = *first.get_or_insert(record.timestamp);
+        let offset = Duration::try_from(record.timestamp - first).unwrap_or_default();
+        let position = *index.entry((record.transport, record.peer)).or_insert_with(|| {
+            sessions.push(Session {
+                transport: record.transport,
+                peer: record.peer,
+                frames: Vec::new(),
+            });
+            sessions.len() - 1
+        });
+        sessions[position].frames.push((offset, record.frame));
+    }
+
+    if sessions.is_empty() {
+        bail!("{}: capture contains no inbound frames", path.display());
+    }
+
+    Ok(sessions)
+}
+
+fn log_frame(session: &Session, frame: &[u8]) {
+    tracing::info!(
+        transport = %session.transport,
+        direction = "outbound",
+        peer = %session.peer,
+        "{} >> {}",
+        session.peer,
+        String::from_utf8_lossy(frame).trim()
+    );
+}
+
+fn log_responses(session: &Session, buffer: &[u8]) -> u32 {
+    let responses = String::from_utf8_lossy(buffer);
+    let mut count = 0;
+    for response in responses.split_inclusive('\r').filter(|r| !r.trim().is_empty()) {
+        tracing::info!(
+            transport = %session.transport,
+            direction = "inbound",
+            peer = %session.peer,
+            "{} << {}",
+            session.peer,
+            response.trim()
+        );
+        count += 1;
+    }
+
+    count
+}
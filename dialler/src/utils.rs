@@ -1,3 +1,4 @@
+use common::keys::KeyProvider;
 use common::scenarios::{DiallerConfig, Scenarios};
 use common::utils::{SharedKeysMap, get_account_name};
 use std::{sync::Arc, time::Duration};
@@ -6,12 +7,24 @@ use crate::cli::{Args, SharedSignalsMap};
 use crate::dialler::Dialler;
 
 /// Creates all diallers from the scenarios file and command line parameters.
-pub fn create_diallers(args: &Args, signals: &SharedSignalsMap, keys: &SharedKeysMap) -> Vec<Dialler> {
+pub fn create_diallers(
+    args: &Args,
+    signals: &SharedSignalsMap,
+    keys: &SharedKeysMap,
+    key_provider: Option<Arc<dyn KeyProvider>>,
+) -> Vec<Dialler> {
     let mut result = Vec::new();
 
     if let Some(scenarios) = &args.scenarios {
         for (index, dialler) in scenarios.diallers.iter().enumerate() {
-            result.extend(build_diallers(args, dialler, signals, keys, (index + 1) as u16));
+            result.extend(build_diallers(
+                args,
+                dialler,
+                signals,
+                keys,
+                key_provider.clone(),
+                (index + 1) as u16,
+            ));
         }
     }
 
@@ -19,7 +32,7 @@ pub fn create_diallers(args: &Args, signals: &SharedSignalsMap, keys: &SharedKey
         let dialler = DiallerConfig::new(args.account.clone(), args.sequence, args.udp, args.diallers)
             .with_line_number(args.line.clone())
             .with_receiver_number(args.receiver.clone());
-        result.extend(build_diallers(args, &dialler, signals, keys, 0));
+        result.extend(build_diallers(args, &dialler, signals, keys, key_provider, 0));
     }
 
     set_timeouts(result, args.timeout.into())
@@ -65,6 +78,7 @@ fn build_diallers(
     config: &DiallerConfig,
     signals: &SharedSignalsMap,
     keys: &SharedKeysMap,
+    key_provider: Option<Arc<dyn KeyProvider>>,
     index: u16,
 ) -> Vec<Dialler> {
     let mut result = Vec::with_capacity(config.count.max(1).into());
@@ -78,6 +92,7 @@ fn build_diallers(
                 .with_receiver_number(config.receiver.clone())
                 .with_line_prefix(config.prefix.clone())
                 .with_key(Arc::clone(keys), index)
+                .with_key_provider(key_provider.clone())
                 .with_start_sequence(config.sequence.saturating_sub(1))
                 .with_msg_mode(args.show),
         );
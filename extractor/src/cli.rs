@@ -0,0 +1,48 @@
+use clap::{Parser, ValueEnum};
+use common::keys::SecretKey;
+use common::logging::LoggingArgs;
+use common::scenarios::Scenarios;
+use common::utils::{parse_key, parse_scenarios_path};
+use std::path::PathBuf;
+
+/// Output format of the messages timeline.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+/// Extracts DC09 traffic from pcap and pcapng captures.
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct Args {
+    /// Capture file in pcap or pcapng format.
+    pub input: PathBuf,
+
+    /// Receiver ports to extract TCP and UDP traffic from (comma separated).
+    #[arg(long, short, value_delimiter = ',', default_value = "8080")]
+    pub ports: Vec<u16>,
+
+    /// Default key to decrypt DC09 messages (16, 24 or 32 bytes long).
+    #[arg(long, short, value_parser = parse_key)]
+    pub key: Option<SecretKey>,
+
+    /// Configuration file with per-account keys to decrypt DC09 messages.
+    #[arg(long, value_parser = parse_scenarios_path)]
+    pub scenarios: Option<Scenarios>,
+
+    /// Output format of the messages timeline.
+    #[arg(long, short, value_enum, default_value = "text")]
+    pub format: OutputFormat,
+
+    /// Write the timeline to the specified file instead of the standard output.
+    #[arg(long, short, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Export extracted frames to a capture file that can be replayed by the dialler.
+    #[arg(long, value_name = "FILE")]
+    pub capture: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub logging: LoggingArgs,
+}
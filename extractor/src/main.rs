@@ -0,0 +1,61 @@
+use anyhow::Result;
+use clap::Parser;
+use common::capture::{CaptureRecord, CaptureWriter};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use crate::cli::{Args, OutputFormat};
+use crate::timeline::{Keys, TimelineEntry, extract_frames};
+
+mod cli;
+mod packet;
+mod pcap;
+mod stream;
+mod timeline;
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let _logging_guard = common::logging::initialize_stderr("extractor", &args.logging)?;
+
+    let data = std::fs::read(&args.input)?;
+    let packets = pcap::read_packets(&data)?;
+    let frames = extract_frames(&packets, &args.ports);
+
+    let keys = Keys::new(args.scenarios.as_ref(), args.key.clone());
+    let entries = frames.iter().map(|f| TimelineEntry::new(f, &keys)).collect::<Vec<_>>();
+
+    let mut output: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(std::io::stdout().lock()),
+    };
+
+    match args.format {
+        OutputFormat::Text => {
+            for entry in &entries {
+                writeln!(output, "{}", entry.to_text())?;
+            }
+        },
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut output, &entries)?;
+            writeln!(output)?;
+        },
+    }
+    output.flush()?;
+
+    if let Some(path) = &args.capture {
+        let mut writer = CaptureWriter::new(BufWriter::new(File::create(path)?))?;
+        for frame in &frames {
+            writer.write(&CaptureRecord::from(frame))?;
+        }
+        writer.flush()?;
+    }
+
+    log::info!(
+        "extracted {} frames ({} failed to parse) from {} packets",
+        entries.len(),
+        entries.iter().filter(|e| e.error.is_some()).count(),
+        packets.len()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,121 @@
+use common::capture::Transport;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+const LINKTYPE_NULL: u32 = 0;
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: [u32; 3] = [12, 14, 101];
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_IPV4: u32 = 228;
+const LINKTYPE_IPV6: u32 = 229;
+const LINKTYPE_LINUX_SLL2: u32 = 276;
+
+const ETHERTYPE_VLAN: [u16; 2] = [0x8100, 0x88A8];
+const PROTOCOL_TCP: u8 = 6;
+const PROTOCOL_UDP: u8 = 17;
+const IPV6_EXTENSION_HEADERS: [u8; 3] = [0, 43, 60];
+
+/// TCP header fields required for stream reassembly.
+#[derive(Debug, Clone, Copy)]
+pub struct TcpHeader {
+    pub sequence: u32,
+    pub syn: bool,
+}
+
+/// Transport layer segment decoded from a captured packet.
+#[derive(Debug)]
+pub struct Segment<'a> {
+    pub source: SocketAddr,
+    pub destination: SocketAddr,
+    pub transport: Transport,
+    pub tcp: Option<TcpHeader>,
+    pub payload: &'a [u8],
+}
+
+/// Decodes TCP or UDP segment from the packet, `None` if the packet is not supported.\
+/// **Note** that fragmented IPv4 packets are not reassembled and are skipped.
+pub fn decode(link_type: u32, data: &[u8]) -> Option<Segment<'_>> {
+    let ip = match link_type {
+        LINKTYPE_NULL => data.get(4..)?,
+        LINKTYPE_ETHERNET => decode_ethernet(data)?,
+        LINKTYPE_LINUX_SLL => data.get(16..)?,
+        LINKTYPE_LINUX_SLL2 => data.get(20..)?,
+        LINKTYPE_IPV4 | LINKTYPE_IPV6 => data,
+        link_type if LINKTYPE_RAW.contains(&link_type) => data,
+        _ => return None,
+    };
+
+    let (source, destination, protocol, payload) = match ip.first()? >> 4 {
+        4 => decode_ipv4(ip)?,
+        6 => decode_ipv6(ip)?,
+        _ => return None,
+    };
+
+    match protocol {
+        PROTOCOL_TCP => {
+            let offset = usize::from(payload.get(12)? >> 4) * 4;
+            Some(Segment {
+                source: SocketAddr::new(source, u16::from_be_bytes([payload[0], payload[1]])),
+                destination: SocketAddr::new(destination, u16::from_be_bytes([payload[2], payload[3]])),
+                transport: Transport::Tcp,
+                tcp: Some(TcpHeader {
+                    sequence: u32::from_be_bytes(payload.get(4..8)?.try_into().ok()?),
+                    syn: payload.get(13)? & 0x02 != 0,
+                }),
+                payload: payload.get(offset..)?,
+            })
+        },
+        PROTOCOL_UDP => {
+            let len = usize::from(u16::from_be_bytes(payload.get(4..6)?.try_into().ok()?));
+            Some(Segment {
+                source: SocketAddr::new(source, u16::from_be_bytes([payload[0], payload[1]])),
+                destination: SocketAddr::new(destination, u16::from_be_bytes([payload[2], payload[3]])),
+                transport: Transport::Udp,
+                tcp: None,
+                payload: payload.get(8..len.min(payload.len()).max(8))?,
+            })
+        },
+        _ => None,
+    }
+}
+
+fn decode_ethernet(data: &[u8]) -> Option<&[u8]> {
+    let mut offset = 12;
+    let mut ethertype = u16::from_be_bytes(data.get(offset..offset + 2)?.try_into().ok()?);
+    while ETHERTYPE_VLAN.contains(&ethertype) {
+        offset += 4;
+        ethertype = u16::from_be_bytes(data.get(offset..offset + 2)?.try_into().ok()?);
+    }
+
+    data.get(offset + 2..)
+}
+
+fn decode_ipv4(data: &[u8]) -> Option<(IpAddr, IpAddr, u8, &[u8])> {
+    let header = usize::from(data.first()? & 0x0F) * 4;
+    let total = usize::from(u16::from_be_bytes(data.get(2..4)?.try_into().ok()?));
+    let fragment = u16::from_be_bytes(data.get(6..8)?.try_into().ok()?);
+    if fragment & 0x3FFF != 0 {
+        return None;
+    }
+
+    let source = Ipv4Addr::from(<[u8; 4]>::try_from(data.get(12..16)?).ok()?);
+    let destination = Ipv4Addr::from(<[u8; 4]>::try_from(data.get(16..20)?).ok()?);
+    let payload = data.get(header..total.min(data.len()))?;
+
+    Some((source.into(), destination.into(), *data.get(9)?, payload))
+}
+
+fn decode_ipv6(data: &[u8]) -> Option<(IpAddr, IpAddr, u8, &[u8])> {
+    let len = usize::from(u16::from_be_bytes(data.get(4..6)?.try_into().ok()?));
+    let source = Ipv6Addr::from(<[u8; 16]>::try_from(data.get(8..24)?).ok()?);
+    let destination = Ipv6Addr::from(<[u8; 16]>::try_from(data.get(24..40)?).ok()?);
+
+    let mut next = *data.get(6)?;
+    let mut payload = data.get(40..(40 + len).min(data.len()))?;
+    while IPV6_EXTENSION_HEADERS.contains(&next) {
+        let header = (usize::from(*payload.get(1)?) + 1) * 8;
+        next = *payload.first()?;
+        payload = payload.get(header..)?;
+    }
+
+    Some((source.into(), destination.into(), next, payload))
+}
@@ -0,0 +1,211 @@
+use anyhow::{Result, anyhow, bail};
+use common::time::OffsetDateTime;
+
+#[cfg(test)]
+#[path = "./pcap.tests.rs"]
+mod pcap_tests;
+
+const PCAPNG_SECTION_HEADER: u32 = 0x0A0D_0D0A;
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+const PCAPNG_INTERFACE_DESCRIPTION: u32 = 1;
+const PCAPNG_SIMPLE_PACKET: u32 = 3;
+const PCAPNG_ENHANCED_PACKET: u32 = 6;
+const PCAPNG_OPTION_TSRESOL: u16 = 9;
+
+/// Single packet read from a capture file.
+#[derive(Debug, Clone)]
+pub struct Packet {
+    pub timestamp: OffsetDateTime,
+    pub link_type: u32,
+    pub data: Vec<u8>,
+}
+
+/// Reads all packets from a pcap or pcapng capture.
+pub fn read_packets(data: &[u8]) -> Result<Vec<Packet>> {
+    let Some(magic) = data.get(..4) else {
+        bail!("file is too short to be a pcap capture");
+    };
+
+    match magic {
+        [0xD4, 0xC3, 0xB2, 0xA1] => read_pcap(data, true, 1_000),
+        [0xA1, 0xB2, 0xC3, 0xD4] => read_pcap(data, false, 1_000),
+        [0x4D, 0x3C, 0xB2, 0xA1] => read_pcap(data, true, 1),
+        [0xA1, 0xB2, 0x3C, 0x4D] => read_pcap(data, false, 1),
+        [0x0A, 0x0D, 0x0D, 0x0A] => read_pcapng(data),
+        _ => bail!("unknown capture format, expected pcap or pcapng"),
+    }
+}
+
+/// Reads packets from a classic pcap capture, `nanos` is the number of nanoseconds per timestamp fraction unit.
+fn read_pcap(data: &[u8], le: bool, nanos: i128) -> Result<Vec<Packet>> {
+    let bytes = Bytes { data, le };
+    let link_type = bytes.u32(20)?;
+
+    let mut packets = Vec::new();
+    let mut offset = 24;
+    while offset < data.len() {
+        let seconds = bytes.u32(offset)?;
+        let fraction = bytes.u32(offset + 4)?;
+        let len = bytes.u32(offset + 8)? as usize;
+        let packet = bytes.slice(offset + 16, len)?;
+
+        let timestamp = i128::from(seconds) * 1_000_000_000 + i128::from(fraction) * nanos;
+        packets.push(Packet {
+            timestamp: OffsetDateTime::from_unix_timestamp_nanos(timestamp)?,
+            link_type,
+            data: packet.to_vec(),
+        });
+        offset += 16 + len;
+    }
+
+    Ok(packets)
+}
+
+/// Interface described in a pcapng section.
+struct Interface {
+    link_type: u32,
+    resolution: Resolution,
+}
+
+/// Timestamp resolution of a pcapng interface.
+#[derive(Clone, Copy)]
+enum Resolution {
+    Decimal(u8),
+    Binary(u8),
+}
+
+impl Resolution {
+    fn to_nanos(self, timestamp: u64) -> i128 {
+        let timestamp = i128::from(timestamp);
+        match self {
+            Self::Decimal(exp) if exp <= 9 => timestamp * 10_i128.pow(u32::from(9 - exp)),
+            Self::Decimal(exp) => timestamp / 10_i128.pow(u32::from(exp - 9).min(30)),
+            Self::Binary(exp) => (timestamp * 1_000_000_000) >> exp.min(64),
+        }
+    }
+}
+
+/// Reads packets from a pcapng capture.\
+/// **Note** that only enhanced and simple packet blocks are read, all other blocks are skipped.
+fn read_pcapng(data: &[u8]) -> Result<Vec<Packet>> {
+    let mut packets = Vec::new();
+    let mut interfaces = Vec::<Interface>::new();
+    let mut bytes = Bytes { data, le: true };
+    let mut last_timestamp = OffsetDateTime::UNIX_EPOCH;
+
+    let mut offset = 0;
+    while offset < data.len() {
+        if bytes.u32(offset)? == PCAPNG_SECTION_HEADER {
+            let magic = bytes.slice(offset + 8, 4)?;
+            bytes.le = match u32::from_le_bytes(magic.try_into()?) {
+                PCAPNG_BYTE_ORDER_MAGIC => true,
+                _ if u32::from_be_bytes(magic.try_into()?) == PCAPNG_BYTE_ORDER_MAGIC => false,
+                _ => bail!("invalid pcapng byte order magic at offset {offset}"),
+            };
+            interfaces.clear();
+        }
+
+        let block_type = bytes.u32(offset)?;
+        let len = bytes.u32(offset + 4)? as usize;
+        if len < 12 {
+            bail!("invalid pcapng block length {len} at offset {offset}");
+        }
+
+        let body = bytes.slice(offset + 8, len - 12)?;
+        let body_offset = offset + 8;
+        match block_type {
+            PCAPNG_INTERFACE_DESCRIPTION => interfaces.push(Interface {
+                link_type: u32::from(bytes.u16(body_offset)?),
+                resolution: read_resolution(&bytes, body_offset + 8, body_offset + body.len())?,
+            }),
+            PCAPNG_ENHANCED_PACKET => {
+                let interface = bytes.u32(body_offset)? as usize;
+                let interface = interfaces
+                    .get(interface)
+                    .ok_or_else(|| anyhow!("unknown pcapng interface {interface} at offset {offset}"))?;
+                let timestamp = (u64::from(bytes.u32(body_offset + 4)?) << 32) | u64::from(bytes.u32(body_offset + 8)?);
+                let captured = bytes.u32(body_offset + 12)? as usize;
+
+                last_timestamp = OffsetDateTime::from_unix_timestamp_nanos(interface.resolution.to_nanos(timestamp))?;
+                packets.push(Packet {
+                    timestamp: last_timestamp,
+                    link_type: interface.link_type,
+                    data: bytes.slice(body_offset + 20, captured)?.to_vec(),
+                });
+            },
+            PCAPNG_SIMPLE_PACKET => {
+                let interface = interfaces
+                    .first()
+                    .ok_or_else(|| anyhow!("simple packet without an interface at offset {offset}"))?;
+                let original = bytes.u32(body_offset)? as usize;
+                packets.push(Packet {
+                    timestamp: last_timestamp,
+                    link_type: interface.link_type,
+                    data: body.get(4..).unwrap_or_default().iter().take(original).copied().collect(),
+                });
+            },
+            _ => {},
+        }
+
+        offset += len;
+    }
+
+    Ok(packets)
+}
+
+/// Reads `if_tsresol` option of a pcapng interface description block, defaults to microseconds.
+fn read_resolution(bytes: &Bytes, mut offset: usize, end: usize) -> Result<Resolution> {
+    while offset + 4 <= end {
+        let code = bytes.u16(offset)?;
+        let len = usize::from(bytes.u16(offset + 2)?);
+        if code == 0 {
+            break;
+        }
+
+        if code == PCAPNG_OPTION_TSRESOL && len == 1 {
+            let value = bytes.slice(offset + 4, 1)?[0];
+            return Ok(if value & 0x80 == 0 {
+                Resolution::Decimal(value)
+            } else {
+                Resolution::Binary(value & 0x7F)
+            });
+        }
+
+        offset += 4 + len.next_multiple_of(4);
+    }
+
+    Ok(Resolution::Decimal(6))
+}
+
+/// Bounds-checked reader of integers with configurable byte order.
+struct Bytes<'a> {
+    data: &'a [u8],
+    le: bool,
+}
+
+impl Bytes<'_> {
+    fn slice(&self, offset: usize, len: usize) -> Result<&[u8]> {
+        offset
+            .checked_add(len)
+            .and_then(|end| self.data.get(offset..end))
+            .ok_or_else(|| anyhow!("capture is truncated at offset {offset}"))
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16> {
+        let bytes = self.slice(offset, 2)?.try_into()?;
+        Ok(if self.le {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32> {
+        let bytes = self.slice(offset, 4)?.try_into()?;
+        Ok(if self.le {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+}
@@ -0,0 +1,161 @@
+use super::*;
+use crate::packet::decode;
+use crate::timeline::{Keys, TimelineEntry, extract_frames};
+use common::capture::{Direction, Transport};
+use common::dc09::{DC09Error, calculate_crc};
+use common::keys::SecretKey;
+
+/// Builds Ethernet frame with IPv4 and TCP (`sequence` is `Some`) or UDP headers.
+fn ethernet_packet(source: u16, destination: u16, sequence: Option<u32>, payload: &[u8]) -> Vec<u8> {
+    let transport = match sequence {
+        Some(sequence) => {
+            let mut tcp = Vec::new();
+            tcp.extend_from_slice(&source.to_be_bytes());
+            tcp.extend_from_slice(&destination.to_be_bytes());
+            tcp.extend_from_slice(&sequence.to_be_bytes());
+            tcp.extend_from_slice(&[0, 0, 0, 0, 0x50, 0x18, 0xFF, 0xFF, 0, 0, 0, 0]);
+            tcp
+        },
+        None => {
+            let mut udp = Vec::new();
+            udp.extend_from_slice(&source.to_be_bytes());
+            udp.extend_from_slice(&destination.to_be_bytes());
+            udp.extend_from_slice(&(8 + payload.len() as u16).to_be_bytes());
+            udp.extend_from_slice(&[0, 0]);
+            udp
+        },
+    };
+
+    let total = (20 + transport.len() + payload.len()) as u16;
+    let mut packet = vec![0; 12];
+    packet.extend_from_slice(&[0x08, 0x00, 0x45, 0]);
+    packet.extend_from_slice(&total.to_be_bytes());
+    packet.extend_from_slice(&[0, 0, 0x40, 0, 64, if sequence.is_some() { 6 } else { 17 }, 0, 0]);
+    packet.extend_from_slice(&[10, 0, 0, 1, 10, 0, 0, 2]);
+    packet.extend_from_slice(&transport);
+    packet.extend_from_slice(payload);
+    packet
+}
+
+fn pcap(packets: &[Vec<u8>]) -> Vec<u8> {
+    let mut data = vec![0xD4, 0xC3, 0xB2, 0xA1, 2, 0, 4, 0];
+    data.extend_from_slice(&[0; 8]);
+    data.extend_from_slice(&65535_u32.to_le_bytes());
+    data.extend_from_slice(&1_u32.to_le_bytes());
+    for (i, packet) in packets.iter().enumerate() {
+        data.extend_from_slice(&1_700_000_000_u32.to_le_bytes());
+        data.extend_from_slice(&(i as u32 * 1_000).to_le_bytes());
+        data.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        data.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        data.extend_from_slice(packet);
+    }
+
+    data
+}
+
+#[test]
+fn read_pcap_test() {
+    let packet = ethernet_packet(40000, 8080, None, b"\nframe\r");
+    let packets = read_packets(&pcap(&[packet.clone(), packet])).unwrap();
+
+    assert_eq!(2, packets.len());
+    assert_eq!(1, packets[0].link_type);
+    assert_eq!(1_700_000_000_001_000_000, packets[1].timestamp.unix_timestamp_nanos());
+
+    let segment = decode(packets[0].link_type, &packets[0].data).unwrap();
+    assert_eq!(Transport::Udp, segment.transport);
+    assert_eq!("10.0.0.1:40000", segment.source.to_string());
+    assert_eq!("10.0.0.2:8080", segment.destination.to_string());
+    assert_eq!(b"\nframe\r", segment.payload);
+}
+
+#[test]
+fn read_pcapng_test() {
+    let packet = ethernet_packet(40000, 8080, None, b"\nframe\r");
+    let mut data = Vec::new();
+    data.extend_from_slice(&0x0A0D_0D0A_u32.to_le_bytes());
+    data.extend_from_slice(&28_u32.to_le_bytes());
+    data.extend_from_slice(&0x1A2B_3C4D_u32.to_le_bytes());
+    data.extend_from_slice(&[1, 0, 0, 0]);
+    data.extend_from_slice(&u64::MAX.to_le_bytes());
+    data.extend_from_slice(&28_u32.to_le_bytes());
+
+    data.extend_from_slice(&1_u32.to_le_bytes());
+    data.extend_from_slice(&28_u32.to_le_bytes());
+    data.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0]);
+    data.extend_from_slice(&[9, 0, 1, 0, 9, 0, 0, 0]);
+    data.extend_from_slice(&28_u32.to_le_bytes());
+
+    let padded = packet.len().next_multiple_of(4);
+    let len = (32 + padded) as u32;
+    let timestamp = 1_700_000_000_000_000_000_u64;
+    data.extend_from_slice(&6_u32.to_le_bytes());
+    data.extend_from_slice(&len.to_le_bytes());
+    data.extend_from_slice(&0_u32.to_le_bytes());
+    data.extend_from_slice(&((timestamp >> 32) as u32).to_le_bytes());
+    data.extend_from_slice(&(timestamp as u32).to_le_bytes());
+    data.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+    data.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+    data.extend_from_slice(&packet);
+    data.resize(data.len() + padded - packet.len(), 0);
+    data.extend_from_slice(&len.to_le_bytes());
+
+    let packets = read_packets(&data).unwrap();
+    assert_eq!(1, packets.len());
+    assert_eq!(packet, packets[0].data);
+    assert_eq!(i128::from(timestamp), packets[0].timestamp.unix_timestamp_nanos());
+}
+
+#[test]
+fn reassemble_tcp_test() {
+    let packets = read_packets(&pcap(&[
+        ethernet_packet(40000, 8080, Some(100), b"\nDBA30013\"NULL\""),
+        ethernet_packet(40000, 8080, Some(121), b"[]\r"),
+        ethernet_packet(40000, 8080, Some(100), b"\nDBA30013\"NULL\""),
+        ethernet_packet(40000, 8080, Some(115), b"0001#1"),
+        ethernet_packet(8080, 40000, Some(5000), b"\nACK\r"),
+        ethernet_packet(40000, 9999, Some(1), b"\nignored\r"),
+    ]))
+    .unwrap();
+
+    let frames = extract_frames(&packets, &[8080]);
+    assert_eq!(2, frames.len());
+    assert_eq!(b"\nDBA30013\"NULL\"0001#1[]\r".as_slice(), frames[0].data);
+    assert_eq!(Direction::Inbound, frames[0].direction);
+    assert_eq!(Direction::Outbound, frames[1].direction);
+    assert_eq!("10.0.0.2:40000", frames[1].peer.to_string());
+}
+
+#[test]
+fn empty_encrypted_payload_test() {
+    let body = "\"*SIA-DCS\"0001#1234";
+    let frame = format!("\n{:04X}{:04X}{body}\r", calculate_crc(body), body.len());
+    let packets = read_packets(&pcap(&[ethernet_packet(40000, 8080, None, frame.as_bytes())])).unwrap();
+
+    let frames = extract_frames(&packets, &[8080]);
+    assert_eq!(1, frames.len());
+
+    let keys = Keys::new(None, Some(SecretKey::from("aaaaaaaaaaaaaaaa")));
+    let entry = TimelineEntry::new(&frames[0], &keys);
+    assert_eq!(None, entry.token);
+    assert_eq!(Some(DC09Error::ParsePayloadError.to_string()), entry.error);
+}
+
+#[test]
+fn invalid_capture_test() {
+    assert!(read_packets(b"DC09CAP1").is_err());
+    assert!(read_packets(&pcap(&[vec![0; 10]])[..40]).is_err());
+}
+
+#[test]
+fn truncated_udp_header_test() {
+    let packet = ethernet_packet(40000, 8080, None, b"\nframe\r");
+    for len in 14 + 20 + 4..14 + 20 + 8 {
+        assert!(decode(1, &packet[..len]).is_none());
+    }
+
+    // A declared length shorter than the header yields an empty payload.
+    let mut packet = ethernet_packet(40000, 8080, None, b"\nframe\r");
+    packet[14 + 20 + 4..14 + 20 + 6].copy_from_slice(&2_u16.to_be_bytes());
+    assert_eq!(b"", decode(1, &packet).unwrap().payload);
+}
@@ -0,0 +1,56 @@
+use std::collections::BTreeMap;
+
+/// Reassembles a single direction of a TCP connection and splits it into DC09 frames.
+#[derive(Debug, Default)]
+pub struct TcpStream {
+    base: Option<u32>,
+    next: u32,
+    pending: BTreeMap<u32, Vec<u8>>,
+    buffer: Vec<u8>,
+}
+
+impl TcpStream {
+    /// Adds a segment to the stream and returns frames completed by it.\
+    /// **Note** that retransmitted and out-of-order segments are handled, data before the first seen segment is ignored.
+    pub fn push(&mut self, sequence: u32, syn: bool, payload: &[u8]) -> Vec<Vec<u8>> {
+        if syn {
+            self.base = Some(sequence.wrapping_add(1));
+            self.next = 0;
+            self.pending.clear();
+            self.buffer.clear();
+        }
+
+        let base = *self.base.get_or_insert(sequence);
+        if payload.is_empty() {
+            return Vec::new();
+        }
+
+        let start = sequence.wrapping_sub(base).wrapping_add(u32::from(syn));
+        if start.saturating_add(payload.len() as u32) > self.next {
+            self.pending.entry(start).or_insert_with(|| payload.to_vec());
+        }
+
+        while let Some(entry) = self.pending.first_entry()
+            && *entry.key() <= self.next
+        {
+            let (start, data) = entry.remove_entry();
+            let skip = (self.next - start) as usize;
+            if let Some(data) = data.get(skip..) {
+                self.buffer.extend_from_slice(data);
+                self.next += data.len() as u32;
+            }
+        }
+
+        split_frames(&mut self.buffer)
+    }
+}
+
+/// Removes complete frames (terminated by `\r`) from the `buffer`.
+pub fn split_frames(buffer: &mut Vec<u8>) -> Vec<Vec<u8>> {
+    let mut frames = Vec::new();
+    while let Some(end) = buffer.iter().position(|b| *b == b'\r') {
+        frames.push(buffer.drain(..=end).collect());
+    }
+
+    frames
+}
@@ -0,0 +1,190 @@
+use common::capture::{CaptureRecord, Direction, Transport};
+use common::dc09::{DC09Message, parse_dc09_account_name};
+use common::keys::SecretKey;
+use common::scenarios::Scenarios;
+use common::time::OffsetDateTime;
+use common::time::format_description::well_known::Rfc3339;
+use common::utils::get_account_name;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use crate::packet::decode;
+use crate::pcap::Packet;
+use crate::stream::{TcpStream, split_frames};
+
+/// Maps accounts to keys used to decrypt their messages.
+pub struct Keys {
+    accounts: HashMap<String, Option<SecretKey>>,
+    default: Option<SecretKey>,
+}
+
+impl Keys {
+    /// Creates new [`Keys`] instance from the scenarios file and the default key.
+    pub fn new(scenarios: Option<&Scenarios>, default: Option<SecretKey>) -> Self {
+        let mut accounts = HashMap::new();
+        for dialler in scenarios.map(|s| s.diallers.as_slice()).unwrap_or_default() {
+            let account = dialler.name.parse::<u32>().ok();
+            for i in 0..dialler.count.max(1) {
+                accounts.insert(get_account_name(i, account, &dialler.name, false), dialler.key.clone());
+            }
+        }
+
+        Self { accounts, default }
+    }
+
+    /// Returns key for the specified frame.
+    pub fn get(&self, frame: &str) -> Option<&str> {
+        match parse_dc09_account_name(frame).ok().and_then(|a| self.accounts.get(&a)) {
+            Some(key) => key.as_ref().map(SecretKey::expose),
+            None => self.default.as_ref().map(SecretKey::expose),
+        }
+    }
+}
+
+/// DC09 frame extracted from the capture.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub timestamp: OffsetDateTime,
+    pub transport: Transport,
+    pub direction: Direction,
+    pub peer: SocketAddr,
+    pub receiver: SocketAddr,
+    pub data: Vec<u8>,
+}
+
+impl From<&Frame> for CaptureRecord {
+    fn from(frame: &Frame) -> Self {
+        Self {
+            timestamp: frame.timestamp,
+            transport: frame.transport,
+            direction: frame.direction,
+            peer: frame.peer,
+            frame: frame.data.clone(),
+        }
+    }
+}
+
+/// Extracts DC09 frames exchanged with receivers listening on any of the `ports`.
+pub fn extract_frames(packets: &[Packet], ports: &[u16]) -> Vec<Frame> {
+    let mut frames = Vec::new();
+    let mut streams = HashMap::<(SocketAddr, SocketAddr), TcpStream>::new();
+    for packet in packets {
+        let Some(segment) = decode(packet.link_type, &packet.data) else {
+            continue;
+        };
+
+        let (direction, peer, receiver) = if ports.contains(&segment.destination.port()) {
+            (Direction::Inbound, segment.source, segment.destination)
+        } else if ports.contains(&segment.source.port()) {
+            (Direction::Outbound, segment.destination, segment.source)
+        } else {
+            continue;
+        };
+
+        let data = match segment.tcp {
+            Some(tcp) => {
+                streams
+                    .entry((segment.source, segment.destination))
+                    .or_default()
+                    .push(tcp.sequence, tcp.syn, segment.payload)
+            },
+            None => {
+                let mut buffer = segment.payload.to_vec();
+                let mut data = split_frames(&mut buffer);
+                if buffer.iter().any(|b| !b.is_ascii_whitespace()) {
+                    data.push(buffer);
+                }
+
+                data
+            },
+        };
+
+        frames.extend(data.into_iter().map(|data| Frame {
+            timestamp: packet.timestamp,
+            transport: segment.transport,
+            direction,
+            peer,
+            receiver,
+            data,
+        }));
+    }
+
+    frames
+}
+
+/// Single entry of the messages timeline.
+#[derive(Debug, Serialize)]
+pub struct TimelineEntry {
+    pub timestamp: String,
+    pub transport: Transport,
+    pub direction: Direction,
+    pub peer: SocketAddr,
+    pub receiver: SocketAddr,
+    pub frame: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decrypted: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sequence: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl TimelineEntry {
+    /// Creates new [`TimelineEntry`] by parsing the frame with a key from `keys`.
+    pub fn new(frame: &Frame, keys: &Keys) -> Self {
+        let raw = String::from_utf8_lossy(&frame.data);
+        let parsed = DC09Message::try_from(&raw, keys.get(&raw));
+
+        let mut entry = Self {
+            timestamp: frame.timestamp.format(&Rfc3339).unwrap_or_default(),
+            transport: frame.transport,
+            direction: frame.direction,
+            peer: frame.peer,
+            receiver: frame.receiver,
+            frame: raw.trim().to_owned(),
+            decrypted: None,
+            token: None,
+            account: None,
+            sequence: None,
+            data: None,
+            error: None,
+        };
+
+        match parsed {
+            Ok(msg) => {
+                entry.decrypted = msg.was_encrypted().then(|| msg.to_string().trim().to_owned());
+                entry.token = Some(msg.token);
+                entry.account = Some(msg.account);
+                entry.sequence = Some(msg.sequence);
+                entry.data = msg.data;
+            },
+            Err(e) => entry.error = Some(e.to_string()),
+        }
+
+        entry
+    }
+
+    /// Formats entry as a single timeline line.
+    pub fn to_text(&self) -> String {
+        let mut line = format!(
+            "{} {} {} {} {}",
+            self.timestamp, self.transport, self.peer, self.direction, self.frame
+        );
+        if let Some(decrypted) = &self.decrypted {
+            line.push_str(&format!(" → {decrypted}"));
+        }
+
+        if let Some(error) = &self.error {
+            line.push_str(&format!(" ({error})"));
+        }
+
+        line
+    }
+}
@@ -0,0 +1,59 @@
+use common::capture::{CaptureRecord, CaptureWriter, Direction, Transport};
+use std::fs::File;
+use std::io::BufWriter;
+use std::net::SocketAddr;
+use std::path::Path;
+use tokio::sync::mpsc::{Receiver, Sender, channel};
+
+use crate::utils::increase_capture_records_dropped;
+
+/// Maximum number of records waiting to be written to the capture file.
+const CAPTURE_QUEUE_SIZE: usize = 10_000;
+
+/// Records inbound and outbound frames to a capture file.\
+/// **Note** that records are written by a background thread and flushed whenever there are no pending records, records
+/// are dropped while the queue is full.
+#[derive(Debug, Clone)]
+pub struct Capture {
+    tx: Sender<CaptureRecord>,
+}
+
+impl Capture {
+    /// Creates the capture file and starts the background writer.
+    pub fn start(path: &Path) -> std::io::Result<Self> {
+        let writer = CaptureWriter::new(BufWriter::new(File::create(path)?))?;
+        let (tx, rx) = channel(CAPTURE_QUEUE_SIZE);
+        let path = path.display().to_string();
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = write_records(writer, rx) {
+                log::error!("capture {path}: {e}");
+            }
+        });
+
+        Ok(Self { tx })
+    }
+
+    /// Records a frame exchanged with the `peer`.
+    pub fn record(&self, transport: Transport, direction: Direction, peer: SocketAddr, frame: &[u8]) {
+        if self
+            .tx
+            .try_send(CaptureRecord::new(transport, direction, peer, frame))
+            .is_err()
+        {
+            increase_capture_records_dropped();
+        }
+    }
+}
+
+fn write_records(mut writer: CaptureWriter<BufWriter<File>>, mut rx: Receiver<CaptureRecord>) -> std::io::Result<()> {
+    while let Some(record) = rx.blocking_recv() {
+        writer.write(&record)?;
+        while let Ok(record) = rx.try_recv() {
+            writer.write(&record)?;
+        }
+
+        writer.flush()?;
+    }
+
+    Ok(())
+}
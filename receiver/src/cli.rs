@@ -1,52 +1,266 @@
-use clap::Parser;
-use common::logging::DisplayMode;
+use clap::builder::RangedU64ValueParser;
+use clap::{CommandFactory, FromArgMatches, Parser};
+use common::delay::DelayProfile;
+use common::keys::{AccountSelector, CachedKeyProvider, DEFAULT_KEY_REFRESH, KeySource, SecretKey};
+use common::logging::{DisplayMode, LoggingArgs};
 use common::scenarios::Scenarios;
-use common::utils::{SharedKeysMap, parse_key, parse_scenarios_path};
+use common::utils::{SharedKeysMap, load_accounts, load_scenarios, parse_accounts_file, parse_key, parse_scenarios_file};
 use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
-use crate::server::{ResponseMode, ResponseModes};
+use crate::config_file::ConfigFile;
+use receiver::metrics::{ApiAuth, ApiCredential, PublicEndpoint, load_credentials, parse_credentials_file};
+use receiver::server::{
+    AccountLabels, ConnectionLimits, DEFAULT_ACCOUNT_LABEL_LIMIT, DEFAULT_KEY_GRACE, DEFAULT_MAX_FRAME_SIZE,
+    DEFAULT_SHUTDOWN_TIMEOUT, DEFAULT_UDP_QUEUE_SIZE, DEFAULT_UDP_RETRANSMIT_CACHE_SIZE, DEFAULT_UDP_RETRANSMIT_TTL,
+    DEFAULT_UDP_WORKERS, LimitPolicy, MIN_MAX_FRAME_SIZE, QueuePolicy, ResponseDelays, ResponseMode, ResponseModes, ServerConfig,
+    UdpPipeline, UnknownAccountPolicy,
+};
 
 /// Test server that handles DC09 dialler connections.
 #[derive(Parser, Debug, Clone)]
 #[command(version, about, long_about = None)]
 pub struct Args {
+    /// Configuration file in the TOML or YAML format, cli args and environment variables take precedence over it.
+    #[arg(long, env = "RECEIVER_CONFIG", value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Print the effective configuration in the TOML format and exit, keys are printed as `<redacted>` and have to be
+    /// replaced before the output is used as a configuration file.
+    #[arg(long)]
+    pub print_config: bool,
+
     /// IP address to listen on.
-    #[arg(default_value = "127.0.0.1")]
+    #[arg(env = "RECEIVER_ADDRESS", default_value = "127.0.0.1")]
     pub address: IpAddr,
 
     /// Port number to listen on.
-    #[arg(long, short, default_value = "8080")]
+    #[arg(long, short, env = "RECEIVER_PORT", default_value = "8080")]
     pub port: u16,
 
     /// Port number for metrics server.
-    #[arg(long, short, value_name = "PORT", default_value = "9090")]
+    #[arg(long, short, env = "RECEIVER_METRICS", value_name = "PORT", default_value = "9090")]
     pub metrics: u16,
 
     /// Key to decrypt DC09 messages (16, 24 or 32 bytes long).
-    #[arg(long, short, value_parser = parse_key)]
-    pub key: Option<String>,
+    #[arg(long, short, env = "RECEIVER_KEY", value_parser = parse_key)]
+    pub key: Option<SecretKey>,
+
+    /// Key source used for accounts without a key: key file, `env:PREFIX` or `http://` URL of a key service.
+    #[arg(long, env = "RECEIVER_KEY_SOURCE", value_name = "SOURCE", value_delimiter = ',')]
+    pub key_source: Vec<KeySource>,
+
+    /// Interval of reloading keys from the key sources, in seconds (0 disables refreshing).
+    #[arg(long, env = "RECEIVER_KEY_REFRESH", value_name = "SECS", default_value_t = DEFAULT_KEY_REFRESH)]
+    pub key_refresh: u64,
+
+    /// Time the previous key of an account is accepted after its next key becomes valid, in seconds.
+    #[arg(long, env = "RECEIVER_KEY_GRACE", value_name = "SECS", default_value_t = DEFAULT_KEY_GRACE)]
+    pub key_grace: u64,
+
+    /// Candidate key tried for encrypted messages from accounts missing in the scenarios or accounts file that can't be
+    /// decrypted with their key, the matching key is learned for the account.
+    #[arg(long, env = "RECEIVER_TRIAL_KEY", value_name = "KEY", value_delimiter = ',', value_parser = parse_key)]
+    pub trial_key: Vec<SecretKey>,
 
     /// Display mode for received messages.
-    #[arg(long, value_enum, value_name = "MODE", default_value = "target")]
+    #[arg(long, env = "RECEIVER_SHOW", value_enum, value_name = "MODE", default_value = "target")]
     pub show: DisplayMode,
 
     /// Send `NAK` instead of `ACK` for received messages.
-    #[arg(long, conflicts_with = "duh")]
+    #[arg(long, env = "RECEIVER_NAK", conflicts_with = "duh")]
     pub nak: bool,
 
     /// Send `DUH` instead of `ACK` for received messages.
-    #[arg(long, conflicts_with = "nak")]
+    #[arg(long, env = "RECEIVER_DUH", conflicts_with = "nak")]
     pub duh: bool,
 
+    /// Delay applied before sending responses: `fixed:MS`, `uniform:MIN:MAX`, `normal:MEAN:STD_DEV` or `histogram:FILE`.
+    #[arg(long, env = "RECEIVER_DELAY", value_name = "PROFILE")]
+    pub delay: Option<DelayProfile>,
+
+    /// Close TCP connections idle for the specified number of seconds (0 means never).
+    #[arg(long, env = "RECEIVER_IDLE_TIMEOUT", value_name = "SECS", default_value_t = 0)]
+    pub idle_timeout: u64,
+
+    /// Maximum number of concurrent TCP connections (0 means unlimited).
+    #[arg(long, env = "RECEIVER_MAX_CONNECTIONS", value_name = "COUNT", default_value_t = 0)]
+    pub max_connections: usize,
+
+    /// Maximum number of concurrent TCP connections per client IP address (0 means unlimited).
+    #[arg(long, env = "RECEIVER_MAX_CONNECTIONS_PER_IP", value_name = "COUNT", default_value_t = 0)]
+    pub max_connections_per_ip: usize,
+
+    /// Behaviour for TCP connections exceeding connection limits.
+    #[arg(
+        long,
+        env = "RECEIVER_LIMIT_POLICY",
+        value_enum,
+        value_name = "POLICY",
+        default_value = "reject"
+    )]
+    pub limit_policy: LimitPolicy,
+
+    /// Maximum size of a single DC09 frame (TCP) or datagram (UDP) in bytes, at least 64.
+    #[arg(long, env = "RECEIVER_MAX_FRAME_SIZE", value_name = "BYTES", default_value_t = DEFAULT_MAX_FRAME_SIZE)]
+    #[arg(value_parser = RangedU64ValueParser::<usize>::new().range(MIN_MAX_FRAME_SIZE as u64..))]
+    pub max_frame_size: usize,
+
+    /// Number of workers parsing and decrypting UDP datagrams.
+    #[arg(long, env = "RECEIVER_UDP_WORKERS", value_name = "COUNT", default_value_t = DEFAULT_UDP_WORKERS)]
+    #[arg(value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub udp_workers: usize,
+
+    /// Capacity of UDP datagram and response queues.
+    #[arg(long, env = "RECEIVER_UDP_QUEUE_SIZE", value_name = "COUNT", default_value_t = DEFAULT_UDP_QUEUE_SIZE)]
+    #[arg(value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub udp_queue_size: usize,
+
+    /// Behaviour for UDP datagrams received while the datagram queue is full.
+    #[arg(
+        long,
+        env = "RECEIVER_UDP_QUEUE_POLICY",
+        value_enum,
+        value_name = "POLICY",
+        default_value = "drop"
+    )]
+    pub udp_queue_policy: QueuePolicy,
+
+    /// Replay cached responses for UDP frames retransmitted within the specified number of seconds (0 disables the cache).
+    #[arg(long, env = "RECEIVER_UDP_RETRANSMIT_TTL", value_name = "SECS", default_value_t = DEFAULT_UDP_RETRANSMIT_TTL)]
+    pub udp_retransmit_ttl: u64,
+
+    /// Maximum number of cached responses for retransmitted UDP frames.
+    #[arg(long, env = "RECEIVER_UDP_RETRANSMIT_CACHE_SIZE", value_name = "COUNT")]
+    #[arg(default_value_t = DEFAULT_UDP_RETRANSMIT_CACHE_SIZE, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub udp_retransmit_cache_size: usize,
+
     /// Configuration file specifying defined scenarios for the run.
-    #[arg(long, value_parser = parse_scenarios_path)]
-    pub scenarios: Option<Scenarios>,
+    #[arg(long, env = "RECEIVER_SCENARIOS", value_parser = parse_scenarios_file)]
+    pub scenarios: Option<PathBuf>,
+
+    /// File with additional known accounts that use the default key (one account per line).
+    #[arg(long, env = "RECEIVER_ACCOUNTS", value_name = "FILE", value_parser = parse_accounts_file)]
+    pub accounts: Option<PathBuf>,
+
+    /// Response policy for messages from accounts not present in the scenarios or accounts file.
+    #[arg(
+        long,
+        env = "RECEIVER_UNKNOWN_ACCOUNT",
+        value_enum,
+        value_name = "POLICY",
+        default_value = "accept"
+    )]
+    pub unknown_account: UnknownAccountPolicy,
+
+    /// Maximum number of accounts not present in the scenarios or accounts file labelled individually in metrics,
+    /// messages from further accounts are labelled as `other`.
+    #[arg(long, env = "RECEIVER_ACCOUNT_LABEL_LIMIT", value_name = "COUNT", default_value_t = DEFAULT_ACCOUNT_LABEL_LIMIT)]
+    pub account_label_limit: usize,
+
+    /// Disable per-account metric series, messages from all accounts are labelled as `all`.
+    #[arg(long, env = "RECEIVER_NO_ACCOUNT_LABELS")]
+    pub no_account_labels: bool,
+
+    /// Time to wait for in-flight connections on shutdown, in seconds.
+    #[arg(long, env = "RECEIVER_SHUTDOWN_TIMEOUT", value_name = "SECS", default_value_t = DEFAULT_SHUTDOWN_TIMEOUT)]
+    pub shutdown_timeout: u64,
+
+    /// Record all inbound and outbound frames to the specified capture file.
+    #[arg(long, env = "RECEIVER_CAPTURE", value_name = "FILE")]
+    pub capture: Option<PathBuf>,
+
+    /// Reload scenarios and accounts files when they change.
+    #[arg(long, env = "RECEIVER_WATCH")]
+    pub watch: bool,
+
+    /// Credential required by the HTTP API in the `NAME:SECRET` format, sent as a bearer token or basic authentication.
+    #[arg(long, env = "RECEIVER_API_TOKEN", value_name = "NAME:SECRET")]
+    pub api_token: Vec<ApiCredential>,
+
+    /// File with credentials required by the HTTP API (one `NAME:SECRET` per line).
+    #[arg(long, env = "RECEIVER_API_TOKEN_FILE", value_name = "FILE", value_parser = parse_credentials_file)]
+    pub api_token_file: Option<PathBuf>,
+
+    /// Read-only HTTP endpoints available without credentials when the HTTP API requires them.
+    #[arg(
+        long,
+        env = "RECEIVER_PUBLIC_ENDPOINTS",
+        value_enum,
+        value_name = "ENDPOINTS",
+        value_delimiter = ','
+    )]
+    #[arg(num_args = 0.., default_value = "metrics,healthz,readyz")]
+    pub public_endpoints: Vec<PublicEndpoint>,
+
+    #[command(flatten)]
+    pub logging: LoggingArgs,
 }
 
 impl Args {
+    /// Parses cli args and environment variables and merges them with the configuration file.\
+    /// **Note** that the precedence is: cli args, environment variables, configuration file and defaults.
+    pub fn load() -> Result<Self, String> {
+        let matches = Self::command().get_matches();
+        let mut args = Self::from_arg_matches(&matches).map_err(|e| e.to_string())?;
+        if let Some(path) = &args.config {
+            ConfigFile::load(path)?.merge_into(&mut args, &matches);
+        }
+
+        Ok(args)
+    }
+
+    /// Loads scenarios from the configured file.
+    pub fn load_scenarios(&self) -> Result<Option<Scenarios>, String> {
+        self.scenarios
+            .as_deref()
+            .map(|path| load_scenarios(path).map_err(|e| format!("{}: {e}", path.display())))
+            .transpose()
+    }
+
+    /// Loads additional accounts from the configured file.
+    pub fn load_accounts(&self) -> Result<Vec<AccountSelector>, String> {
+        self.accounts
+            .as_deref()
+            .map(|path| load_accounts(path).map_err(|e| format!("{}: {e}", path.display())))
+            .transpose()
+            .map(Option::unwrap_or_default)
+    }
+
     /// Returns a hash map with all keys provided to the app.
-    pub fn build_keys_map(&self) -> SharedKeysMap {
-        common::utils::build_keys_map(self.scenarios.as_ref(), self.key.as_deref())
+    pub fn build_keys_map(&self, scenarios: Option<&Scenarios>) -> SharedKeysMap {
+        common::utils::build_keys_map(scenarios, self.key.as_ref())
+    }
+
+    /// Loads keys from the configured key sources, `None` if there are no key sources.
+    pub async fn load_key_sources(&self) -> Result<Option<Arc<CachedKeyProvider>>, String> {
+        if self.key_source.is_empty() {
+            return Ok(None);
+        }
+
+        let provider = CachedKeyProvider::load(self.key_source.clone()).await?;
+        Ok(Some(Arc::new(provider)))
+    }
+
+    /// Builds server configuration from cli args and configuration files.
+    pub fn build_server_config(&self) -> Result<ServerConfig, String> {
+        let scenarios = self.load_scenarios()?;
+        let accounts = self.load_accounts()?;
+        let keys = self.build_keys_map(scenarios.as_ref());
+        let diallers = scenarios.map(|s| s.diallers).unwrap_or_default();
+
+        Ok(ServerConfig::new(&diallers, keys)
+            .with_msg_mode(self.show)
+            .with_limits(self.connection_limits())
+            .with_accounts(&accounts)
+            .with_unknown_account_policy(self.unknown_account)
+            .with_shutdown_timeout(Duration::from_secs(self.shutdown_timeout))
+            .with_udp_pipeline(self.udp_pipeline())
+            .with_account_labels(self.account_labels())
+            .with_trial_keys(self.trial_key.clone())
+            .with_key_grace(Duration::from_secs(self.key_grace)))
     }
 
     /// Returns response modes configured by cli args for the server: `ACK`, `NAK` or `DUH`.
@@ -59,4 +273,50 @@ impl Args {
             ResponseModes::new(ResponseMode::Ack, ResponseMode::Ack)
         }
     }
+
+    /// Returns connection lifecycle limits configured by cli args.
+    pub fn connection_limits(&self) -> ConnectionLimits {
+        ConnectionLimits {
+            idle_timeout: (self.idle_timeout > 0).then(|| Duration::from_secs(self.idle_timeout)),
+            max_connections: self.max_connections,
+            max_connections_per_ip: self.max_connections_per_ip,
+            policy: self.limit_policy,
+            max_frame_size: self.max_frame_size,
+        }
+    }
+
+    /// Returns UDP processing pipeline settings configured by cli args.
+    pub fn udp_pipeline(&self) -> UdpPipeline {
+        UdpPipeline {
+            workers: self.udp_workers,
+            queue_size: self.udp_queue_size,
+            policy: self.udp_queue_policy,
+            retransmit_ttl: (self.udp_retransmit_ttl > 0).then(|| Duration::from_secs(self.udp_retransmit_ttl)),
+            retransmit_cache_size: self.udp_retransmit_cache_size,
+        }
+    }
+
+    /// Returns cardinality controls of metrics labelled by account configured by cli args.
+    pub fn account_labels(&self) -> AccountLabels {
+        AccountLabels {
+            enabled: !self.no_account_labels,
+            limit: self.account_label_limit,
+        }
+    }
+
+    /// Returns authentication settings of the HTTP API configured by cli args and the credentials file.
+    pub fn api_auth(&self) -> Result<ApiAuth, String> {
+        let mut credentials = self.api_token.clone();
+        if let Some(path) = &self.api_token_file {
+            credentials.extend(load_credentials(path).map_err(|e| format!("{}: {e}", path.display()))?);
+        }
+
+        Ok(ApiAuth::new(credentials, self.public_endpoints.clone()))
+    }
+
+    /// Returns response delays configured by cli args, per-account delays from the scenarios file are part of the
+    /// server configuration.
+    pub fn response_delays(&self) -> ResponseDelays {
+        ResponseDelays::new(self.delay.clone())
+    }
 }
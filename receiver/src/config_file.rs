@@ -0,0 +1,346 @@
+use clap::ArgMatches;
+use clap::parser::ValueSource;
+use common::delay::DelayProfile;
+use common::keys::{KeySource, SecretKey};
+use common::logging::{DisplayMode, LogFormat, LogRotation, parse_log_level};
+use common::utils::{load_accounts, load_scenarios, parse_key};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+use crate::cli::Args;
+use receiver::metrics::{PublicEndpoint, load_credentials};
+use receiver::server::{LimitPolicy, MIN_MAX_FRAME_SIZE, QueuePolicy, UnknownAccountPolicy};
+
+#[cfg(test)]
+#[path = "./config_file.tests.rs"]
+mod config_file_tests;
+
+/// Receiver configuration file, every key mirrors the cli argument with the same name.\
+/// **Note** that keys missing in the file keep values from environment variables or defaults.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    pub address: Option<IpAddr>,
+    pub port: Option<u16>,
+    pub metrics: Option<u16>,
+    pub key: Option<SecretKey>,
+    pub show: Option<DisplayMode>,
+    pub nak: Option<bool>,
+    pub duh: Option<bool>,
+    pub scenarios: Option<PathBuf>,
+    pub accounts: Option<PathBuf>,
+    pub unknown_account: Option<UnknownAccountPolicy>,
+    pub shutdown_timeout: Option<u64>,
+    pub capture: Option<PathBuf>,
+    pub watch: Option<bool>,
+    pub delay: Option<DelayProfile>,
+    pub limits: LimitsSection,
+    pub udp: UdpSection,
+    pub account_labels: AccountLabelsSection,
+    pub keys: KeysSection,
+    pub api: ApiSection,
+    pub logging: LoggingSection,
+}
+
+/// `[limits]` section of the configuration file.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsSection {
+    pub idle_timeout: Option<u64>,
+    pub max_connections: Option<usize>,
+    pub max_connections_per_ip: Option<usize>,
+    pub policy: Option<LimitPolicy>,
+    pub max_frame_size: Option<usize>,
+}
+
+/// `[udp]` section of the configuration file.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UdpSection {
+    pub workers: Option<usize>,
+    pub queue_size: Option<usize>,
+    pub queue_policy: Option<QueuePolicy>,
+    pub retransmit_ttl: Option<u64>,
+    pub retransmit_cache_size: Option<usize>,
+}
+
+/// `[account_labels]` section of the configuration file.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AccountLabelsSection {
+    pub enabled: Option<bool>,
+    pub limit: Option<usize>,
+}
+
+/// `[keys]` section of the configuration file.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeysSection {
+    pub sources: Option<Vec<KeySource>>,
+    pub refresh: Option<u64>,
+    pub grace: Option<u64>,
+    pub trial: Option<Vec<SecretKey>>,
+}
+
+/// `[api]` section of the configuration file.\
+/// **Note** that credentials can be set only by cli args, environment variables or the credentials file.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiSection {
+    pub token_file: Option<PathBuf>,
+    pub public_endpoints: Option<Vec<PublicEndpoint>>,
+}
+
+/// `[logging]` section of the configuration file.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingSection {
+    pub format: Option<LogFormat>,
+    pub file: Option<PathBuf>,
+    pub rotation: Option<LogRotation>,
+    pub max_files: Option<usize>,
+    pub level: Option<String>,
+}
+
+impl ConfigFile {
+    /// Loads and validates configuration file in the TOML or YAML format (chosen by the file extension).\
+    /// **Note** that relative paths in the file are resolved against the directory of the file.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        let mut config = match path.extension().and_then(|e| e.to_str()) {
+            Some("yaml" | "yml") => Self::from_yaml(&content),
+            _ => Self::from_toml(&content),
+        }
+        .map_err(|e| format!("{}: {e}", path.display()))?;
+
+        let directory = path.parent().unwrap_or(Path::new(""));
+        config.resolve_paths(directory);
+        config.validate().map_err(|e| format!("{}: {e}", path.display()))?;
+        Ok(config)
+    }
+
+    /// Parses configuration in the TOML format, errors are prefixed with the offending key.
+    pub fn from_toml(content: &str) -> Result<Self, String> {
+        serde_path_to_error::deserialize(toml::Deserializer::new(content)).map_err(|e| match e.path().to_string() {
+            path if path == "." => e.inner().message().to_owned(),
+            path => format!("{path}: {}", e.inner().message()),
+        })
+    }
+
+    /// Parses configuration in the YAML format, errors are prefixed with the offending key.
+    pub fn from_yaml(content: &str) -> Result<Self, String> {
+        serde_yaml_ng::from_str(content).map_err(|e| e.to_string())
+    }
+
+    /// Serializes configuration in the TOML format.
+    pub fn to_toml(&self) -> Result<String, String> {
+        toml::to_string(self).map_err(|e| e.to_string())
+    }
+
+    /// Validates values that can't be checked during deserialization, errors are prefixed with the offending key.
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(key) = &self.key {
+            validate_key(key).map_err(|e| format!("key: {e}"))?;
+        }
+
+        for (index, key) in self.keys.trial.iter().flatten().enumerate() {
+            validate_key(key).map_err(|e| format!("keys.trial[{index}]: {e}"))?;
+        }
+
+        if self.nak == Some(true) && self.duh == Some(true) {
+            return Err("duh: can't be enabled together with nak".to_owned());
+        }
+
+        if let Some(delay) = &self.delay {
+            delay.validate().map_err(|e| format!("delay: {e}"))?;
+        }
+
+        if let Some(path) = &self.scenarios {
+            load_scenarios(path).map_err(|e| format!("scenarios: {}: {e}", path.display()))?;
+        }
+
+        if let Some(path) = &self.accounts {
+            load_accounts(path).map_err(|e| format!("accounts: {}: {e}", path.display()))?;
+        }
+
+        if self.limits.max_frame_size.is_some_and(|size| size < MIN_MAX_FRAME_SIZE) {
+            return Err(format!("limits.max_frame_size: value must be at least {MIN_MAX_FRAME_SIZE}"));
+        }
+
+        for (key, value) in [
+            ("udp.workers", self.udp.workers),
+            ("udp.queue_size", self.udp.queue_size),
+            ("udp.retransmit_cache_size", self.udp.retransmit_cache_size),
+        ] {
+            if value == Some(0) {
+                return Err(format!("{key}: value must be at least 1"));
+            }
+        }
+
+        if let Some(path) = &self.api.token_file {
+            load_credentials(path).map_err(|e| format!("api.token_file: {}: {e}", path.display()))?;
+        }
+
+        if let Some(level) = &self.logging.level {
+            parse_log_level(level).map_err(|e| format!("logging.level: {e}"))?;
+        }
+
+        Ok(())
+    }
+
+    /// Resolves relative paths against the `directory`.
+    fn resolve_paths(&mut self, directory: &Path) {
+        for path in [
+            &mut self.scenarios,
+            &mut self.accounts,
+            &mut self.capture,
+            &mut self.api.token_file,
+            &mut self.logging.file,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if path.is_relative() {
+                *path = directory.join(&*path);
+            }
+        }
+
+        for source in self.keys.sources.iter_mut().flatten() {
+            if let KeySource::File(path) = source
+                && path.is_relative()
+            {
+                *path = directory.join(&*path);
+            }
+        }
+    }
+
+    /// Applies values from the file to `args` that were not set on the command line or by environment variables.
+    pub fn merge_into(self, args: &mut Args, matches: &ArgMatches) {
+        let is_default = |id: &str| {
+            !matches!(
+                matches.value_source(id),
+                Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+            )
+        };
+
+        macro_rules! merge {
+            ($($id:literal: $field:expr => $value:expr),* $(,)?) => {
+                $(if let Some(value) = $value && is_default($id) {
+                    $field = value.into();
+                })*
+            };
+        }
+
+        merge! {
+            "address": args.address => self.address,
+            "port": args.port => self.port,
+            "metrics": args.metrics => self.metrics,
+            "key": args.key => self.key,
+            "key_source": args.key_source => self.keys.sources,
+            "key_refresh": args.key_refresh => self.keys.refresh,
+            "key_grace": args.key_grace => self.keys.grace,
+            "trial_key": args.trial_key => self.keys.trial,
+            "show": args.show => self.show,
+            "scenarios": args.scenarios => self.scenarios,
+            "accounts": args.accounts => self.accounts,
+            "unknown_account": args.unknown_account => self.unknown_account,
+            "shutdown_timeout": args.shutdown_timeout => self.shutdown_timeout,
+            "capture": args.capture => self.capture,
+            "watch": args.watch => self.watch,
+            "delay": args.delay => self.delay,
+            "idle_timeout": args.idle_timeout => self.limits.idle_timeout,
+            "max_connections": args.max_connections => self.limits.max_connections,
+            "max_connections_per_ip": args.max_connections_per_ip => self.limits.max_connections_per_ip,
+            "limit_policy": args.limit_policy => self.limits.policy,
+            "max_frame_size": args.max_frame_size => self.limits.max_frame_size,
+            "udp_workers": args.udp_workers => self.udp.workers,
+            "udp_queue_size": args.udp_queue_size => self.udp.queue_size,
+            "udp_queue_policy": args.udp_queue_policy => self.udp.queue_policy,
+            "udp_retransmit_ttl": args.udp_retransmit_ttl => self.udp.retransmit_ttl,
+            "udp_retransmit_cache_size": args.udp_retransmit_cache_size => self.udp.retransmit_cache_size,
+            "no_account_labels": args.no_account_labels => self.account_labels.enabled.map(|enabled| !enabled),
+            "account_label_limit": args.account_label_limit => self.account_labels.limit,
+            "api_token_file": args.api_token_file => self.api.token_file,
+            "public_endpoints": args.public_endpoints => self.api.public_endpoints,
+            "log_format": args.logging.log_format => self.logging.format,
+            "log_file": args.logging.log_file => self.logging.file,
+            "log_rotation": args.logging.log_rotation => self.logging.rotation,
+            "log_max_files": args.logging.log_max_files => self.logging.max_files,
+            "log_level": args.logging.log_level => self.logging.level,
+        }
+
+        // Response mode set on the command line replaces the one from the file, even if the other flag is used.
+        if is_default("nak") && is_default("duh") {
+            merge! {
+                "nak": args.nak => self.nak,
+                "duh": args.duh => self.duh,
+            }
+        }
+    }
+}
+
+impl From<&Args> for ConfigFile {
+    fn from(args: &Args) -> Self {
+        Self {
+            address: Some(args.address),
+            port: Some(args.port),
+            metrics: Some(args.metrics),
+            key: args.key.clone(),
+            show: Some(args.show),
+            nak: Some(args.nak),
+            duh: Some(args.duh),
+            scenarios: args.scenarios.clone(),
+            accounts: args.accounts.clone(),
+            unknown_account: Some(args.unknown_account),
+            shutdown_timeout: Some(args.shutdown_timeout),
+            capture: args.capture.clone(),
+            watch: Some(args.watch),
+            delay: args.delay.clone(),
+            limits: LimitsSection {
+                idle_timeout: Some(args.idle_timeout),
+                max_connections: Some(args.max_connections),
+                max_connections_per_ip: Some(args.max_connections_per_ip),
+                policy: Some(args.limit_policy),
+                max_frame_size: Some(args.max_frame_size),
+            },
+            udp: UdpSection {
+                workers: Some(args.udp_workers),
+                queue_size: Some(args.udp_queue_size),
+                queue_policy: Some(args.udp_queue_policy),
+                retransmit_ttl: Some(args.udp_retransmit_ttl),
+                retransmit_cache_size: Some(args.udp_retransmit_cache_size),
+            },
+            account_labels: AccountLabelsSection {
+                enabled: Some(!args.no_account_labels),
+                limit: Some(args.account_label_limit),
+            },
+            keys: KeysSection {
+                sources: Some(args.key_source.clone()),
+                refresh: Some(args.key_refresh),
+                grace: Some(args.key_grace),
+                trial: Some(args.trial_key.clone()),
+            },
+            api: ApiSection {
+                token_file: args.api_token_file.clone(),
+                public_endpoints: Some(args.public_endpoints.clone()),
+            },
+            logging: LoggingSection {
+                format: Some(args.logging.log_format),
+                file: args.logging.log_file.clone(),
+                rotation: Some(args.logging.log_rotation),
+                max_files: Some(args.logging.log_max_files),
+                level: args.logging.log_level.clone(),
+            },
+        }
+    }
+}
+
+/// Validates key from the configuration file, reporting keys redacted by `--print-config` separately.
+fn validate_key(key: &SecretKey) -> Result<(), String> {
+    if key.is_redacted() {
+        return Err("redacted by --print-config, replace it with the actual key".to_owned());
+    }
+
+    parse_key(key.expose()).map(|_| ())
+}
@@ -0,0 +1,164 @@
+use super::*;
+use clap::{CommandFactory, FromArgMatches};
+use receiver::server::LimitPolicy;
+
+fn parse_args(config: &ConfigFile, cli: &[&str]) -> Args {
+    let matches = Args::command().get_matches_from(std::iter::once("receiver").chain(cli.iter().copied()));
+    let mut args = Args::from_arg_matches(&matches).unwrap();
+    config.clone().merge_into(&mut args, &matches);
+    args
+}
+
+#[test]
+fn parse_toml_test() {
+    let config = ConfigFile::from_toml(
+        r#"
+        port = 8081
+        nak = true
+
+        [delay]
+        type = "fixed"
+        delay = 100
+
+        [limits]
+        policy = "queue"
+
+        [keys]
+        sources = ["env:DC09_KEY", "http://127.0.0.1:8200/keys"]
+        refresh = 30
+
+        [logging]
+        level = "info,receiver::messages=debug"
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(Some(8081), config.port);
+    assert_eq!(Some(true), config.nak);
+    assert_eq!(Some(DelayProfile::Fixed { delay: 100 }), config.delay);
+    assert_eq!(Some(LimitPolicy::Queue), config.limits.policy);
+    assert_eq!(None, config.udp.workers);
+    assert_eq!(Some(30), config.keys.refresh);
+    assert_eq!(
+        Some(KeySource::Env("DC09_KEY".to_owned())),
+        config.keys.sources.as_ref().map(|s| s[0].clone())
+    );
+    assert!(config.validate().is_ok());
+}
+
+#[test]
+fn parse_yaml_test() {
+    let config = ConfigFile::from_yaml("port: 8081\nudp:\n  workers: 2\n  queue_policy: block\n").unwrap();
+
+    assert_eq!(Some(8081), config.port);
+    assert_eq!(Some(2), config.udp.workers);
+    assert_eq!(Some(QueuePolicy::Block), config.udp.queue_policy);
+}
+
+#[test]
+fn invalid_config_test() {
+    let error = ConfigFile::from_toml("[limits]\npolicy = \"wait\"").unwrap_err();
+    assert!(error.starts_with("limits.policy: unknown variant `wait`"), "{error}");
+
+    let error = ConfigFile::from_toml("[udp]\nworkerz = 2").unwrap_err();
+    assert!(error.starts_with("udp.workerz: unknown field `workerz`"), "{error}");
+
+    let error = ConfigFile::from_toml("[keys]\nsources = [\"https://vault/keys\"]").unwrap_err();
+    assert!(error.starts_with("keys.sources[0]: unsupported key service URL"), "{error}");
+
+    let error = ConfigFile::from_yaml("port: 99999").unwrap_err();
+    assert!(error.starts_with("port: invalid value"), "{error}");
+
+    let error = ConfigFile::from_toml("key = \"short\"").unwrap().validate().unwrap_err();
+    assert!(error.starts_with("key: "), "{error}");
+
+    let error = ConfigFile::from_toml("[keys]\ntrial = [\"0123456789abcdef\", \"short\"]")
+        .unwrap()
+        .validate()
+        .unwrap_err();
+    assert!(error.starts_with("keys.trial[1]: "), "{error}");
+
+    let error = ConfigFile::from_toml("[keys]\ntrial = [\"<redacted>\"]")
+        .unwrap()
+        .validate()
+        .unwrap_err();
+    assert_eq!(
+        "keys.trial[0]: redacted by --print-config, replace it with the actual key",
+        error
+    );
+
+    let error = ConfigFile::from_toml("[udp]\nqueue_size = 0")
+        .unwrap()
+        .validate()
+        .unwrap_err();
+    assert_eq!("udp.queue_size: value must be at least 1", error);
+
+    let error = ConfigFile::from_toml("[limits]\nmax_frame_size = 0")
+        .unwrap()
+        .validate()
+        .unwrap_err();
+    assert_eq!("limits.max_frame_size: value must be at least 64", error);
+
+    let error = ConfigFile::from_toml("[logging]\nlevel = \"a=b=c\"")
+        .unwrap()
+        .validate()
+        .unwrap_err();
+    assert!(error.starts_with("logging.level: "), "{error}");
+}
+
+#[test]
+fn resolve_paths_test() {
+    let log_file = std::env::temp_dir().join("receiver.log");
+    let mut config = ConfigFile::from_toml("capture = \"frames.cap\"\n[keys]\nsources = [\"keys.txt\", \"env:KEY\"]").unwrap();
+    config.logging.file = Some(log_file.clone());
+    config.resolve_paths(Path::new("config"));
+
+    assert_eq!(Some(Path::new("config").join("frames.cap")), config.capture);
+    assert_eq!(Some(log_file), config.logging.file);
+    assert_eq!(
+        Some(vec![
+            KeySource::File(Path::new("config").join("keys.txt")),
+            KeySource::Env("KEY".to_owned())
+        ]),
+        config.keys.sources
+    );
+}
+
+#[test]
+fn merge_precedence_test() {
+    let config = ConfigFile::from_toml("port = 8081\nmetrics = 9091\nduh = true\n[udp]\nworkers = 2").unwrap();
+
+    let args = parse_args(&config, &[]);
+    assert_eq!(8081, args.port);
+    assert_eq!(9091, args.metrics);
+    assert_eq!(2, args.udp_workers);
+    assert!(args.duh);
+
+    let args = parse_args(&config, &["--port", "8082", "--nak"]);
+    assert_eq!(8082, args.port);
+    assert_eq!(9091, args.metrics);
+    assert!(args.nak);
+    assert!(!args.duh);
+}
+
+#[test]
+fn effective_config_test() {
+    let config = ConfigFile::from_toml("port = 8081\n[account_labels]\nenabled = false").unwrap();
+    let args = parse_args(&config, &["--udp-workers", "3"]);
+    let effective = ConfigFile::from_toml(&ConfigFile::from(&args).to_toml().unwrap()).unwrap();
+
+    assert_eq!(Some(8081), effective.port);
+    assert_eq!(Some(3), effective.udp.workers);
+    assert_eq!(Some(false), effective.account_labels.enabled);
+    assert_eq!(ConfigFile::from(&args), effective);
+}
+
+#[test]
+fn printed_keys_test() {
+    let config = ConfigFile::from_toml("key = \"0123456789abcdef\"").unwrap();
+    let printed = ConfigFile::from(&parse_args(&config, &[])).to_toml().unwrap();
+    assert!(!printed.contains("0123456789abcdef"));
+
+    let error = ConfigFile::from_toml(&printed).unwrap().validate().unwrap_err();
+    assert_eq!("key: redacted by --print-config, replace it with the actual key", error);
+}
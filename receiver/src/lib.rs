@@ -0,0 +1,12 @@
+//! DC09 receiver that can be embedded into integration tests.
+//!
+//! Servers are created with [`server::TcpServer`] or [`server::UdpServer`] and share the [`server::ServerState`],
+//! which holds the [`server::EventHandler`] deciding responses and the [`server::KeyProvider`] supplying keys.
+//! The [`testing`] module runs the receiver on OS-assigned ports inside tests.
+
+pub mod capture;
+pub mod metrics;
+pub mod reload;
+pub mod server;
+pub mod testing;
+pub mod utils;
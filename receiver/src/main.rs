@@ -1,28 +1,84 @@
 use anyhow::Result;
-use clap::Parser;
-use server::{Server, ServerConfig, TcpServer, UdpServer};
+use receiver::capture::Capture;
+use receiver::metrics::{self, AppState};
+use receiver::reload::ConfigReloader;
+use receiver::server::{ConfigKeys, Server, ServerState, SharedServerConfig, TcpServer, UdpServer};
 use std::sync::Arc;
-use std::sync::atomic::AtomicBool;
+use std::time::Duration;
 
-use crate::metrics::AppState;
+use crate::config_file::ConfigFile;
 
 mod cli;
-mod metrics;
-mod server;
-mod utils;
+mod config_file;
+
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let _logging_guard = common::logging::initialize("receiver")?;
+    let args = cli::Args::load().map_err(anyhow::Error::msg)?;
+    if args.print_config {
+        print!("{}", ConfigFile::from(&args).to_toml().map_err(anyhow::Error::msg)?);
+        return Ok(());
+    }
 
-    let args = cli::Args::parse();
+    let logging = common::logging::initialize("receiver", &args.logging)?;
+    let loader = {
+        let args = args.clone();
+        Box::new(move || args.build_server_config())
+    };
+    let paths = [args.scenarios.clone(), args.accounts.clone()]
+        .into_iter()
+        .flatten()
+        .collect();
+    let reloader = Arc::new(ConfigReloader::new(loader, paths).map_err(anyhow::Error::msg)?);
+    let auth = args.api_auth().map_err(anyhow::Error::msg)?;
+    let capture = args.capture.as_deref().map(Capture::start).transpose()?;
+    let key_sources = args.load_key_sources().await.map_err(anyhow::Error::msg)?;
+    let keys = ConfigKeys::new(reloader.config()).with_sources(key_sources.clone());
+    let server = ServerState::new(&reloader.config(), args.response_modes())
+        .with_response_delays(args.response_delays())
+        .with_keys(Arc::new(keys))
+        .with_capture(capture);
     let state = AppState {
-        tcp_ready: Arc::new(AtomicBool::new(false)),
-        udp_ready: Arc::new(AtomicBool::new(false)),
-        response_modes: Arc::new(args.response_modes()),
+        server,
+        config_reloader: Arc::clone(&reloader),
+        log_level: logging.level_handle(),
+        auth: Arc::new(auth),
     };
 
     metrics::register_all();
+
+    #[cfg(unix)]
+    {
+        let reloader = Arc::clone(&reloader);
+        tokio::spawn(async move {
+            if let Err(e) = reloader.watch_signal().await {
+                log::error!("unable to listen for SIGHUP: {e}");
+            }
+        });
+    }
+
+    if let Some(key_sources) = key_sources
+        && args.key_refresh > 0
+    {
+        tokio::spawn(key_sources.refresh_every(Duration::from_secs(args.key_refresh)));
+    }
+
+    if args.watch {
+        tokio::spawn(Arc::clone(&reloader).watch_files(WATCH_INTERVAL));
+    }
+
+    let shutdown = state.server.shutdown.clone();
+    tokio::spawn(async move {
+        match common::shutdown::wait_for_signal().await {
+            Ok(()) => {
+                log::info!("shutdown requested, draining connections");
+                shutdown.cancel();
+            },
+            Err(e) => log::error!("unable to listen for shutdown signals: {e}"),
+        }
+    });
+
     let metrics_state = state.clone();
     tokio::spawn(async move {
         if let Err(e) = metrics::start_metrics_server(args.address, args.metrics, metrics_state).await {
@@ -32,8 +88,8 @@ async fn main() -> Result<()> {
 
     log::info!("start listening on {}:{}", args.address, args.port);
     let (tcp, udp) = tokio::join!(
-        run_receiver::<TcpServer>(&args, state.clone()),
-        run_receiver::<UdpServer>(&args, state.clone())
+        run_receiver::<TcpServer>(&args, reloader.config(), state.server.clone()),
+        run_receiver::<UdpServer>(&args, reloader.config(), state.server.clone())
     );
 
     if let Err(error) = tcp {
@@ -44,19 +100,13 @@ async fn main() -> Result<()> {
         log::error!("udp: {error}");
     }
 
+    log::info!("receiver stopped");
     Ok(())
 }
 
-async fn run_receiver<T: Server>(args: &cli::Args, state: AppState) -> Result<()> {
-    let config = create_server_config(args);
+async fn run_receiver<T: Server>(args: &cli::Args, config: SharedServerConfig, state: ServerState) -> Result<()> {
     let mut server = T::new(format!("{}:{}", args.address, args.port), config, state).await?;
     server.run().await?;
 
     Ok(())
 }
-
-fn create_server_config(args: &cli::Args) -> ServerConfig {
-    let keys = args.build_keys_map();
-    let diallers = args.scenarios.as_ref().map(|s| s.diallers.clone()).unwrap_or_default();
-    ServerConfig::new(&diallers, keys).with_msg_mode(args.show)
-}
@@ -0,0 +1,257 @@
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use common::dc09::{is_account_prefix_valid, is_receiver_valid};
+use common::keys::{AccountSelector, SecretKey};
+use common::utils::parse_key;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use time::OffsetDateTime;
+
+use crate::metrics::AppState;
+use crate::metrics::server::ErrorResponse;
+use crate::server::{AccountConfig, KeyState, KeyVersion, ResponseMode, TrialMatch};
+
+#[cfg(test)]
+#[path = "./accounts.tests.rs"]
+mod accounts_tests;
+
+/// Path segment selecting entries that match any account.
+const ANY_ACCOUNT: &str = "*";
+
+/// Account representation returned by the HTTP API.\
+/// **Note** that keys are write-only and never returned.
+#[derive(Serialize)]
+pub struct AccountResponse {
+    /// Account name, `*` for entries matching any account.
+    account: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    receiver: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<String>,
+    has_key: bool,
+    /// States of the account key and the rotated keys, present only for accounts with rotated keys.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    keys: Vec<KeyStateResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    supervision: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response: Option<ResponseMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_seen: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    supervision_expired: Option<bool>,
+}
+
+impl AccountResponse {
+    fn new(selector: &AccountSelector, config: &AccountConfig, state: &AppState, grace: Duration) -> Self {
+        let account = selector.account.as_deref();
+        let keys = if config.rotation.is_empty() {
+            Vec::new()
+        } else {
+            config
+                .key_states(OffsetDateTime::now_utc(), grace)
+                .into_iter()
+                .map(|s| KeyStateResponse {
+                    valid_from: s.valid_from.map(|t| t.unix_timestamp()),
+                    state: s.state,
+                })
+                .collect()
+        };
+        let supervision = &state.server.supervision;
+        Self {
+            account: account.unwrap_or(ANY_ACCOUNT).to_owned(),
+            receiver: selector.receiver.clone(),
+            line: selector.line.clone(),
+            has_key: config.key.is_some() || !config.rotation.is_empty(),
+            keys,
+            supervision: config.supervision,
+            response: config.response,
+            last_seen: account.and_then(|a| supervision.last_seen(a)).map(|t| t.unix_timestamp()),
+            supervision_expired: account.zip(config.supervision).map(|(a, i)| supervision.is_expired(a, i)),
+        }
+    }
+}
+
+/// State of an account key returned by the HTTP API, `valid_from` is missing for the unrotated account key.
+#[derive(Serialize)]
+pub struct KeyStateResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    valid_from: Option<i64>,
+    state: KeyState,
+}
+
+/// Receiver number and line prefix narrowing the account in the path, both optional.
+#[derive(Deserialize)]
+pub struct SelectorQuery {
+    receiver: Option<String>,
+    line: Option<String>,
+}
+
+impl SelectorQuery {
+    /// Returns registry selector of the `account` (`*` matches any account) on the queried receiver and line.
+    fn selector(&self, account: &str) -> Result<AccountSelector, (StatusCode, Json<ErrorResponse>)> {
+        let bad_request = |error| Err((StatusCode::BAD_REQUEST, Json(ErrorResponse { error })));
+        if account != ANY_ACCOUNT && (account.is_empty() || !account.chars().all(char::is_alphanumeric)) {
+            return bad_request(format!("invalid account name '{account}'"));
+        }
+
+        if let Some(receiver) = self.receiver.as_deref().filter(|r| !is_receiver_valid(r)) {
+            return bad_request(format!("invalid receiver number '{receiver}'"));
+        }
+
+        if let Some(line) = self.line.as_deref().filter(|l| !is_account_prefix_valid(l)) {
+            return bad_request(format!("invalid line prefix '{line}'"));
+        }
+
+        let account = Some(account).filter(|a| *a != ANY_ACCOUNT);
+        Ok(AccountSelector::new(account, self.receiver.as_deref(), self.line.as_deref()))
+    }
+}
+
+/// Panel matched by trial decryption returned by the HTTP API.\
+/// **Note** that the learned key is never returned, only its position in the trial keys.
+#[derive(Serialize)]
+pub struct TrialMatchResponse {
+    account: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    receiver: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<String>,
+    /// Number of the matching trial key, starting from 1.
+    trial_key: usize,
+    matched_at: i64,
+    messages: u64,
+}
+
+impl TrialMatchResponse {
+    fn new(selector: AccountSelector, matched: TrialMatch) -> Self {
+        Self {
+            account: selector.account.unwrap_or_default(),
+            receiver: selector.receiver,
+            line: selector.line,
+            trial_key: matched.candidate + 1,
+            matched_at: matched.matched_at.unix_timestamp(),
+            messages: matched.messages,
+        }
+    }
+}
+
+/// Account settings accepted by the HTTP API.
+#[derive(Deserialize)]
+pub struct AccountRequest {
+    key: Option<SecretKey>,
+    /// Keys replacing the `key`, each from its validity start.
+    #[serde(default)]
+    keys: Vec<KeyVersionRequest>,
+    supervision: Option<u32>,
+    response: Option<ResponseMode>,
+}
+
+/// Rotated key accepted by the HTTP API, `valid_from` is a Unix timestamp.
+#[derive(Deserialize)]
+pub struct KeyVersionRequest {
+    key: SecretKey,
+    valid_from: i64,
+}
+
+/// `GET /accounts` - returns all accounts from the accounts registry.
+pub async fn list_accounts(State(state): State<AppState>) -> Json<Vec<AccountResponse>> {
+    let config = state.config_reloader.config().load_full();
+    let mut accounts = config.diallers.iter().collect::<Vec<_>>();
+    accounts.sort_unstable_by(|a, b| a.0.cmp(b.0));
+
+    Json(
+        accounts
+            .into_iter()
+            .map(|(selector, account)| AccountResponse::new(selector, account, &state, config.key_grace))
+            .collect(),
+    )
+}
+
+/// `GET /accounts/{account}` - returns a single account from the accounts registry.
+pub async fn get_account(
+    State(state): State<AppState>,
+    Path(account): Path<String>,
+    Query(query): Query<SelectorQuery>,
+) -> Result<Json<AccountResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let selector = query.selector(&account)?;
+    let config = state.config_reloader.config().load_full();
+    match config.get_account(&selector) {
+        Some(account) => Ok(Json(AccountResponse::new(&selector, account, &state, config.key_grace))),
+        None => Err(account_not_found(&selector)),
+    }
+}
+
+/// `PUT /accounts/{account}` - adds or replaces an account in the accounts registry.
+pub async fn set_account(
+    State(state): State<AppState>,
+    Path(account): Path<String>,
+    Query(query): Query<SelectorQuery>,
+    Json(request): Json<AccountRequest>,
+) -> Result<(StatusCode, Json<AccountResponse>), (StatusCode, Json<ErrorResponse>)> {
+    let selector = query.selector(&account)?;
+
+    let bad_request = |error| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error }));
+    if let Some(key) = &request.key {
+        parse_key(key.expose()).map_err(bad_request)?;
+    }
+
+    let mut rotation = Vec::with_capacity(request.keys.len());
+    for (index, version) in request.keys.into_iter().enumerate() {
+        parse_key(version.key.expose()).map_err(|e| bad_request(format!("keys[{index}]: {e}")))?;
+        let valid_from = OffsetDateTime::from_unix_timestamp(version.valid_from)
+            .map_err(|e| bad_request(format!("keys[{index}]: invalid valid_from: {e}")))?;
+        rotation.push(KeyVersion {
+            key: version.key,
+            valid_from,
+        });
+    }
+
+    let config = AccountConfig {
+        key: request.key,
+        rotation,
+        supervision: request.supervision,
+        response: request.response,
+    };
+
+    let added = state.config_reloader.set_account(selector.clone(), config.clone());
+
+    log::info!("account {selector} {} via HTTP API", if added { "added" } else { "updated" });
+    let status = if added { StatusCode::CREATED } else { StatusCode::OK };
+    let grace = state.config_reloader.config().load().key_grace;
+    Ok((status, Json(AccountResponse::new(&selector, &config, &state, grace))))
+}
+
+/// `DELETE /accounts/{account}` - removes an account from the accounts registry.
+pub async fn delete_account(
+    State(state): State<AppState>,
+    Path(account): Path<String>,
+    Query(query): Query<SelectorQuery>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let selector = query.selector(&account)?;
+    if state.config_reloader.remove_account(&selector) {
+        log::info!("account {selector} removed via HTTP API");
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(account_not_found(&selector))
+    }
+}
+
+/// `GET /trial-matches` - returns panels whose keys were found by trial decryption.
+pub async fn list_trial_matches(State(state): State<AppState>) -> Json<Vec<TrialMatchResponse>> {
+    let matches = state.server.trial_matches.list();
+    Json(matches.into_iter().map(|(s, m)| TrialMatchResponse::new(s, m)).collect())
+}
+
+/// `DELETE /trial-matches` - forgets all keys learned by trial decryption.
+pub async fn clear_trial_matches(State(state): State<AppState>) -> StatusCode {
+    let cleared = state.server.trial_matches.clear();
+    log::info!("{cleared} trial decryption matches cleared via HTTP API");
+    StatusCode::NO_CONTENT
+}
+
+fn account_not_found(selector: &AccountSelector) -> (StatusCode, Json<ErrorResponse>) {
+    let error = format!("account '{selector}' not found");
+    (StatusCode::NOT_FOUND, Json(ErrorResponse { error }))
+}
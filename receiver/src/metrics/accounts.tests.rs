@@ -0,0 +1,141 @@
+use super::*;
+use crate::metrics::auth::ApiAuth;
+use crate::reload::ConfigReloader;
+use crate::server::{ResponseModes, ServerConfig, ServerState};
+use common::keys::KeyQuery;
+use common::logging::LogLevelHandle;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const DEFAULT_KEY: &str = "0000000000000000";
+const KEY: &str = "0123456789abcdef";
+
+fn app_state() -> AppState {
+    let loader = Box::new(|| {
+        let keys = HashMap::from([(0, SecretKey::from(DEFAULT_KEY))]);
+        Ok(ServerConfig::new(&[], Arc::new(keys)).with_accounts(&["1000".parse().unwrap()]))
+    });
+    let reloader = Arc::new(ConfigReloader::new(loader, Vec::new()).unwrap());
+    AppState {
+        server: ServerState::new(&reloader.config(), ResponseModes::default()),
+        config_reloader: reloader,
+        log_level: LogLevelHandle::detached(),
+        auth: Arc::new(ApiAuth::new(Vec::new(), Vec::new())),
+    }
+}
+
+fn query() -> Query<SelectorQuery> {
+    Query(serde_json::from_value(json!({})).unwrap())
+}
+
+async fn put(state: &AppState, account: &str, request: Value) -> Result<(StatusCode, Value), StatusCode> {
+    let request = Json(serde_json::from_value(request).unwrap());
+    set_account(State(state.clone()), Path(account.to_owned()), query(), request)
+        .await
+        .map(|(status, Json(response))| (status, serde_json::to_value(response).unwrap()))
+        .map_err(|(status, _)| status)
+}
+
+async fn get(state: &AppState, account: &str) -> Result<Value, StatusCode> {
+    get_account(State(state.clone()), Path(account.to_owned()), query())
+        .await
+        .map(|Json(response)| serde_json::to_value(response).unwrap())
+        .map_err(|(status, _)| status)
+}
+
+async fn delete(state: &AppState, account: &str) -> StatusCode {
+    delete_account(State(state.clone()), Path(account.to_owned()), query())
+        .await
+        .unwrap_or_else(|(status, _)| status)
+}
+
+fn get_key(state: &AppState, account: &str) -> Option<String> {
+    let config = state.config_reloader.config().load_full();
+    config
+        .get_key(&KeyQuery::new(account, None, None))
+        .map(|k| k.expose().to_owned())
+}
+
+async fn list(state: &AppState) -> Vec<String> {
+    let Json(accounts) = list_accounts(State(state.clone())).await;
+    accounts.into_iter().map(|a| a.account).collect()
+}
+
+#[tokio::test]
+async fn accounts_survive_reload_test() {
+    let state = app_state();
+    assert_eq!(
+        Ok(StatusCode::CREATED),
+        put(&state, "1234", json!({"key": KEY})).await.map(|r| r.0)
+    );
+    assert_eq!(StatusCode::NO_CONTENT, delete(&state, "1000").await);
+
+    assert_eq!(Ok(1), state.config_reloader.reload());
+    assert_eq!(Some(true), get(&state, "1234").await.unwrap()["has_key"].as_bool());
+    assert_eq!(Err(StatusCode::NOT_FOUND), get(&state, "1000").await);
+    assert_eq!(vec!["1234"], list(&state).await);
+
+    // Discarding the runtime changes restores accounts from the configuration files.
+    assert_eq!(2, state.config_reloader.clear_accounts());
+    assert_eq!(vec!["1000"], list(&state).await);
+}
+
+#[tokio::test]
+async fn account_handlers_test() {
+    let state = app_state();
+    assert_eq!(vec!["1000"], list(&state).await);
+
+    let (status, response) = put(&state, "1234", json!({"key": KEY, "supervision": 60})).await.unwrap();
+    assert_eq!(StatusCode::CREATED, status);
+    assert_eq!(Some(60), response["supervision"].as_u64());
+    assert_eq!(Some(KEY.to_owned()), get_key(&state, "1234"));
+    assert_eq!(vec!["1000", "1234"], list(&state).await);
+
+    let (status, response) = put(&state, "1234", json!({"key": KEY})).await.unwrap();
+    assert_eq!(StatusCode::OK, status);
+    assert_eq!(None, response["supervision"].as_u64());
+    assert_eq!(Err(StatusCode::NOT_FOUND), get(&state, "5678").await);
+
+    assert_eq!(StatusCode::NO_CONTENT, delete(&state, "1234").await);
+    assert_eq!(StatusCode::NOT_FOUND, delete(&state, "1234").await);
+    assert_eq!(Err(StatusCode::NOT_FOUND), get(&state, "1234").await);
+    assert_eq!(Some(DEFAULT_KEY.to_owned()), get_key(&state, "1234"));
+    assert_eq!(vec!["1000"], list(&state).await);
+}
+
+#[tokio::test]
+async fn write_only_keys_test() {
+    let state = app_state();
+    let keys = json!([{"key": KEY, "valid_from": 0}, {"key": DEFAULT_KEY, "valid_from": 4102444800_i64}]);
+    let (_, response) = put(&state, "1234", json!({"key": KEY, "keys": keys})).await.unwrap();
+
+    for response in [response, get(&state, "1234").await.unwrap()] {
+        assert_eq!(Some(true), response["has_key"].as_bool());
+        assert_eq!(None, response.get("key"));
+        // The account key and both rotated keys.
+        assert_eq!(3, response["keys"].as_array().unwrap().len());
+        assert!(!response.to_string().contains(KEY));
+        assert!(!response.to_string().contains(DEFAULT_KEY));
+    }
+}
+
+#[tokio::test]
+async fn invalid_key_test() {
+    let state = app_state();
+    assert_eq!(
+        Err(StatusCode::BAD_REQUEST),
+        put(&state, "1234", json!({"key": "0123"})).await
+    );
+    assert_eq!(
+        Err(StatusCode::BAD_REQUEST),
+        put(&state, "1234", json!({"key": format!("{KEY}0")})).await
+    );
+
+    let keys = json!([{"key": "0123", "valid_from": 0}]);
+    assert_eq!(Err(StatusCode::BAD_REQUEST), put(&state, "1234", json!({"keys": keys})).await);
+    let keys = json!([{"key": KEY, "valid_from": i64::MAX}]);
+    assert_eq!(Err(StatusCode::BAD_REQUEST), put(&state, "1234", json!({"keys": keys})).await);
+
+    assert_eq!(Err(StatusCode::NOT_FOUND), get(&state, "1234").await);
+}
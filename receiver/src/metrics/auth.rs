@@ -0,0 +1,191 @@
+use axum::Json;
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::{HeaderMap, Method, StatusCode, header};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use base64::Engine;
+use base64::alphabet::STANDARD;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Debug, Formatter};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use subtle::ConstantTimeEq;
+
+use crate::metrics::AppState;
+use crate::metrics::server::ErrorResponse;
+
+#[cfg(test)]
+#[path = "./auth.tests.rs"]
+mod auth_tests;
+
+/// Log target of audit lines recording changes made via the HTTP API.
+pub const AUDIT_TARGET: &str = "receiver::audit";
+
+/// User name recorded in audit lines when authentication is disabled.
+const ANONYMOUS_USER: &str = "anonymous";
+
+/// Standard base64 of basic authentication credentials, padding is optional.
+const BASIC_CREDENTIALS: GeneralPurpose = GeneralPurpose::new(
+    &STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// Named secret accepted as a bearer token or a basic authentication password.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ApiCredential {
+    pub name: String,
+    pub secret: String,
+}
+
+impl Debug for ApiCredential {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApiCredential")
+            .field("name", &self.name)
+            .field("secret", &"***")
+            .finish()
+    }
+}
+
+impl FromStr for ApiCredential {
+    type Err = String;
+
+    /// Parses credential in the `NAME:SECRET` format.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some((name, secret)) if !name.trim().is_empty() && !secret.is_empty() => Ok(Self {
+                name: name.trim().to_owned(),
+                secret: secret.to_owned(),
+            }),
+            _ => Err("credential must be in the NAME:SECRET format".to_owned()),
+        }
+    }
+}
+
+/// Read-only endpoint that can be accessed without authentication.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PublicEndpoint {
+    Metrics,
+    Healthz,
+    Readyz,
+}
+
+impl PublicEndpoint {
+    fn path(self) -> &'static str {
+        match self {
+            Self::Metrics => "/metrics",
+            Self::Healthz => "/healthz",
+            Self::Readyz => "/readyz",
+        }
+    }
+}
+
+/// Authentication settings of the HTTP API.\
+/// **Note** that authentication is enabled only when at least one credential is configured.
+#[derive(Debug, Clone, Default)]
+pub struct ApiAuth {
+    credentials: Vec<ApiCredential>,
+    public: Vec<PublicEndpoint>,
+}
+
+impl ApiAuth {
+    /// Creates new [`ApiAuth`] instance.
+    pub fn new(credentials: Vec<ApiCredential>, public: Vec<PublicEndpoint>) -> Self {
+        Self { credentials, public }
+    }
+
+    /// Returns `true` if requests have to be authenticated.
+    pub fn is_enabled(&self) -> bool {
+        !self.credentials.is_empty()
+    }
+
+    /// Returns `true` if the request can be served without authentication.
+    fn is_public(&self, method: &Method, path: &str) -> bool {
+        !self.is_enabled() || (is_read_only(method) && self.public.iter().any(|e| e.path() == path))
+    }
+
+    /// Returns name of the credential matching `Authorization` header (`Bearer` or `Basic` scheme).
+    pub fn authenticate(&self, headers: &HeaderMap) -> Option<&str> {
+        let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+        let (scheme, value) = value.trim().split_once(' ')?;
+        let value = value.trim();
+
+        let credential = if scheme.eq_ignore_ascii_case("bearer") {
+            let secret = value.as_bytes();
+            self.credentials
+                .iter()
+                .find(|c| bool::from(c.secret.as_bytes().ct_eq(secret)))
+        } else if scheme.eq_ignore_ascii_case("basic") {
+            let decoded = BASIC_CREDENTIALS.decode(value).ok()?;
+            let (name, secret) = std::str::from_utf8(&decoded).ok()?.split_once(':')?;
+            self.credentials
+                .iter()
+                .find(|c| c.name == name && bool::from(c.secret.as_bytes().ct_eq(secret.as_bytes())))
+        } else {
+            None
+        };
+
+        credential.map(|c| c.name.as_str())
+    }
+}
+
+/// Validates API credentials file and returns its path, so it can be loaded again later.
+pub fn parse_credentials_file(s: &str) -> Result<PathBuf, String> {
+    load_credentials(Path::new(s)).map(|_| PathBuf::from(s))
+}
+
+/// Loads API credentials from the provided file path (one `NAME:SECRET` per line, `#` starts a comment).
+pub fn load_credentials(path: &Path) -> Result<Vec<ApiCredential>, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("unable to read the provided credentials file: {e}"))?;
+
+    let mut credentials = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let credential = line.parse().map_err(|e| format!("line {}: {e}", index + 1))?;
+        credentials.push(credential);
+    }
+
+    Ok(credentials)
+}
+
+/// Middleware that rejects unauthenticated requests and writes audit lines for mutating requests.
+pub async fn authorize(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().clone();
+    let path = request.uri().path().to_owned();
+
+    let user = if state.auth.is_public(&method, &path) {
+        ANONYMOUS_USER.to_owned()
+    } else if let Some(user) = state.auth.authenticate(request.headers()) {
+        user.to_owned()
+    } else {
+        tracing::warn!(target: AUDIT_TARGET, %peer, %method, path, "unauthorized {method} {path} from {peer}");
+        let error = "missing or invalid credentials".to_owned();
+        let challenge = [(header::WWW_AUTHENTICATE, r#"Bearer, Basic realm="receiver""#)];
+        return (StatusCode::UNAUTHORIZED, challenge, Json(ErrorResponse { error })).into_response();
+    };
+
+    let response = next.run(request).await;
+    if !is_read_only(&method) {
+        let status = response.status().as_u16();
+        let message = format!("{user} {method} {path} from {peer} -> {status}");
+        tracing::info!(target: AUDIT_TARGET, user, %peer, %method, path, status, "{message}");
+    }
+
+    response
+}
+
+fn is_read_only(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD)
+}
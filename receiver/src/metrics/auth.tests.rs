@@ -0,0 +1,64 @@
+use super::*;
+use axum::http::HeaderValue;
+
+fn auth() -> ApiAuth {
+    ApiAuth::new(
+        vec!["alice:s3cret".parse().unwrap(), "ci:token:with:colons".parse().unwrap()],
+        vec![PublicEndpoint::Metrics, PublicEndpoint::Healthz],
+    )
+}
+
+fn headers(authorization: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(header::AUTHORIZATION, HeaderValue::from_str(authorization).unwrap());
+    headers
+}
+
+#[test]
+fn parse_credential_test() {
+    let credential = "ci:token:with:colons".parse::<ApiCredential>().unwrap();
+    assert_eq!("ci", credential.name);
+    assert_eq!("token:with:colons", credential.secret);
+    assert!(!format!("{credential:?}").contains("token"));
+
+    assert!("token".parse::<ApiCredential>().is_err());
+    assert!(":token".parse::<ApiCredential>().is_err());
+    assert!("name:".parse::<ApiCredential>().is_err());
+}
+
+#[test]
+fn authenticate_test() {
+    let auth = auth();
+    assert_eq!(Some("alice"), auth.authenticate(&headers("Bearer s3cret")));
+    assert_eq!(Some("ci"), auth.authenticate(&headers("bearer token:with:colons")));
+    assert_eq!(Some("alice"), auth.authenticate(&headers("Basic YWxpY2U6czNjcmV0")));
+
+    assert_eq!(None, auth.authenticate(&headers("Bearer wrong")));
+    assert_eq!(None, auth.authenticate(&headers("Basic Y2k6czNjcmV0")));
+    assert_eq!(None, auth.authenticate(&headers("Basic !!!")));
+    assert_eq!(None, auth.authenticate(&headers("s3cret")));
+    assert_eq!(None, auth.authenticate(&HeaderMap::new()));
+}
+
+#[test]
+fn public_endpoints_test() {
+    let auth = auth();
+    assert!(auth.is_public(&Method::GET, "/metrics"));
+    assert!(auth.is_public(&Method::GET, "/healthz"));
+    assert!(!auth.is_public(&Method::GET, "/readyz"));
+    assert!(!auth.is_public(&Method::GET, "/accounts"));
+    assert!(!auth.is_public(&Method::POST, "/metrics"));
+
+    let disabled = ApiAuth::default();
+    assert!(!disabled.is_enabled());
+    assert!(disabled.is_public(&Method::PUT, "/mode/message/nak"));
+}
+
+#[test]
+fn basic_credentials_test() {
+    let auth = auth();
+    assert_eq!(Some("ci"), auth.authenticate(&headers("Basic Y2k6dG9rZW46d2l0aDpjb2xvbnM=")));
+    assert_eq!(Some("ci"), auth.authenticate(&headers("Basic Y2k6dG9rZW46d2l0aDpjb2xvbnM")));
+    assert_eq!(None, auth.authenticate(&headers("Basic Y2k6dG9rZW46d2l0aDpjb2xvbnM-")));
+    assert_eq!(None, auth.authenticate(&headers("Basic YWxpY2U6czNjcmV0eA")));
+}
@@ -0,0 +1,59 @@
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+
+use crate::metrics::AppState;
+use crate::metrics::server::ErrorResponse;
+use crate::server::{ExpectationReport, ExpectationSpec};
+
+/// `POST /expectations` - registers messages expected to be received.
+pub async fn add_expectation(
+    State(state): State<AppState>,
+    Json(spec): Json<ExpectationSpec>,
+) -> Result<(StatusCode, Json<ExpectationReport>), (StatusCode, Json<ErrorResponse>)> {
+    match state.server.expectations.add(spec) {
+        Ok(report) => Ok((StatusCode::CREATED, Json(report))),
+        Err(error) => Err((StatusCode::BAD_REQUEST, Json(ErrorResponse { error }))),
+    }
+}
+
+/// `GET /expectations` - returns state of all registered expectations.
+pub async fn list_expectations(State(state): State<AppState>) -> Json<Vec<ExpectationReport>> {
+    Json(state.server.expectations.list())
+}
+
+/// `GET /expectations/{id}` - returns state of a single expectation with the matching events.
+pub async fn get_expectation(
+    State(state): State<AppState>,
+    Path(id): Path<u64>,
+) -> Result<Json<ExpectationReport>, (StatusCode, Json<ErrorResponse>)> {
+    state
+        .server
+        .expectations
+        .get(id)
+        .map(Json)
+        .ok_or_else(|| expectation_not_found(id))
+}
+
+/// `DELETE /expectations` - removes all expectations.
+pub async fn clear_expectations(State(state): State<AppState>) -> StatusCode {
+    state.server.expectations.clear();
+    StatusCode::NO_CONTENT
+}
+
+/// `DELETE /expectations/{id}` - removes a single expectation.
+pub async fn delete_expectation(
+    State(state): State<AppState>,
+    Path(id): Path<u64>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    if state.server.expectations.remove(id) {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(expectation_not_found(id))
+    }
+}
+
+fn expectation_not_found(id: u64) -> (StatusCode, Json<ErrorResponse>) {
+    let error = format!("expectation {id} not found");
+    (StatusCode::NOT_FOUND, Json(ErrorResponse { error }))
+}
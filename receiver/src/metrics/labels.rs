@@ -0,0 +1,80 @@
+use std::collections::HashSet;
+use std::sync::RwLock;
+
+use common::keys::KeyQuery;
+
+use crate::metrics;
+use crate::server::ServerConfig;
+
+#[cfg(test)]
+#[path = "./labels.tests.rs"]
+mod labels_tests;
+
+/// Label used for accounts aggregated after the account label limit is reached.
+const OTHER_ACCOUNT_LABEL: &str = "other";
+
+/// Label used for all accounts when per-account series are disabled.
+const ALL_ACCOUNT_LABEL: &str = "all";
+
+/// Maximum number of aggregated accounts remembered to count each of them once.
+const MAX_SUPPRESSED_ACCOUNTS: usize = 100_000;
+
+/// Keeps account label cardinality of metrics within configured limits.
+#[derive(Debug, Default)]
+pub struct AccountLabeler {
+    accounts: RwLock<LabelledAccounts>,
+}
+
+#[derive(Debug, Default)]
+struct LabelledAccounts {
+    labelled: HashSet<String>,
+    suppressed: HashSet<String>,
+}
+
+impl AccountLabeler {
+    /// Returns label value to use for the account of the `query` in metrics.\
+    /// **Note** that accounts registered by name in the accounts registry are always labelled individually, other
+    /// accounts (including those matched by a wildcard entry) only until the configured limit is reached.
+    pub fn label<'a>(&self, query: &'a KeyQuery, config: &ServerConfig) -> &'a str {
+        if !config.account_labels.enabled {
+            return ALL_ACCOUNT_LABEL;
+        }
+
+        let account = query.account.as_str();
+        if config.find_account(query).is_some_and(|(s, _)| s.account.is_some()) {
+            return account;
+        }
+
+        let accounts = self.accounts.read().expect("lock is not poisoned");
+        if accounts.labelled.contains(account) {
+            return account;
+        }
+        if accounts.suppressed.contains(account) || accounts.suppressed.len() >= MAX_SUPPRESSED_ACCOUNTS {
+            return OTHER_ACCOUNT_LABEL;
+        }
+
+        drop(accounts);
+        let mut accounts = self.accounts.write().expect("lock is not poisoned");
+        if accounts.labelled.contains(account) {
+            return account;
+        }
+        if accounts.labelled.len() < config.account_labels.limit {
+            accounts.labelled.insert(account.to_owned());
+            return account;
+        }
+
+        if accounts.suppressed.len() < MAX_SUPPRESSED_ACCOUNTS && accounts.suppressed.insert(account.to_owned()) {
+            metrics::account_labels_suppressed().inc();
+        }
+        OTHER_ACCOUNT_LABEL
+    }
+
+    /// Forgets individually labelled and aggregated accounts and returns the number of the labelled ones.\
+    /// **Note** that it should be called only together with resetting the metrics.
+    pub fn clear(&self) -> usize {
+        let mut accounts = self.accounts.write().expect("lock is not poisoned");
+        let cleared = accounts.labelled.len();
+        *accounts = LabelledAccounts::default();
+        cleared
+    }
+}
@@ -0,0 +1,71 @@
+use super::*;
+use crate::server::AccountLabels;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+fn config(enabled: bool, limit: usize) -> ServerConfig {
+    ServerConfig::new(&[], Arc::new(HashMap::new()))
+        .with_accounts(&["1000".parse().unwrap(), "*".parse().unwrap()])
+        .with_account_labels(AccountLabels { enabled, limit })
+}
+
+fn label(labeler: &AccountLabeler, account: &str, config: &ServerConfig) -> String {
+    labeler.label(&KeyQuery::new(account, None, None), config).to_owned()
+}
+
+#[test]
+fn account_limit_test() {
+    let config = config(true, 2);
+    let labeler = AccountLabeler::default();
+    let suppressed = metrics::account_labels_suppressed().get();
+
+    assert_eq!("1234", label(&labeler, "1234", &config));
+    assert_eq!("5678", label(&labeler, "5678", &config));
+    assert_eq!("other", label(&labeler, "9999", &config));
+    assert_eq!("other", label(&labeler, "9999", &config));
+    assert_eq!("other", label(&labeler, "8888", &config));
+    assert_eq!("1234", label(&labeler, "1234", &config));
+
+    // Every aggregated account is counted once regardless of its messages.
+    assert_eq!(2, metrics::account_labels_suppressed().get() - suppressed);
+
+    // Aggregated accounts are forgotten and counted again after clearing.
+    assert_eq!(2, labeler.clear());
+    assert_eq!("9999", label(&labeler, "9999", &config));
+    assert_eq!("1234", label(&labeler, "1234", &config));
+    assert_eq!("other", label(&labeler, "5678", &config));
+    assert_eq!(3, metrics::account_labels_suppressed().get() - suppressed);
+}
+
+#[test]
+fn registered_accounts_test() {
+    let config = config(true, 1);
+    let labeler = AccountLabeler::default();
+
+    // Accounts registered by name don't take up the limit, accounts matched by the wildcard entry do.
+    assert_eq!("1000", label(&labeler, "1000", &config));
+    assert_eq!("1234", label(&labeler, "1234", &config));
+    assert_eq!("1000", label(&labeler, "1000", &config));
+    assert_eq!(1, labeler.clear());
+}
+
+#[test]
+fn disabled_labels_test() {
+    let config = config(false, 10);
+    let labeler = AccountLabeler::default();
+
+    assert_eq!("all", label(&labeler, "1000", &config));
+    assert_eq!("all", label(&labeler, "1234", &config));
+    assert_eq!(0, labeler.clear());
+}
+
+#[test]
+fn clear_test() {
+    let config = config(true, 1);
+    let labeler = AccountLabeler::default();
+    assert_eq!("1234", label(&labeler, "1234", &config));
+
+    assert_eq!(1, labeler.clear());
+    assert_eq!("5678", label(&labeler, "5678", &config));
+    assert_eq!(1, labeler.clear());
+}
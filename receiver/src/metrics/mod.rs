@@ -1,8 +1,17 @@
+pub use self::auth::{ApiAuth, ApiCredential, PublicEndpoint, load_credentials, parse_credentials_file};
+pub use self::labels::AccountLabeler;
 pub use self::prometheus::{
-    active_connections, connections_total, heartbeats_received, last_message_timestamp, message_size_bytes, messages_failed,
-    messages_received, register_all,
+    account_labels_suppressed, active_connections, capture_records_dropped, config_reloads, connections_rejected,
+    connections_timed_out, connections_total, heartbeats_received, last_message_timestamp, message_size_bytes,
+    messages_by_encryption, messages_failed, messages_received, previous_key_messages, processing_duration_seconds, register_all,
+    reset_all, response_delay_seconds, responses_sent, timestamp_skew_seconds, trial_decryptions, udp_datagrams_dropped,
+    udp_queue_depth, udp_retransmits, unknown_account_messages,
 };
 pub use self::server::{AppState, start_metrics_server};
 
+mod accounts;
+mod auth;
+mod expectations;
+mod labels;
 mod prometheus;
 mod server;
@@ -1,4 +1,4 @@
-use prometheus::{GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts};
+use prometheus::{GaugeVec, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts};
 use std::sync::OnceLock;
 
 /// Counter for total DC-09 messages received.
@@ -25,6 +25,21 @@ pub fn messages_failed() -> &'static IntCounterVec {
     })
 }
 
+/// Counter for total DC-09 messages received from accounts missing in the accounts registry.
+pub fn unknown_account_messages() -> &'static IntCounterVec {
+    static METRIC: OnceLock<IntCounterVec> = OnceLock::new();
+    METRIC.get_or_init(|| {
+        IntCounterVec::new(
+            Opts::new(
+                "dc09_unknown_account_messages_total",
+                "Total DC-09 messages received from unknown accounts",
+            ),
+            &["transport", "policy"],
+        )
+        .expect("metric can be created")
+    })
+}
+
 /// Counter for total connections accepted.
 pub fn connections_total() -> &'static IntCounterVec {
     static METRIC: OnceLock<IntCounterVec> = OnceLock::new();
@@ -37,6 +52,130 @@ pub fn connections_total() -> &'static IntCounterVec {
     })
 }
 
+/// Counter for total connections rejected due to connection limits.
+pub fn connections_rejected() -> &'static IntCounterVec {
+    static METRIC: OnceLock<IntCounterVec> = OnceLock::new();
+    METRIC.get_or_init(|| {
+        IntCounterVec::new(
+            Opts::new(
+                "dc09_connections_rejected_total",
+                "Total connections rejected due to connection limits",
+            ),
+            &["transport", "reason"],
+        )
+        .expect("metric can be created")
+    })
+}
+
+/// Counter for total connections closed due to idle timeout.
+pub fn connections_timed_out() -> &'static IntCounterVec {
+    static METRIC: OnceLock<IntCounterVec> = OnceLock::new();
+    METRIC.get_or_init(|| {
+        IntCounterVec::new(
+            Opts::new(
+                "dc09_connections_timed_out_total",
+                "Total connections closed due to idle timeout",
+            ),
+            &["transport"],
+        )
+        .expect("metric can be created")
+    })
+}
+
+/// Gauge for the number of items waiting in UDP pipeline queues.
+pub fn udp_queue_depth() -> &'static IntGaugeVec {
+    static METRIC: OnceLock<IntGaugeVec> = OnceLock::new();
+    METRIC.get_or_init(|| {
+        IntGaugeVec::new(
+            Opts::new("dc09_udp_queue_depth", "Number of items waiting in UDP pipeline queues"),
+            &["queue"],
+        )
+        .expect("metric can be created")
+    })
+}
+
+/// Counter for total UDP datagrams dropped without processing.
+pub fn udp_datagrams_dropped() -> &'static IntCounterVec {
+    static METRIC: OnceLock<IntCounterVec> = OnceLock::new();
+    METRIC.get_or_init(|| {
+        IntCounterVec::new(
+            Opts::new(
+                "dc09_udp_datagrams_dropped_total",
+                "Total UDP datagrams dropped without processing",
+            ),
+            &["reason"],
+        )
+        .expect("metric can be created")
+    })
+}
+
+/// Counter for total retransmitted UDP frames answered with a cached response.
+pub fn udp_retransmits() -> &'static IntCounter {
+    static METRIC: OnceLock<IntCounter> = OnceLock::new();
+    METRIC.get_or_init(|| {
+        IntCounter::new(
+            "dc09_udp_retransmits_total",
+            "Total retransmitted UDP frames answered with a cached response",
+        )
+        .expect("metric can be created")
+    })
+}
+
+/// Counter for total DC-09 messages received split by encryption.
+pub fn messages_by_encryption() -> &'static IntCounterVec {
+    static METRIC: OnceLock<IntCounterVec> = OnceLock::new();
+    METRIC.get_or_init(|| {
+        IntCounterVec::new(
+            Opts::new(
+                "dc09_messages_by_encryption_total",
+                "Total DC-09 messages received split by encryption",
+            ),
+            &["transport", "encryption"],
+        )
+        .expect("metric can be created")
+    })
+}
+
+/// Counter for total frames not recorded because the capture queue was full.
+pub fn capture_records_dropped() -> &'static IntCounter {
+    static METRIC: OnceLock<IntCounter> = OnceLock::new();
+    METRIC.get_or_init(|| {
+        IntCounter::new(
+            "dc09_capture_records_dropped_total",
+            "Total frames not recorded because the capture queue was full",
+        )
+        .expect("metric can be created")
+    })
+}
+
+/// Counter for total responses sent by response type and account.\
+/// **Note** that responses replayed for UDP retransmissions are counted in [`udp_retransmits`] only.
+pub fn responses_sent() -> &'static IntCounterVec {
+    static METRIC: OnceLock<IntCounterVec> = OnceLock::new();
+    METRIC.get_or_init(|| {
+        IntCounterVec::new(
+            Opts::new(
+                "dc09_responses_sent_total",
+                "Total responses sent by response type and account, excluding replayed UDP responses",
+            ),
+            &["transport", "response", "account"],
+        )
+        .expect("metric can be created")
+    })
+}
+
+/// Counter for total distinct accounts aggregated into `other` due to the account label limit.
+pub fn account_labels_suppressed() -> &'static IntCounter {
+    static METRIC: OnceLock<IntCounter> = OnceLock::new();
+    METRIC.get_or_init(|| {
+        IntCounter::new(
+            "dc09_account_labels_suppressed_total",
+            "Total distinct accounts aggregated into other due to the account label limit",
+        )
+        .expect("metric can be created")
+    })
+}
+
 /// Counter for total heartbeat/null messages received.
 pub fn heartbeats_received() -> &'static IntCounterVec {
     static METRIC: OnceLock<IntCounterVec> = OnceLock::new();
@@ -85,6 +224,100 @@ pub fn message_size_bytes() -> &'static HistogramVec {
     })
 }
 
+/// Histogram for delays applied before sending responses in seconds.
+pub fn response_delay_seconds() -> &'static HistogramVec {
+    static METRIC: OnceLock<HistogramVec> = OnceLock::new();
+    METRIC.get_or_init(|| {
+        HistogramVec::new(
+            HistogramOpts::new(
+                "dc09_response_delay_seconds",
+                "Delay applied before sending responses in seconds",
+            )
+            .buckets(vec![0.0, 0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0]),
+            &["transport"],
+        )
+        .expect("metric can be created")
+    })
+}
+
+/// Histogram for time from reading a message to writing its response in seconds.
+pub fn processing_duration_seconds() -> &'static HistogramVec {
+    static METRIC: OnceLock<HistogramVec> = OnceLock::new();
+    METRIC.get_or_init(|| {
+        HistogramVec::new(
+            HistogramOpts::new(
+                "dc09_processing_duration_seconds",
+                "Time from reading a message to writing its response in seconds",
+            )
+            .buckets(vec![
+                0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+            ]),
+            &["transport"],
+        )
+        .expect("metric can be created")
+    })
+}
+
+/// Histogram for difference between the receiver clock and message timestamps in seconds.
+pub fn timestamp_skew_seconds() -> &'static HistogramVec {
+    static METRIC: OnceLock<HistogramVec> = OnceLock::new();
+    METRIC.get_or_init(|| {
+        HistogramVec::new(
+            HistogramOpts::new(
+                "dc09_timestamp_skew_seconds",
+                "Difference between the receiver clock and message timestamps in seconds",
+            )
+            .buckets(vec![
+                -3600.0, -300.0, -60.0, -20.0, -5.0, -1.0, 0.0, 1.0, 5.0, 20.0, 60.0, 300.0, 3600.0,
+            ]),
+            &["transport"],
+        )
+        .expect("metric can be created")
+    })
+}
+
+/// Counter for configuration reloads.
+pub fn config_reloads() -> &'static IntCounterVec {
+    static METRIC: OnceLock<IntCounterVec> = OnceLock::new();
+    METRIC.get_or_init(|| {
+        IntCounterVec::new(
+            Opts::new("dc09_config_reloads_total", "Total configuration reloads"),
+            &["result"],
+        )
+        .expect("metric can be created")
+    })
+}
+
+/// Counter for trial decryptions of messages from accounts missing in the accounts registry.
+pub fn trial_decryptions() -> &'static IntCounterVec {
+    static METRIC: OnceLock<IntCounterVec> = OnceLock::new();
+    METRIC.get_or_init(|| {
+        IntCounterVec::new(
+            Opts::new(
+                "dc09_trial_decryptions_total",
+                "Total trial decryptions of messages from unknown accounts",
+            ),
+            &["result"],
+        )
+        .expect("metric can be created")
+    })
+}
+
+/// Counter for messages decrypted with the previous account key within its grace window.
+pub fn previous_key_messages() -> &'static IntCounterVec {
+    static METRIC: OnceLock<IntCounterVec> = OnceLock::new();
+    METRIC.get_or_init(|| {
+        IntCounterVec::new(
+            Opts::new(
+                "dc09_previous_key_messages_total",
+                "Total DC-09 messages decrypted with the previous account key",
+            ),
+            &["account"],
+        )
+        .expect("metric can be created")
+    })
+}
+
 /// Call once at startup to register all metrics with the default registry.
 pub fn register_all() {
     let registry = prometheus::default_registry();
@@ -95,9 +328,39 @@ pub fn register_all() {
     registry
         .register(Box::new(messages_failed().clone()))
         .expect("metric registered");
+    registry
+        .register(Box::new(unknown_account_messages().clone()))
+        .expect("metric registered");
     registry
         .register(Box::new(connections_total().clone()))
         .expect("metric registered");
+    registry
+        .register(Box::new(connections_rejected().clone()))
+        .expect("metric registered");
+    registry
+        .register(Box::new(connections_timed_out().clone()))
+        .expect("metric registered");
+    registry
+        .register(Box::new(udp_queue_depth().clone()))
+        .expect("metric registered");
+    registry
+        .register(Box::new(udp_datagrams_dropped().clone()))
+        .expect("metric registered");
+    registry
+        .register(Box::new(udp_retransmits().clone()))
+        .expect("metric registered");
+    registry
+        .register(Box::new(capture_records_dropped().clone()))
+        .expect("metric registered");
+    registry
+        .register(Box::new(messages_by_encryption().clone()))
+        .expect("metric registered");
+    registry
+        .register(Box::new(responses_sent().clone()))
+        .expect("metric registered");
+    registry
+        .register(Box::new(account_labels_suppressed().clone()))
+        .expect("metric registered");
     registry
         .register(Box::new(heartbeats_received().clone()))
         .expect("metric registered");
@@ -110,9 +373,79 @@ pub fn register_all() {
     registry
         .register(Box::new(message_size_bytes().clone()))
         .expect("metric registered");
+    registry
+        .register(Box::new(response_delay_seconds().clone()))
+        .expect("metric registered");
+    registry
+        .register(Box::new(processing_duration_seconds().clone()))
+        .expect("metric registered");
+    registry
+        .register(Box::new(timestamp_skew_seconds().clone()))
+        .expect("metric registered");
+    registry
+        .register(Box::new(config_reloads().clone()))
+        .expect("metric registered");
+    registry
+        .register(Box::new(trial_decryptions().clone()))
+        .expect("metric registered");
+    registry
+        .register(Box::new(previous_key_messages().clone()))
+        .expect("metric registered");
+
+    initialize_series();
+}
+
+/// Resets counters, histograms and per-account series, keeping gauges that reflect the current state.
+pub fn reset_all() {
+    messages_received().reset();
+    messages_failed().reset();
+    unknown_account_messages().reset();
+    connections_total().reset();
+    connections_rejected().reset();
+    connections_timed_out().reset();
+    udp_datagrams_dropped().reset();
+    udp_retransmits().reset();
+    capture_records_dropped().reset();
+    messages_by_encryption().reset();
+    responses_sent().reset();
+    account_labels_suppressed().reset();
+    heartbeats_received().reset();
+    last_message_timestamp().reset();
+    message_size_bytes().reset();
+    response_delay_seconds().reset();
+    processing_duration_seconds().reset();
+    timestamp_skew_seconds().reset();
+    config_reloads().reset();
+    trial_decryptions().reset();
+    previous_key_messages().reset();
+
+    initialize_series();
+}
+
+/// Creates series with known label values, so they are exported before the first observation.
+fn initialize_series() {
+    for result in &["success", "failure"] {
+        config_reloads().with_label_values(&[result]);
+    }
+
+    for result in &["matched", "failed"] {
+        trial_decryptions().with_label_values(&[result]);
+    }
+
+    for queue in &["datagrams", "responses"] {
+        udp_queue_depth().with_label_values(&[queue]);
+    }
+
+    udp_datagrams_dropped().with_label_values(&["QueueFull"]);
 
     for transport in &["TCP", "UDP"] {
         connections_total().with_label_values(&[transport]);
         message_size_bytes().with_label_values(&[transport]);
+        response_delay_seconds().with_label_values(&[transport]);
+        processing_duration_seconds().with_label_values(&[transport]);
+        timestamp_skew_seconds().with_label_values(&[transport]);
+        for encryption in &["encrypted", "plain"] {
+            messages_by_encryption().with_label_values(&[transport, encryption]);
+        }
     }
 }
@@ -1,29 +1,36 @@
 use anyhow::Result;
 use axum::Json;
-use axum::extract::Path;
-use axum::routing::put;
+use axum::extract::{Path, Query};
+use axum::middleware;
+use axum::routing::{post, put};
 use axum::{Router, extract::State, http::StatusCode, response::IntoResponse, routing::get};
+use common::delay::DelayProfile;
+use common::logging::LogLevelHandle;
 use prometheus::{self, Encoder, TextEncoder};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
-use std::net::IpAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::str::FromStr;
-use std::sync::{Arc, atomic::AtomicBool, atomic::Ordering};
+use std::sync::{Arc, atomic::Ordering};
 use tokio::net::TcpListener;
 
-use crate::server::{ResponseMode, ResponseModes};
+use crate::metrics::{self, ApiAuth, accounts, auth, expectations};
+use crate::reload::ConfigReloader;
+use crate::server::{ResponseMode, ServerState};
 
 /// Shared application state used by the HTTP server handlers.
 #[derive(Clone)]
 pub struct AppState {
-    pub tcp_ready: Arc<AtomicBool>,
-    pub udp_ready: Arc<AtomicBool>,
-    pub response_modes: Arc<ResponseModes>,
+    pub server: ServerState,
+    pub config_reloader: Arc<ConfigReloader>,
+    pub log_level: LogLevelHandle,
+    pub auth: Arc<ApiAuth>,
 }
 
 #[derive(Serialize)]
-struct ErrorResponse {
-    error: String,
+pub(super) struct ErrorResponse {
+    pub error: String,
 }
 
 #[derive(Serialize)]
@@ -62,6 +69,48 @@ impl ModesResponse {
     }
 }
 
+#[derive(Serialize)]
+struct ReloadResponse {
+    status: &'static str,
+    accounts: usize,
+}
+
+/// Log level directives in the `RUST_LOG` syntax.
+#[derive(Serialize, Deserialize)]
+struct LogLevel {
+    level: String,
+}
+
+/// Options of the state reset.
+#[derive(Deserialize)]
+struct ResetQuery {
+    #[serde(default)]
+    metrics: bool,
+}
+
+/// State cleared by the reset, counts are numbers of removed entries.
+#[derive(Serialize)]
+struct ResetResponse {
+    response_modes: ModesResponse,
+    supervision: usize,
+    retransmit_cache: usize,
+    expectations: usize,
+    trial_matches: usize,
+    /// Accounts added, updated or removed via the HTTP API.
+    accounts: usize,
+    /// Per-account delay overrides, the default delay is restored as well.
+    delays: usize,
+    metrics: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    account_labels: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct DelaysResponse {
+    default: Option<DelayProfile>,
+    accounts: HashMap<String, DelayProfile>,
+}
+
 /// Message type path parameter
 #[derive(Debug, Clone, Copy)]
 enum MessageType {
@@ -117,9 +166,9 @@ async fn health_handler() -> impl IntoResponse {
 
 /// `GET /readyz` - Kubernetes readiness probe endpoint.
 async fn ready_handler(State(state): State<AppState>) -> impl IntoResponse {
-    let tcp = state.tcp_ready.load(Ordering::Relaxed);
-    let udp = state.udp_ready.load(Ordering::Relaxed);
-    if tcp && udp {
+    let tcp = state.server.tcp_ready.load(Ordering::Relaxed);
+    let udp = state.server.udp_ready.load(Ordering::Relaxed);
+    if tcp && udp && !state.server.shutdown.is_cancelled() {
         let status = "ready";
         (StatusCode::OK, Json(ReadyResponse { status, tcp, udp }))
     } else {
@@ -131,8 +180,8 @@ async fn ready_handler(State(state): State<AppState>) -> impl IntoResponse {
 /// `GET /mode` - returns the current response modes for messages and heartbeats.
 async fn get_modes(State(state): State<AppState>) -> impl IntoResponse {
     let resp = ModesResponse {
-        message: Some(state.response_modes.message().to_string()),
-        heartbeat: Some(state.response_modes.heartbeat().to_string()),
+        message: Some(state.server.response_modes.message().to_string()),
+        heartbeat: Some(state.server.response_modes.heartbeat().to_string()),
     };
 
     (StatusCode::OK, Json(resp))
@@ -149,8 +198,8 @@ async fn get_mode(
     })?;
 
     match msg_type {
-        MessageType::Message => Ok(Json(ModesResponse::message(state.response_modes.message()))),
-        MessageType::Heartbeat => Ok(Json(ModesResponse::heartbeat(state.response_modes.heartbeat()))),
+        MessageType::Message => Ok(Json(ModesResponse::message(state.server.response_modes.message()))),
+        MessageType::Heartbeat => Ok(Json(ModesResponse::heartbeat(state.server.response_modes.heartbeat()))),
     }
 }
 
@@ -171,16 +220,160 @@ async fn set_mode(
 
     match msg_type {
         MessageType::Message => {
-            state.response_modes.set_message(mode);
+            state.server.response_modes.set_message(mode);
             Ok(Json(ModesResponse::message(mode)))
         },
         MessageType::Heartbeat => {
-            state.response_modes.set_heartbeat(mode);
+            state.server.response_modes.set_heartbeat(mode);
             Ok(Json(ModesResponse::heartbeat(mode)))
         },
     }
 }
 
+/// `GET /delay` - returns the default and all per-account response delay profiles.
+async fn get_delays(State(state): State<AppState>) -> impl IntoResponse {
+    let config = state.config_reloader.config().load_full();
+    let resp = DelaysResponse {
+        default: state.server.response_delays.default_profile(),
+        accounts: state.server.response_delays.account_profiles(&config),
+    };
+
+    (StatusCode::OK, Json(resp))
+}
+
+/// `PUT /delay` - sets the default response delay profile.
+async fn set_default_delay(
+    State(state): State<AppState>,
+    Json(profile): Json<DelayProfile>,
+) -> Result<Json<DelayProfile>, (StatusCode, Json<ErrorResponse>)> {
+    profile
+        .validate()
+        .map_err(|error| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error })))?;
+
+    state.server.response_delays.set_default_profile(Some(profile.clone()));
+    Ok(Json(profile))
+}
+
+/// `DELETE /delay` - removes the default response delay profile.
+async fn delete_default_delay(State(state): State<AppState>) -> impl IntoResponse {
+    state.server.response_delays.set_default_profile(None);
+    StatusCode::NO_CONTENT
+}
+
+/// `GET /delay/{account}` - returns the response delay profile for a specific account.
+async fn get_account_delay(
+    State(state): State<AppState>,
+    Path(account): Path<String>,
+) -> Result<Json<DelayProfile>, (StatusCode, Json<ErrorResponse>)> {
+    let config = state.config_reloader.config().load_full();
+    state
+        .server
+        .response_delays
+        .account_profile(&account, &config)
+        .map(Json)
+        .ok_or_else(|| {
+            let error = format!("no delay profile for account '{account}'");
+            (StatusCode::NOT_FOUND, Json(ErrorResponse { error }))
+        })
+}
+
+/// `PUT /delay/{account}` - sets the response delay profile for a specific account.
+async fn set_account_delay(
+    State(state): State<AppState>,
+    Path(account): Path<String>,
+    Json(profile): Json<DelayProfile>,
+) -> Result<Json<DelayProfile>, (StatusCode, Json<ErrorResponse>)> {
+    profile
+        .validate()
+        .map_err(|error| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error })))?;
+
+    state
+        .server
+        .response_delays
+        .set_account_profile(&account, Some(profile.clone()));
+    Ok(Json(profile))
+}
+
+/// `DELETE /delay/{account}` - removes the response delay profile set for a specific account via the HTTP API.\
+/// **Note** that the profile from the scenarios file, if any, applies again.
+async fn delete_account_delay(
+    State(state): State<AppState>,
+    Path(account): Path<String>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    match state.server.response_delays.set_account_profile(&account, None) {
+        Some(_) => Ok(StatusCode::NO_CONTENT),
+        None => {
+            let error = format!("no delay profile for account '{account}'");
+            Err((StatusCode::NOT_FOUND, Json(ErrorResponse { error })))
+        },
+    }
+}
+
+/// `GET /log/level` - returns the current log level directives.
+async fn get_log_level(State(state): State<AppState>) -> Json<LogLevel> {
+    Json(LogLevel {
+        level: state.log_level.get(),
+    })
+}
+
+/// `PUT /log/level` - replaces log level directives without restarting the receiver.
+async fn set_log_level(
+    State(state): State<AppState>,
+    Json(request): Json<LogLevel>,
+) -> Result<Json<LogLevel>, (StatusCode, Json<ErrorResponse>)> {
+    state
+        .log_level
+        .set(&request.level)
+        .map_err(|error| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error })))?;
+
+    log::info!("log level changed to '{}'", request.level);
+    Ok(Json(LogLevel {
+        level: state.log_level.get(),
+    }))
+}
+
+/// `POST /reset` - restores response modes and delays from cli args and clears runtime state between test cases.\
+/// **Note** that Prometheus counters and histograms are reset only with `?metrics=true`.
+async fn reset_state(State(state): State<AppState>, Query(query): Query<ResetQuery>) -> Json<ResetResponse> {
+    state.server.response_modes.reset();
+    let response = ResetResponse {
+        response_modes: ModesResponse {
+            message: Some(state.server.response_modes.message().to_string()),
+            heartbeat: Some(state.server.response_modes.heartbeat().to_string()),
+        },
+        supervision: state.server.supervision.clear(),
+        retransmit_cache: state.server.retransmit_cache.as_ref().map_or(0, |cache| cache.clear()),
+        expectations: state.server.expectations.clear(),
+        trial_matches: state.server.trial_matches.clear(),
+        accounts: state.config_reloader.clear_accounts(),
+        delays: state.server.response_delays.reset(),
+        metrics: query.metrics,
+        account_labels: query.metrics.then(|| {
+            metrics::reset_all();
+            state.server.account_labels.clear()
+        }),
+    };
+
+    log::info!("receiver state reset via HTTP API");
+    Json(response)
+}
+
+/// `POST /config/reload` - reloads scenarios and accounts files.
+async fn reload_config(State(state): State<AppState>) -> Result<Json<ReloadResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let reloader = Arc::clone(&state.config_reloader);
+    match tokio::task::spawn_blocking(move || reloader.reload()).await {
+        Ok(Ok(accounts)) => Ok(Json(ReloadResponse {
+            status: "reloaded",
+            accounts,
+        })),
+        Ok(Err(error)) => Err((StatusCode::UNPROCESSABLE_ENTITY, Json(ErrorResponse { error }))),
+        Err(e) => {
+            let error = format!("configuration reload failed: {e}");
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error })))
+        },
+    }
+}
+
 /// Starts the auxiliary HTTP server that exposes observability and health
 /// endpoints for Kubernetes and Prometheus.
 pub async fn start_metrics_server(address: IpAddr, port: u16, state: AppState) -> Result<()> {
@@ -191,11 +384,41 @@ pub async fn start_metrics_server(address: IpAddr, port: u16, state: AppState) -
         .route("/mode", get(get_modes))
         .route("/mode/{msg_type}", get(get_mode))
         .route("/mode/{msg_type}/{mode}", put(set_mode))
+        .route("/delay", get(get_delays).put(set_default_delay).delete(delete_default_delay))
+        .route(
+            "/delay/{account}",
+            get(get_account_delay).put(set_account_delay).delete(delete_account_delay),
+        )
+        .route("/config/reload", post(reload_config))
+        .route("/reset", post(reset_state))
+        .route("/log/level", get(get_log_level).put(set_log_level))
+        .route(
+            "/expectations",
+            get(expectations::list_expectations)
+                .post(expectations::add_expectation)
+                .delete(expectations::clear_expectations),
+        )
+        .route(
+            "/expectations/{id}",
+            get(expectations::get_expectation).delete(expectations::delete_expectation),
+        )
+        .route("/accounts", get(accounts::list_accounts))
+        .route(
+            "/accounts/{account}",
+            get(accounts::get_account)
+                .put(accounts::set_account)
+                .delete(accounts::delete_account),
+        )
+        .route(
+            "/trial-matches",
+            get(accounts::list_trial_matches).delete(accounts::clear_trial_matches),
+        )
+        .layer(middleware::from_fn_with_state(state.clone(), auth::authorize))
         .with_state(state);
 
     let listener = TcpListener::bind((address, port)).await?;
     log::info!("start listening on http://{address}:{port}/metrics");
 
-    axum::serve(listener, app).await?;
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
     Ok(())
 }
@@ -0,0 +1,168 @@
+use anyhow::Result;
+use arc_swap::ArcSwap;
+use common::keys::AccountSelector;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use crate::metrics;
+use crate::server::{AccountConfig, ServerConfig, SharedServerConfig};
+
+#[cfg(test)]
+#[path = "./reload.tests.rs"]
+mod reload_tests;
+
+/// Builds server configuration from the configuration files.
+pub type ConfigLoader = Box<dyn Fn() -> Result<ServerConfig, String> + Send + Sync>;
+
+/// Accounts changed at runtime, `None` removes the account loaded from the configuration files.
+pub type AccountOverrides = HashMap<AccountSelector, Option<AccountConfig>>;
+
+/// Configuration loaded from the files and the accounts changed at runtime on top of it.
+struct Overlay {
+    base: Arc<ServerConfig>,
+    accounts: AccountOverrides,
+}
+
+/// Reloads keys, accounts and per-account response delays from the configuration files into the shared server
+/// configuration.\
+/// **Note** that accounts changed at runtime are applied on top of every reloaded configuration.
+pub struct ConfigReloader {
+    loader: ConfigLoader,
+    paths: Vec<PathBuf>,
+    config: SharedServerConfig,
+    overlay: Mutex<Overlay>,
+}
+
+impl ConfigReloader {
+    /// Creates new [`ConfigReloader`] instance with the initial configuration loaded, `paths` are the files
+    /// watched for changes.
+    pub fn new(loader: ConfigLoader, paths: Vec<PathBuf>) -> Result<Self, String> {
+        let config = Arc::new(loader()?);
+        Ok(Self {
+            loader,
+            paths,
+            config: Arc::new(ArcSwap::new(Arc::clone(&config))),
+            overlay: Mutex::new(Overlay {
+                base: config,
+                accounts: AccountOverrides::new(),
+            }),
+        })
+    }
+
+    /// Returns shared server configuration handle.
+    pub fn config(&self) -> SharedServerConfig {
+        Arc::clone(&self.config)
+    }
+
+    /// Reloads configuration files and swaps the server configuration.\
+    /// **Note** that on error the previous configuration keeps serving.
+    pub fn reload(&self) -> Result<usize, String> {
+        match (self.loader)() {
+            Ok(config) => {
+                let mut overlay = self.overlay.lock().expect("lock is not poisoned");
+                overlay.base = Arc::new(config);
+                let accounts = self.apply(&overlay);
+                metrics::config_reloads().with_label_values(&["success"]).inc();
+                log::info!("configuration reloaded, {accounts} known accounts");
+                Ok(accounts)
+            },
+            Err(error) => {
+                metrics::config_reloads().with_label_values(&["failure"]).inc();
+                log::error!("configuration reload failed, keeping previous configuration: {error}");
+                Err(error)
+            },
+        }
+    }
+
+    /// Adds or replaces account on top of the configuration files, returns `true` if the account was added.
+    pub fn set_account(&self, selector: AccountSelector, config: AccountConfig) -> bool {
+        let mut overlay = self.overlay.lock().expect("lock is not poisoned");
+        let added = self.config.load().get_account(&selector).is_none();
+        overlay.accounts.insert(selector, Some(config));
+        self.apply(&overlay);
+        added
+    }
+
+    /// Removes account from the accounts registry, returns `true` if the account existed.\
+    /// **Note** that an account from the configuration files stays removed after reloads.
+    pub fn remove_account(&self, selector: &AccountSelector) -> bool {
+        let mut overlay = self.overlay.lock().expect("lock is not poisoned");
+        if self.config.load().get_account(selector).is_none() {
+            return false;
+        }
+
+        if overlay.base.get_account(selector).is_some() {
+            overlay.accounts.insert(selector.clone(), None);
+        } else {
+            overlay.accounts.remove(selector);
+        }
+        self.apply(&overlay);
+        true
+    }
+
+    /// Discards all accounts changed at runtime and returns their number.
+    pub fn clear_accounts(&self) -> usize {
+        let mut overlay = self.overlay.lock().expect("lock is not poisoned");
+        let cleared = overlay.accounts.len();
+        overlay.accounts.clear();
+        self.apply(&overlay);
+        cleared
+    }
+
+    /// Swaps the server configuration for the `overlay` applied on top of its base, returns number of known accounts.
+    fn apply(&self, overlay: &Overlay) -> usize {
+        if overlay.accounts.is_empty() {
+            self.config.store(Arc::clone(&overlay.base));
+            return overlay.base.diallers.len();
+        }
+
+        let mut config = ServerConfig::clone(&overlay.base);
+        for (selector, account) in &overlay.accounts {
+            match account {
+                Some(account) => config.set_account(selector.clone(), account.clone()),
+                None => config.remove_account(selector),
+            };
+        }
+
+        let accounts = config.diallers.len();
+        self.config.store(Arc::new(config));
+        accounts
+    }
+
+    /// Reloads configuration each time the process receives `SIGHUP`.
+    #[cfg(unix)]
+    pub async fn watch_signal(self: Arc<Self>) -> Result<()> {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        let mut hangup = signal(SignalKind::hangup())?;
+        while hangup.recv().await.is_some() {
+            log::info!("received SIGHUP, reloading configuration");
+            let _ = self.reload();
+        }
+
+        Ok(())
+    }
+
+    /// Reloads configuration when any of the configuration files is modified.
+    pub async fn watch_files(self: Arc<Self>, interval: Duration) {
+        let mut modified = self.paths.iter().map(|p| modified_time(p)).collect::<Vec<_>>();
+
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+
+            let current = self.paths.iter().map(|p| modified_time(p)).collect::<Vec<_>>();
+            if current != modified {
+                modified = current;
+                log::info!("configuration files changed, reloading configuration");
+                let _ = self.reload();
+            }
+        }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
@@ -0,0 +1,78 @@
+use super::*;
+use common::delay::DelayProfile;
+use common::utils::load_scenarios;
+use std::time::Duration;
+
+const WATCH_INTERVAL: Duration = Duration::from_millis(20);
+
+fn scenarios(account: &str, delay: u64) -> String {
+    format!(r#"{{"diallers":[{{"name":"{account}","delay":{{"type":"fixed","delay":{delay}}}}}]}}"#)
+}
+
+fn reloader(name: &str, content: &str) -> (PathBuf, ConfigReloader) {
+    let path = std::env::temp_dir().join(format!("receiver-{name}-{}.json", std::process::id()));
+    std::fs::write(&path, content).unwrap();
+    let loader = {
+        let path = path.clone();
+        Box::new(move || load_scenarios(&path).map(|s| ServerConfig::new(&s.diallers, Arc::default())))
+    };
+    let reloader = ConfigReloader::new(loader, vec![path.clone()]).unwrap();
+    (path, reloader)
+}
+
+fn accounts(reloader: &ConfigReloader) -> Vec<String> {
+    let mut accounts = reloader
+        .config()
+        .load()
+        .diallers
+        .keys()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>();
+    accounts.sort_unstable();
+    accounts
+}
+
+#[test]
+fn reload_test() {
+    let (path, reloader) = reloader("reload", &scenarios("1234", 100));
+    assert_eq!(vec!["1234"], accounts(&reloader));
+
+    std::fs::write(&path, scenarios("5678", 200)).unwrap();
+    assert_eq!(Ok(1), reloader.reload());
+    assert_eq!(vec!["5678"], accounts(&reloader));
+    let config = reloader.config().load_full();
+    assert!(!config.delays.contains_key("1234"));
+    assert_eq!(Some(&DelayProfile::Fixed { delay: 200 }), config.delays.get("5678"));
+
+    // Invalid file is rejected and the previous configuration keeps serving.
+    std::fs::write(&path, r#"{"diallers":[{"name":"1234","key":"short"}]}"#).unwrap();
+    assert!(reloader.reload().is_err());
+    assert_eq!(vec!["5678"], accounts(&reloader));
+
+    std::fs::remove_file(&path).unwrap();
+    assert!(reloader.reload().is_err());
+    assert_eq!(vec!["5678"], accounts(&reloader));
+}
+
+#[tokio::test]
+async fn watch_files_test() {
+    let (path, reloader) = reloader("watch", &scenarios("1234", 100));
+    let reloader = Arc::new(reloader);
+    let watcher = tokio::spawn(Arc::clone(&reloader).watch_files(WATCH_INTERVAL));
+    tokio::time::sleep(WATCH_INTERVAL * 3).await;
+
+    let file = std::fs::File::create(&path).unwrap();
+    std::io::Write::write_all(&mut &file, scenarios("5678", 100).as_bytes()).unwrap();
+    file.set_modified(SystemTime::now() + Duration::from_secs(10)).unwrap();
+    drop(file);
+
+    let reloaded = tokio::time::timeout(Duration::from_secs(5), async {
+        while accounts(&reloader) != ["5678"] {
+            tokio::time::sleep(WATCH_INTERVAL).await;
+        }
+    });
+    assert!(reloaded.await.is_ok());
+
+    watcher.abort();
+    std::fs::remove_file(&path).unwrap();
+}
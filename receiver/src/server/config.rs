@@ -1,32 +1,178 @@
-use common::dc09::parse_dc09_account_name;
+use arc_swap::ArcSwap;
+use clap::ValueEnum;
+use common::delay::DelayProfile;
+use common::keys::{AccountSelector, KeyQuery, SecretKey};
 use common::logging::DisplayMode;
 use common::scenarios::DiallerConfig;
 use common::utils::{SharedKeysMap, get_account_name};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt::Display;
 use std::str::FromStr;
+use std::sync::Arc;
 use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::Duration;
+use time::OffsetDateTime;
 
-pub type DiallerKeys = HashMap<String, u16>;
+#[cfg(test)]
+#[path = "./config.tests.rs"]
+mod config_tests;
+
+pub type AccountsRegistry = HashMap<AccountSelector, AccountConfig>;
+pub type SharedServerConfig = Arc<ArcSwap<ServerConfig>>;
+
+/// Default maximum size of a single DC09 frame in bytes.
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 2048;
+
+/// Smallest accepted maximum size of a single DC09 frame in bytes.
+pub const MIN_MAX_FRAME_SIZE: usize = 64;
+
+/// Default time to wait for in-flight connections on shutdown in seconds.
+pub const DEFAULT_SHUTDOWN_TIMEOUT: u64 = 5;
+
+/// Default number of UDP parse/decrypt workers.
+pub const DEFAULT_UDP_WORKERS: usize = 4;
+
+/// Default capacity of UDP datagram and response queues.
+pub const DEFAULT_UDP_QUEUE_SIZE: usize = 1024;
+
+/// Default time to replay cached responses for retransmitted UDP frames in seconds.
+pub const DEFAULT_UDP_RETRANSMIT_TTL: u64 = 30;
+
+/// Default maximum number of cached responses for retransmitted UDP frames.
+pub const DEFAULT_UDP_RETRANSMIT_CACHE_SIZE: usize = 10_000;
+
+/// Default time the previous account key is accepted after the next key becomes valid in seconds.
+pub const DEFAULT_KEY_GRACE: u64 = 86_400;
+
+/// Default maximum number of accounts missing in the accounts registry labelled individually in metrics.
+pub const DEFAULT_ACCOUNT_LABEL_LIMIT: usize = 1000;
+
+/// Holds configuration of a single account from the accounts registry.
+#[derive(Debug, Default, Clone)]
+pub struct AccountConfig {
+    /// Key valid until the first of the rotated keys becomes valid.
+    pub key: Option<SecretKey>,
+    /// Keys replacing the account key, each from its validity start.
+    pub rotation: Vec<KeyVersion>,
+    pub supervision: Option<u32>,
+    pub response: Option<ResponseMode>,
+}
+
+impl AccountConfig {
+    /// Creates new [`AccountConfig`] instance.
+    pub fn new(key: Option<SecretKey>) -> Self {
+        Self {
+            key,
+            rotation: Vec::new(),
+            supervision: None,
+            response: None,
+        }
+    }
+
+    /// Returns the account key and the rotated keys, ordered by validity start, with their state at the time `now`.\
+    /// **Note** that the previous key stays accepted within the `grace` window after the current key became valid.
+    pub fn key_states(&self, now: OffsetDateTime, grace: Duration) -> Vec<KeyStatus<'_>> {
+        let mut versions = self
+            .rotation
+            .iter()
+            .map(|v| (Some(&v.key), Some(v.valid_from)))
+            .collect::<Vec<_>>();
+        versions.sort_by_key(|(_, valid_from)| *valid_from);
+        versions.insert(0, (self.key.as_ref(), None));
+
+        let current = versions
+            .iter()
+            .rposition(|(_, from)| from.is_none_or(|f| f <= now))
+            .unwrap_or_default();
+        let in_grace = versions[current].1.is_some_and(|from| now < from + grace);
+        versions
+            .into_iter()
+            .enumerate()
+            .map(|(index, (key, valid_from))| KeyStatus {
+                key,
+                valid_from,
+                state: match index {
+                    _ if index > current => KeyState::Pending,
+                    _ if index == current => KeyState::Current,
+                    _ if index + 1 == current && in_grace => KeyState::Grace,
+                    _ => KeyState::Retired,
+                },
+            })
+            .collect()
+    }
+
+    /// Returns the current key and the previous key if it is still within the `grace` window at the time `now`.
+    pub fn active_keys(&self, now: OffsetDateTime, grace: Duration) -> (Option<&SecretKey>, Option<&SecretKey>) {
+        let states = self.key_states(now, grace);
+        let key = |state| states.iter().find(|s| s.state == state).and_then(|s| s.key);
+        (key(KeyState::Current), key(KeyState::Grace))
+    }
+}
+
+/// Key replacing the previous account key from the `valid_from` time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyVersion {
+    pub key: SecretKey,
+    pub valid_from: OffsetDateTime,
+}
+
+/// Lifecycle state of an account key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyState {
+    /// Not valid yet.
+    Pending,
+    /// Used to decrypt messages and encrypt responses.
+    Current,
+    /// Replaced by the current key, but still accepted within the grace window.
+    Grace,
+    /// Replaced by a newer key and no longer accepted.
+    Retired,
+}
+
+/// Account key with its state at a given time, `None` key and validity start stand for the unrotated account key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyStatus<'a> {
+    pub key: Option<&'a SecretKey>,
+    pub valid_from: Option<OffsetDateTime>,
+    pub state: KeyState,
+}
 
 /// Server configuration.
+#[derive(Clone)]
 pub struct ServerConfig {
-    pub diallers: DiallerKeys,
+    pub diallers: AccountsRegistry,
     pub keys: SharedKeysMap,
     pub mode: DisplayMode,
+    pub limits: ConnectionLimits,
+    pub unknown_account: UnknownAccountPolicy,
+    pub shutdown_timeout: Duration,
+    pub udp: UdpPipeline,
+    pub account_labels: AccountLabels,
+    /// Keys tried for encrypted messages from accounts missing in the accounts registry.
+    pub trial_keys: Vec<SecretKey>,
+    /// Time the previous account key is accepted after the next key becomes valid.
+    pub key_grace: Duration,
+    /// Response delay profiles of the accounts from the scenarios file.
+    pub delays: HashMap<String, DelayProfile>,
 }
 
 impl ServerConfig {
     /// Creates new [`ServerConfig`] instance.
     pub fn new(config: &[DiallerConfig], keys: SharedKeysMap) -> Self {
-        let mut diallers = DiallerKeys::new();
+        let mut diallers = AccountsRegistry::new();
+        let mut delays = HashMap::new();
         for (index, dialler) in config.iter().enumerate() {
             let account = dialler.name.parse::<u32>().ok();
-            let index = (index + 1) as u16;
+            let key = keys.get(&((index + 1) as u16));
 
             for i in 0..dialler.count.max(1) {
                 let account = get_account_name(i, account, &dialler.name, false);
-                diallers.insert(account, index);
+                diallers.insert(dialler.selector(&account), AccountConfig::new(key.cloned()));
+                if let Some(delay) = &dialler.delay {
+                    delays.insert(account, delay.clone());
+                }
             }
         }
 
@@ -34,6 +180,14 @@ impl ServerConfig {
             diallers,
             keys,
             mode: DisplayMode::Target,
+            limits: ConnectionLimits::default(),
+            unknown_account: UnknownAccountPolicy::Accept,
+            shutdown_timeout: Duration::from_secs(DEFAULT_SHUTDOWN_TIMEOUT),
+            udp: UdpPipeline::default(),
+            account_labels: AccountLabels::default(),
+            trial_keys: Vec::new(),
+            key_grace: Duration::from_secs(DEFAULT_KEY_GRACE),
+            delays,
         }
     }
 
@@ -43,22 +197,254 @@ impl ServerConfig {
         self
     }
 
-    /// Returns key for the specified message.
-    pub fn get_key_for_message(&self, received_message: &str) -> Option<&str> {
-        if !self.diallers.is_empty()
-            && let Ok(name) = parse_dc09_account_name(received_message)
-            && self.diallers.contains_key(&name)
-        {
-            let index = self.diallers[&name];
-            return self.keys.get(&index).map(String::as_str);
+    /// Sets connection lifecycle limits.
+    pub fn with_limits(mut self, limits: ConnectionLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Adds accounts that use the default key to the accounts registry.
+    pub fn with_accounts(mut self, accounts: &[AccountSelector]) -> Self {
+        let key = self.keys.get(&0);
+        for account in accounts {
+            self.diallers
+                .entry(account.clone())
+                .or_insert_with(|| AccountConfig::new(key.cloned()));
+        }
+
+        self
+    }
+
+    /// Sets policy for messages from accounts missing in the accounts registry.
+    pub fn with_unknown_account_policy(mut self, policy: UnknownAccountPolicy) -> Self {
+        self.unknown_account = policy;
+        self
+    }
+
+    /// Sets time to wait for in-flight connections on shutdown.
+    pub fn with_shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.shutdown_timeout = timeout;
+        self
+    }
+
+    /// Sets UDP processing pipeline settings.\
+    /// **Note** that the pipeline is created on startup, so changing it on reload has no effect.
+    pub fn with_udp_pipeline(mut self, udp: UdpPipeline) -> Self {
+        self.udp = udp;
+        self
+    }
+
+    /// Sets cardinality controls of metrics labelled by account.
+    pub fn with_account_labels(mut self, labels: AccountLabels) -> Self {
+        self.account_labels = labels;
+        self
+    }
+
+    /// Sets keys tried for encrypted messages from accounts missing in the accounts registry that can't be decrypted
+    /// with their key, an empty list disables trial decryption.
+    pub fn with_trial_keys(mut self, keys: Vec<SecretKey>) -> Self {
+        self.trial_keys = keys;
+        self
+    }
+
+    /// Sets time the previous account key is accepted after the next key becomes valid.
+    pub fn with_key_grace(mut self, grace: Duration) -> Self {
+        self.key_grace = grace;
+        self
+    }
+
+    /// Returns account configuration registered exactly for the `selector`.
+    pub fn get_account(&self, selector: &AccountSelector) -> Option<&AccountConfig> {
+        self.diallers.get(selector)
+    }
+
+    /// Returns the most specific registry entry matching the panel identified by the `query`.\
+    /// **Note** that an entry matching the account outranks entries matching the receiver and line only.
+    pub fn find_account(&self, query: &KeyQuery) -> Option<(&AccountSelector, &AccountConfig)> {
+        if self.diallers.is_empty() {
+            return None;
         }
 
-        self.keys.get(&0).map(String::as_str)
+        AccountSelector::candidates(query).find_map(|selector| self.diallers.get_key_value(&selector))
+    }
+
+    /// Adds or replaces account in the accounts registry, returns `true` if the account was added.
+    pub fn set_account(&mut self, selector: AccountSelector, config: AccountConfig) -> bool {
+        self.diallers.insert(selector, config).is_none()
+    }
+
+    /// Removes account from the accounts registry, returns `true` if the account existed.
+    pub fn remove_account(&mut self, selector: &AccountSelector) -> bool {
+        self.diallers.remove(selector).is_some()
+    }
+
+    /// Returns key for messages from the panel identified by the `query`, panels missing in the registry use the
+    /// default key.
+    pub fn get_key(&self, query: &KeyQuery) -> Option<&SecretKey> {
+        match self.find_account(query) {
+            Some((_, config)) => config.active_keys(OffsetDateTime::now_utc(), self.key_grace).0,
+            None => self.keys.get(&0),
+        }
+    }
+}
+
+/// Defines what happens to a connection that exceeds connection limits.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LimitPolicy {
+    /// Close the connection immediately.
+    #[default]
+    Reject,
+    /// Keep the connection waiting until a slot is released.
+    Queue,
+}
+
+/// Defines how messages from accounts missing in the accounts registry are handled.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UnknownAccountPolicy {
+    /// Respond as for any other account.
+    #[default]
+    Accept,
+    /// Respond with `DUH`.
+    Duh,
+    /// Respond with `NAK`.
+    Nak,
+    /// Ignore the message and do not respond.
+    Drop,
+}
+
+impl UnknownAccountPolicy {
+    /// Returns response mode for a message from unknown account, `None` if it should be dropped.
+    pub fn response_mode(&self, mode: ResponseMode) -> Option<ResponseMode> {
+        match self {
+            Self::Accept => Some(mode),
+            Self::Duh => Some(ResponseMode::Duh),
+            Self::Nak => Some(ResponseMode::Nak),
+            Self::Drop => None,
+        }
+    }
+}
+
+impl Display for UnknownAccountPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Accept => write!(f, "accept"),
+            Self::Duh => write!(f, "duh"),
+            Self::Nak => write!(f, "nak"),
+            Self::Drop => write!(f, "drop"),
+        }
+    }
+}
+
+/// Connection lifecycle limits, `0` means unlimited.\
+/// **Note** that `max_frame_size` is always limited and must be at least [`MIN_MAX_FRAME_SIZE`].
+#[derive(Debug, Clone)]
+pub struct ConnectionLimits {
+    pub idle_timeout: Option<Duration>,
+    pub max_connections: usize,
+    pub max_connections_per_ip: usize,
+    pub policy: LimitPolicy,
+    pub max_frame_size: usize,
+}
+
+impl Default for ConnectionLimits {
+    fn default() -> Self {
+        Self {
+            idle_timeout: None,
+            max_connections: 0,
+            max_connections_per_ip: 0,
+            policy: LimitPolicy::Reject,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+        }
+    }
+}
+
+impl ConnectionLimits {
+    /// Checks whether [`ConnectionLimits`] contains valid and meaningful data.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_frame_size < MIN_MAX_FRAME_SIZE {
+            return Err(format!("max_frame_size: value must be at least {MIN_MAX_FRAME_SIZE}"));
+        }
+
+        Ok(())
+    }
+}
+
+/// Defines what happens to a UDP datagram received while the processing queue is full.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QueuePolicy {
+    /// Drop the received datagram.
+    #[default]
+    Drop,
+    /// Stop receiving until the queue has free space, leaving datagrams in the socket buffer.
+    Block,
+}
+
+/// UDP processing pipeline settings.
+#[derive(Debug, Clone)]
+pub struct UdpPipeline {
+    pub workers: usize,
+    pub queue_size: usize,
+    pub policy: QueuePolicy,
+    pub retransmit_ttl: Option<Duration>,
+    pub retransmit_cache_size: usize,
+}
+
+impl Default for UdpPipeline {
+    fn default() -> Self {
+        Self {
+            workers: DEFAULT_UDP_WORKERS,
+            queue_size: DEFAULT_UDP_QUEUE_SIZE,
+            policy: QueuePolicy::Drop,
+            retransmit_ttl: Some(Duration::from_secs(DEFAULT_UDP_RETRANSMIT_TTL)),
+            retransmit_cache_size: DEFAULT_UDP_RETRANSMIT_CACHE_SIZE,
+        }
+    }
+}
+
+impl UdpPipeline {
+    /// Checks whether [`UdpPipeline`] contains valid and meaningful data.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.workers == 0 {
+            return Err("workers: value must be at least 1".to_owned());
+        }
+
+        if self.queue_size == 0 {
+            return Err("queue_size: value must be at least 1".to_owned());
+        }
+
+        if self.retransmit_ttl.is_some() && self.retransmit_cache_size == 0 {
+            return Err("retransmit_cache_size: value must be at least 1".to_owned());
+        }
+
+        Ok(())
+    }
+}
+
+/// Cardinality controls of metrics labelled by account.\
+/// **Note** that accounts from the accounts registry are always labelled individually unless labels are disabled.
+#[derive(Debug, Clone)]
+pub struct AccountLabels {
+    /// Whether per-account series are created at all.
+    pub enabled: bool,
+    /// Maximum number of other accounts labelled individually before they are aggregated.
+    pub limit: usize,
+}
+
+impl Default for AccountLabels {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            limit: DEFAULT_ACCOUNT_LABEL_LIMIT,
+        }
     }
 }
 
 /// Defines possible responses for received messages.
-#[derive(Default, Clone, Copy, PartialEq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ResponseMode {
     #[default]
     Ack,
@@ -119,6 +505,7 @@ impl FromStr for ResponseMode {
 pub struct ResponseModes {
     pub message: AtomicU8,
     pub heartbeat: AtomicU8,
+    initial: (ResponseMode, ResponseMode),
 }
 
 impl Default for ResponseModes {
@@ -133,6 +520,7 @@ impl ResponseModes {
         Self {
             message: AtomicU8::new(message.into()),
             heartbeat: AtomicU8::new(heartbeat.into()),
+            initial: (message, heartbeat),
         }
     }
 
@@ -155,4 +543,10 @@ impl ResponseModes {
     pub fn set_heartbeat(&self, mode: ResponseMode) {
         self.heartbeat.store(mode.into(), Ordering::Relaxed);
     }
+
+    /// Restores response modes the instance was created with.
+    pub fn reset(&self) {
+        self.set_message(self.initial.0);
+        self.set_heartbeat(self.initial.1);
+    }
 }
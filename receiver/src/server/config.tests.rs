@@ -0,0 +1,96 @@
+use super::*;
+use crate::utils::get_response_mode;
+use common::dc09::DC09Message;
+
+const KEY: &str = "0123456789abcdef";
+const DIALLER_KEY: &str = "1111111111111111";
+
+fn key(config: &AccountConfig) -> Option<&str> {
+    config.key.as_ref().map(SecretKey::expose)
+}
+
+#[test]
+fn accounts_registry_test() {
+    let mut dialler = DiallerConfig::new("1000".to_owned(), 0, false, 3);
+    dialler.key = Some(SecretKey::from(DIALLER_KEY));
+    let keys = HashMap::from([(0, SecretKey::from(KEY)), (1, SecretKey::from(DIALLER_KEY))]);
+    let accounts = ["1001".parse().unwrap(), "2000".parse().unwrap()];
+    let mut config = ServerConfig::new(&[dialler], Arc::new(keys)).with_accounts(&accounts);
+
+    assert_eq!(4, config.diallers.len());
+    let selector = "1002".parse().unwrap();
+    assert_eq!(Some(DIALLER_KEY), config.get_account(&selector).and_then(key));
+    // Accounts file does not override the key of a dialler from the scenarios file.
+    assert_eq!(Some(DIALLER_KEY), config.get_account(&accounts[0]).and_then(key));
+    assert_eq!(Some(KEY), config.get_account(&accounts[1]).and_then(key));
+    assert!(config.get_account(&"1003".parse().unwrap()).is_none());
+
+    assert!(config.set_account("3000".parse().unwrap(), AccountConfig::default()));
+    assert!(!config.set_account("3000".parse().unwrap(), AccountConfig::new(Some(SecretKey::from(KEY)))));
+    assert_eq!(Some(KEY), config.get_account(&"3000".parse().unwrap()).and_then(key));
+
+    assert!(config.remove_account(&selector));
+    assert!(!config.remove_account(&selector));
+    let query = KeyQuery::new("1002", None, None);
+    assert!(config.find_account(&query).is_none());
+    assert_eq!(Some(KEY), config.get_key(&query).map(SecretKey::expose));
+}
+
+#[test]
+fn find_account_test() {
+    let mut config = ServerConfig::new(&[], Arc::default());
+    for selector in ["*", "R1 *", "L2 *", "R1 L2 *", "1234", "R1 1234", "R1 L2 1234"] {
+        config.set_account(selector.parse().unwrap(), AccountConfig::default());
+    }
+
+    let find = |account, receiver, line| {
+        let query = KeyQuery::new(account, receiver, line);
+        config.find_account(&query).map(|(selector, _)| selector.to_string())
+    };
+    assert_eq!(Some("R1 L2 1234"), find("1234", Some("R1"), Some("L2")).as_deref());
+    assert_eq!(Some("R1 1234"), find("1234", Some("R1"), Some("L3")).as_deref());
+    assert_eq!(Some("1234"), find("1234", Some("R2"), Some("L2")).as_deref());
+    assert_eq!(Some("R1 L2 *"), find("5678", Some("R1"), Some("L2")).as_deref());
+    assert_eq!(Some("R1 *"), find("5678", Some("R1"), None).as_deref());
+    assert_eq!(Some("L2 *"), find("5678", Some("R2"), Some("L2")).as_deref());
+    assert_eq!(Some("*"), find("5678", None, None).as_deref());
+
+    assert!(
+        ServerConfig::new(&[], Arc::default())
+            .find_account(&KeyQuery::new("1234", None, None))
+            .is_none()
+    );
+}
+
+#[test]
+fn unknown_account_policy_test() {
+    let modes = ResponseModes::new(ResponseMode::Ack, ResponseMode::Nak);
+    let message = DC09Message::new("SIA-DCS".to_owned(), "5678".to_owned(), 1, None);
+    let heartbeat = DC09Message::new("NULL".to_owned(), "5678".to_owned(), 1, None);
+    let known = AccountConfig {
+        response: Some(ResponseMode::Duh),
+        ..AccountConfig::default()
+    };
+
+    let config = |policy| {
+        let mut config = ServerConfig::new(&[], Arc::default()).with_unknown_account_policy(policy);
+        config.set_account("1234".parse().unwrap(), known.clone());
+        config
+    };
+    let mode = |config: &ServerConfig, message: &DC09Message| get_response_mode("TCP", message, config, &modes);
+
+    let accept = config(UnknownAccountPolicy::Accept);
+    assert_eq!(Some(ResponseMode::Ack), mode(&accept, &message));
+    assert_eq!(Some(ResponseMode::Nak), mode(&accept, &heartbeat));
+    assert_eq!(Some(ResponseMode::Duh), mode(&config(UnknownAccountPolicy::Duh), &message));
+    assert_eq!(Some(ResponseMode::Nak), mode(&config(UnknownAccountPolicy::Nak), &message));
+    assert_eq!(None, mode(&config(UnknownAccountPolicy::Drop), &message));
+
+    // Known accounts use their own response mode regardless of the policy.
+    let message = DC09Message::new("SIA-DCS".to_owned(), "1234".to_owned(), 1, None);
+    assert_eq!(Some(ResponseMode::Duh), mode(&config(UnknownAccountPolicy::Drop), &message));
+
+    // Without an accounts registry every account is unknown.
+    let empty = ServerConfig::new(&[], Arc::default()).with_unknown_account_policy(UnknownAccountPolicy::Nak);
+    assert_eq!(Some(ResponseMode::Nak), mode(&empty, &message));
+}
@@ -0,0 +1,70 @@
+use common::dc09::{DC09Error, DC09Message, parse_dc09_with_keys};
+use common::keys::{KeyQuery, SecretKey};
+
+use crate::metrics;
+use crate::server::trial::selector;
+use crate::server::{ServerConfig, ServerState};
+
+#[cfg(test)]
+#[path = "./decode.tests.rs"]
+mod decode_tests;
+
+/// Parses the received `frame` and returns it together with the key that decrypted it.\
+/// **Note** that encrypted frames are also tried with the previous key of a rotated account key within its grace window,
+/// and frames from accounts missing in the accounts registry with the learned key and the trial keys of the `config`.
+/// A matching trial key is learned for the panel.
+pub fn decode_frame(
+    frame: &str,
+    config: &ServerConfig,
+    state: &ServerState,
+) -> Result<(DC09Message, Option<SecretKey>), DC09Error> {
+    let query = KeyQuery::from_frame(frame).unwrap_or_default();
+    let keys = state.keys.keys(&query);
+    let trial = !config.trial_keys.is_empty() && config.find_account(&query).is_none();
+    if !trial && keys.len() < 2 {
+        let key = keys.into_iter().next();
+        return DC09Message::try_from(frame, key.as_ref().map(SecretKey::expose)).map(|message| (message, key));
+    }
+
+    let learned = trial.then(|| state.trial_matches.key(&query)).flatten();
+    let mut candidates = learned.iter().chain(&keys).map(SecretKey::expose).collect::<Vec<_>>();
+    let (keys_offset, trial_offset) = (usize::from(learned.is_some()), candidates.len());
+    if trial {
+        candidates.extend(config.trial_keys.iter().map(SecretKey::expose));
+    }
+
+    let (message, index) = match parse_dc09_with_keys(frame, &candidates) {
+        Ok((message, None)) => return Ok((message, None)),
+        Ok((message, Some(index))) => (message, index),
+        Err(DC09Error::DecryptError) if trial => {
+            metrics::trial_decryptions().with_label_values(&["failed"]).inc();
+            return Err(DC09Error::DecryptError);
+        },
+        Err(e) => return Err(e),
+    };
+
+    if index >= trial_offset {
+        let candidate = index - trial_offset;
+        log::info!(
+            "account {} matched by trial decryption with trial key #{}",
+            selector(&query),
+            candidate + 1
+        );
+        metrics::trial_decryptions().with_label_values(&["matched"]).inc();
+        state.trial_matches.learn(&query, candidate, candidates[index]);
+    } else if index < keys_offset {
+        state.trial_matches.record(&query);
+    } else {
+        if learned.is_some() {
+            state.trial_matches.forget(&query);
+        }
+
+        if index > keys_offset {
+            log::warn!("account {} still uses the previous key", selector(&query));
+            let account = state.account_labels.label(&query, config);
+            metrics::previous_key_messages().with_label_values(&[account]).inc();
+        }
+    }
+
+    Ok((message, Some(SecretKey::from(candidates[index]))))
+}
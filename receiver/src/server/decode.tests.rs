@@ -0,0 +1,142 @@
+use super::*;
+use crate::server::{AccountConfig, KeyState, KeyVersion, ResponseModes};
+use arc_swap::ArcSwap;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use time::OffsetDateTime;
+
+const DEFAULT_KEY: &str = "0000000000000000";
+const TRIAL_KEYS: [&str; 2] = ["1111111111111111", "222222222222222222222222"];
+
+fn frame(account: &str, key: &str) -> String {
+    DC09Message::new(
+        "SIA-DCS".to_owned(),
+        account.to_owned(),
+        1,
+        Some(format!("#{account}|NRR|AStart")),
+    )
+    .with_receiver(Some("R1".to_owned()))
+    .to_encrypted(key)
+    .unwrap()
+}
+
+fn state(config: ServerConfig) -> (ServerConfig, ServerState) {
+    let shared = Arc::new(ArcSwap::from_pointee(config.clone()));
+    (config, ServerState::new(&shared, ResponseModes::default()))
+}
+
+fn config() -> ServerConfig {
+    let keys = HashMap::from([(0, SecretKey::from(DEFAULT_KEY))]);
+    ServerConfig::new(&[], Arc::new(keys)).with_trial_keys(TRIAL_KEYS.map(SecretKey::from).to_vec())
+}
+
+#[test]
+fn learn_trial_key_test() {
+    let (config, state) = state(config());
+
+    let (message, key) = decode_frame(&frame("1234", TRIAL_KEYS[1]), &config, &state).unwrap();
+    assert_eq!("1234", message.account);
+    assert_eq!(Some(TRIAL_KEYS[1]), key.as_ref().map(SecretKey::expose));
+    assert!(decode_frame(&frame("1234", TRIAL_KEYS[1]), &config, &state).is_ok());
+
+    let matches = state.trial_matches.list();
+    assert_eq!(1, matches.len());
+    assert_eq!("R1 L0 1234", matches[0].0.to_string());
+    assert_eq!(1, matches[0].1.candidate);
+    assert_eq!(2, matches[0].1.messages);
+
+    // The panel was re-provisioned with the default key, so the learned key is forgotten.
+    let (_, key) = decode_frame(&frame("1234", DEFAULT_KEY), &config, &state).unwrap();
+    assert_eq!(Some(DEFAULT_KEY), key.as_ref().map(SecretKey::expose));
+    assert!(state.trial_matches.list().is_empty());
+}
+
+#[test]
+fn trial_decryption_skipped_test() {
+    let mut registered = config();
+    registered.set_account(
+        "1234".parse().unwrap(),
+        AccountConfig::new(Some(SecretKey::from(DEFAULT_KEY))),
+    );
+    let (config, state) = state(registered);
+
+    assert!(decode_frame(&frame("1234", TRIAL_KEYS[0]), &config, &state).is_err());
+    assert!(decode_frame(&frame("5678", TRIAL_KEYS[0]), &config, &state).is_ok());
+
+    let error = decode_frame(&frame("9999", "333333333333333333333333"), &config, &state).unwrap_err();
+    assert!(matches!(error, DC09Error::DecryptError), "{error}");
+    assert_eq!(1, state.trial_matches.list().len());
+    assert_eq!(1, state.trial_matches.clear());
+}
+
+fn rotated(valid_from: OffsetDateTime, grace: Duration) -> (ServerConfig, ServerState) {
+    let mut account = AccountConfig::new(Some(SecretKey::from(DEFAULT_KEY)));
+    account.rotation.push(KeyVersion {
+        key: SecretKey::from(TRIAL_KEYS[0]),
+        valid_from,
+    });
+
+    let mut config = ServerConfig::new(&[], Arc::default()).with_key_grace(grace);
+    config.set_account("1234".parse().unwrap(), account);
+    state(config)
+}
+
+#[test]
+fn key_rotation_test() {
+    let now = OffsetDateTime::now_utc();
+    let (config, state) = rotated(now - Duration::from_secs(60), Duration::from_secs(3600));
+    assert_eq!(2, state.keys.keys(&KeyQuery::new("1234", None, None)).len());
+
+    let (_, key) = decode_frame(&frame("1234", TRIAL_KEYS[0]), &config, &state).unwrap();
+    assert_eq!(Some(TRIAL_KEYS[0]), key.as_ref().map(SecretKey::expose));
+    let (_, key) = decode_frame(&frame("1234", DEFAULT_KEY), &config, &state).unwrap();
+    assert_eq!(Some(DEFAULT_KEY), key.as_ref().map(SecretKey::expose));
+
+    // The grace window is over, so only the new key is accepted.
+    let (config, state) = rotated(now - Duration::from_secs(60), Duration::from_secs(30));
+    assert!(decode_frame(&frame("1234", DEFAULT_KEY), &config, &state).is_err());
+    assert!(decode_frame(&frame("1234", TRIAL_KEYS[0]), &config, &state).is_ok());
+
+    // The new key is not valid yet.
+    let (config, state) = rotated(now + Duration::from_secs(60), Duration::from_secs(3600));
+    assert!(decode_frame(&frame("1234", TRIAL_KEYS[0]), &config, &state).is_err());
+    assert!(decode_frame(&frame("1234", DEFAULT_KEY), &config, &state).is_ok());
+}
+
+#[test]
+fn key_states_test() {
+    let now = OffsetDateTime::now_utc();
+    let version = |key: &str, offset: i64| KeyVersion {
+        key: SecretKey::from(key),
+        valid_from: now + time::Duration::seconds(offset),
+    };
+    let mut account = AccountConfig::new(Some(SecretKey::from(DEFAULT_KEY)));
+    account.rotation = vec![version("c", 60), version("a", -7200), version("b", -60)];
+
+    let states = account
+        .key_states(now, Duration::from_secs(3600))
+        .into_iter()
+        .map(|s| (s.key.map(SecretKey::expose), s.state))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![
+            (Some(DEFAULT_KEY), KeyState::Retired),
+            (Some("a"), KeyState::Grace),
+            (Some("b"), KeyState::Current),
+            (Some("c"), KeyState::Pending),
+        ],
+        states
+    );
+    assert_eq!((Some("b"), Some("a")), active_keys(&account, now, Duration::from_secs(3600)));
+    assert_eq!((Some("b"), None), active_keys(&account, now, Duration::from_secs(30)));
+    assert_eq!(
+        (Some(DEFAULT_KEY), None),
+        active_keys(&AccountConfig::new(Some(SecretKey::from(DEFAULT_KEY))), now, Duration::ZERO)
+    );
+}
+
+fn active_keys(account: &AccountConfig, now: OffsetDateTime, grace: Duration) -> (Option<&str>, Option<&str>) {
+    let (current, previous) = account.active_keys(now, grace);
+    (current.map(SecretKey::expose), previous.map(SecretKey::expose))
+}
@@ -0,0 +1,89 @@
+use common::delay::DelayProfile;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use crate::server::ServerConfig;
+
+#[cfg(test)]
+#[path = "./delay.tests.rs"]
+mod delay_tests;
+
+/// Holds response delay profiles set at runtime: the default one and per-account overrides.\
+/// **Note** that per-account overrides take precedence over the profiles from the scenarios file kept in
+/// [`ServerConfig::delays`], so they survive configuration reloads.
+#[derive(Debug, Default)]
+pub struct ResponseDelays {
+    configured: Option<DelayProfile>,
+    default: RwLock<Option<DelayProfile>>,
+    accounts: RwLock<HashMap<String, DelayProfile>>,
+}
+
+impl ResponseDelays {
+    /// Creates new [`ResponseDelays`] instance, the `default` profile is restored by [`ResponseDelays::reset`].
+    pub fn new(default: Option<DelayProfile>) -> Self {
+        Self {
+            configured: default.clone(),
+            default: RwLock::new(default),
+            accounts: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Restores the configured default profile, removes all per-account overrides and returns their number.
+    pub fn reset(&self) -> usize {
+        *self.default.write().expect("lock is not poisoned") = self.configured.clone();
+        let mut accounts = self.accounts.write().expect("lock is not poisoned");
+        let cleared = accounts.len();
+        accounts.clear();
+        cleared
+    }
+
+    /// Returns a random response delay for the specified `account`.
+    pub fn sample(&self, account: &str, config: &ServerConfig) -> Duration {
+        if let Some(delay) = self.accounts.read().expect("lock is not poisoned").get(account) {
+            return delay.sample();
+        }
+
+        if let Some(delay) = config.delays.get(account) {
+            return delay.sample();
+        }
+
+        self.default
+            .read()
+            .expect("lock is not poisoned")
+            .as_ref()
+            .map_or(Duration::ZERO, DelayProfile::sample)
+    }
+
+    /// Gets the default delay profile.
+    pub fn default_profile(&self) -> Option<DelayProfile> {
+        self.default.read().expect("lock is not poisoned").clone()
+    }
+
+    /// Sets the default delay profile.
+    pub fn set_default_profile(&self, profile: Option<DelayProfile>) {
+        *self.default.write().expect("lock is not poisoned") = profile;
+    }
+
+    /// Gets delay profile for the specified `account`, either the override or the one from the scenarios file.
+    pub fn account_profile(&self, account: &str, config: &ServerConfig) -> Option<DelayProfile> {
+        let accounts = self.accounts.read().expect("lock is not poisoned");
+        accounts.get(account).or_else(|| config.delays.get(account)).cloned()
+    }
+
+    /// Gets all per-account delay profiles, overrides replace the profiles from the scenarios file.
+    pub fn account_profiles(&self, config: &ServerConfig) -> HashMap<String, DelayProfile> {
+        let mut profiles = config.delays.clone();
+        profiles.extend(self.accounts.read().expect("lock is not poisoned").clone());
+        profiles
+    }
+
+    /// Sets delay profile override for the specified `account`, `None` removes the override.
+    pub fn set_account_profile(&self, account: &str, profile: Option<DelayProfile>) -> Option<DelayProfile> {
+        let mut accounts = self.accounts.write().expect("lock is not poisoned");
+        match profile {
+            Some(profile) => accounts.insert(account.to_owned(), profile),
+            None => accounts.remove(account),
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use super::*;
+use common::scenarios::DiallerConfig;
+use std::sync::Arc;
+
+fn fixed(millis: u64) -> DelayProfile {
+    DelayProfile::Fixed { delay: millis }
+}
+
+#[test]
+fn response_delays_precedence_test() {
+    let mut dialler = DiallerConfig::new("1234".to_owned(), 0, false, 2);
+    dialler.delay = Some(fixed(200));
+    let config = ServerConfig::new(&[dialler], Arc::default());
+    let delays = ResponseDelays::new(Some(fixed(100)));
+
+    assert_eq!(Duration::from_millis(200), delays.sample("1235", &config));
+    assert_eq!(Duration::from_millis(100), delays.sample("5678", &config));
+
+    delays.set_account_profile("1234", Some(fixed(300)));
+    assert_eq!(Duration::from_millis(300), delays.sample("1234", &config));
+    assert_eq!(2, delays.account_profiles(&config).len());
+    assert_eq!(Some(fixed(300)), delays.account_profile("1234", &config));
+
+    assert_eq!(Some(fixed(300)), delays.set_account_profile("1234", None));
+    assert_eq!(Some(fixed(200)), delays.account_profile("1234", &config));
+    assert_eq!(None, delays.set_account_profile("1234", None));
+}
+
+#[test]
+fn response_delays_reset_test() {
+    let mut dialler = DiallerConfig::new("1234".to_owned(), 0, false, 1);
+    dialler.delay = Some(fixed(200));
+    let config = ServerConfig::new(&[dialler], Arc::default());
+    let delays = ResponseDelays::new(Some(fixed(100)));
+
+    delays.set_default_profile(Some(fixed(50)));
+    delays.set_account_profile("1234", Some(fixed(300)));
+    delays.set_account_profile("5678", Some(fixed(400)));
+    assert_eq!(2, delays.reset());
+
+    assert_eq!(Some(fixed(100)), delays.default_profile());
+    assert_eq!(Duration::from_millis(200), delays.sample("1234", &config));
+    assert_eq!(Duration::from_millis(100), delays.sample("5678", &config));
+    assert_eq!(0, delays.reset());
+}
@@ -0,0 +1,220 @@
+use common::dc09::DC09Message;
+use regex_automata::meta::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use time::{Duration, OffsetDateTime};
+
+#[cfg(test)]
+#[path = "./expectations.tests.rs"]
+mod expectations_tests;
+
+/// Maximum number of matching events kept per expectation.
+const MAX_MATCHED_EVENTS: usize = 100;
+
+/// Messages expected by a test, fields that are not set match any message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExpectationSpec {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// Regular expression matched against the message data.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+    /// Minimum number of matching messages.
+    #[serde(default = "default_count")]
+    pub count: usize,
+    /// Time window in seconds, starting when the expectation is registered.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub within: Option<u64>,
+}
+
+fn default_count() -> usize {
+    1
+}
+
+/// State of an expectation.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExpectationStatus {
+    /// Not enough matching messages yet, the time window is still open.
+    Pending,
+    /// Required number of matching messages was received.
+    Met,
+    /// Time window elapsed before the required number of matching messages was received.
+    Unmet,
+}
+
+/// Message that matched an expectation.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExpectedEvent {
+    pub timestamp: i64,
+    pub transport: String,
+    pub peer: SocketAddr,
+    pub account: String,
+    pub token: String,
+    pub sequence: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+}
+
+/// Expectation state returned by the HTTP API.\
+/// **Note** that only the first 100 matching events are kept, `matched` counts all of them.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExpectationReport {
+    pub id: u64,
+    pub status: ExpectationStatus,
+    pub matched: usize,
+    pub created: i64,
+    #[serde(flatten)]
+    pub spec: ExpectationSpec,
+    pub events: Vec<ExpectedEvent>,
+}
+
+struct Expectation {
+    spec: ExpectationSpec,
+    pattern: Option<Regex>,
+    created: OffsetDateTime,
+    matched: usize,
+    events: Vec<ExpectedEvent>,
+}
+
+impl Expectation {
+    /// Returns end of the time window, `None` if there is no window or it ends beyond the representable time.
+    fn deadline(&self) -> Option<OffsetDateTime> {
+        let within = Duration::seconds(i64::try_from(self.spec.within?).unwrap_or(i64::MAX));
+        self.created.checked_add(within)
+    }
+
+    fn is_open(&self, now: OffsetDateTime) -> bool {
+        self.deadline().is_none_or(|deadline| now <= deadline)
+    }
+
+    fn matches(&self, msg: &DC09Message) -> bool {
+        self.spec.account.as_ref().is_none_or(|account| *account == msg.account)
+            && self.spec.token.as_ref().is_none_or(|token| *token == msg.token)
+            && self
+                .pattern
+                .as_ref()
+                .is_none_or(|pattern| pattern.is_match(msg.data.as_deref().unwrap_or_default()))
+    }
+
+    fn status(&self, now: OffsetDateTime) -> ExpectationStatus {
+        if self.matched >= self.spec.count {
+            ExpectationStatus::Met
+        } else if self.is_open(now) {
+            ExpectationStatus::Pending
+        } else {
+            ExpectationStatus::Unmet
+        }
+    }
+
+    fn report(&self, id: u64, now: OffsetDateTime) -> ExpectationReport {
+        ExpectationReport {
+            id,
+            status: self.status(now),
+            matched: self.matched,
+            created: self.created.unix_timestamp(),
+            spec: self.spec.clone(),
+            events: self.events.clone(),
+        }
+    }
+}
+
+/// Holds expectations registered by tests and matches received messages against them.
+#[derive(Default)]
+pub struct Expectations {
+    next_id: AtomicU64,
+    entries: RwLock<BTreeMap<u64, Expectation>>,
+}
+
+impl Expectations {
+    /// Registers new expectation and returns its initial state.
+    pub fn add(&self, spec: ExpectationSpec) -> Result<ExpectationReport, String> {
+        if spec.count == 0 {
+            return Err("count must be at least 1".to_owned());
+        }
+
+        let pattern = spec
+            .data
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .map_err(|e| match e.syntax_error() {
+                Some(error) => format!("invalid data pattern: {error}"),
+                None => format!("invalid data pattern: {e}"),
+            })?;
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let expectation = Expectation {
+            spec,
+            pattern,
+            created: OffsetDateTime::now_utc(),
+            matched: 0,
+            events: Vec::new(),
+        };
+
+        let report = expectation.report(id, expectation.created);
+        self.entries.write().expect("lock is not poisoned").insert(id, expectation);
+        Ok(report)
+    }
+
+    /// Records a message received from the `peer` in all expectations it matches.
+    pub fn record(&self, transport: &str, peer: &SocketAddr, msg: &DC09Message) {
+        if self.entries.read().expect("lock is not poisoned").is_empty() {
+            return;
+        }
+
+        let now = OffsetDateTime::now_utc();
+        let mut entries = self.entries.write().expect("lock is not poisoned");
+        for expectation in entries.values_mut() {
+            if !expectation.is_open(now) || !expectation.matches(msg) {
+                continue;
+            }
+
+            expectation.matched += 1;
+            if expectation.events.len() < MAX_MATCHED_EVENTS {
+                expectation.events.push(ExpectedEvent {
+                    timestamp: now.unix_timestamp(),
+                    transport: transport.to_owned(),
+                    peer: *peer,
+                    account: msg.account.clone(),
+                    token: msg.token.clone(),
+                    sequence: msg.sequence,
+                    data: msg.data.clone(),
+                });
+            }
+        }
+    }
+
+    /// Returns state of the expectation with the `id`.
+    pub fn get(&self, id: u64) -> Option<ExpectationReport> {
+        let now = OffsetDateTime::now_utc();
+        let entries = self.entries.read().expect("lock is not poisoned");
+        entries.get(&id).map(|expectation| expectation.report(id, now))
+    }
+
+    /// Returns state of all registered expectations.
+    pub fn list(&self) -> Vec<ExpectationReport> {
+        let now = OffsetDateTime::now_utc();
+        let entries = self.entries.read().expect("lock is not poisoned");
+        entries.iter().map(|(id, expectation)| expectation.report(*id, now)).collect()
+    }
+
+    /// Removes the expectation with the `id`, returns `true` if it existed.
+    pub fn remove(&self, id: u64) -> bool {
+        self.entries.write().expect("lock is not poisoned").remove(&id).is_some()
+    }
+
+    /// Removes all expectations and returns their number.
+    pub fn clear(&self) -> usize {
+        let mut entries = self.entries.write().expect("lock is not poisoned");
+        let removed = entries.len();
+        entries.clear();
+        removed
+    }
+}
@@ -0,0 +1,100 @@
+use super::*;
+
+fn spec(account: Option<&str>, token: Option<&str>, data: Option<&str>, count: usize) -> ExpectationSpec {
+    ExpectationSpec {
+        account: account.map(str::to_owned),
+        token: token.map(str::to_owned),
+        data: data.map(str::to_owned),
+        count,
+        within: None,
+    }
+}
+
+fn message(account: &str, token: &str, data: Option<&str>) -> DC09Message {
+    DC09Message::new(token.to_owned(), account.to_owned(), 1, data.map(str::to_owned))
+}
+
+#[test]
+fn match_expectation_test() {
+    let peer = "127.0.0.1:40000".parse().unwrap();
+    let expectations = Expectations::default();
+    let id = expectations
+        .add(spec(Some("1234"), Some("SIA-DCS"), Some("^#1234\\|NBA"), 2))
+        .unwrap()
+        .id;
+
+    expectations.record("tcp", &peer, &message("1234", "SIA-DCS", Some("#1234|NBA1")));
+    expectations.record("tcp", &peer, &message("1234", "SIA-DCS", Some("#1234|NRP1")));
+    expectations.record("tcp", &peer, &message("5678", "SIA-DCS", Some("#1234|NBA1")));
+    expectations.record("tcp", &peer, &message("1234", "NULL", None));
+
+    let report = expectations.get(id).unwrap();
+    assert_eq!(ExpectationStatus::Pending, report.status);
+    assert_eq!(1, report.matched);
+    assert_eq!("tcp", report.events[0].transport);
+
+    expectations.record("udp", &peer, &message("1234", "SIA-DCS", Some("#1234|NBA2")));
+    let report = expectations.get(id).unwrap();
+    assert_eq!(ExpectationStatus::Met, report.status);
+    assert_eq!(2, report.events.len());
+}
+
+#[test]
+fn expired_expectation_test() {
+    let peer = "127.0.0.1:40000".parse().unwrap();
+    let expectations = Expectations::default();
+    let id = expectations
+        .add(ExpectationSpec {
+            within: Some(0),
+            ..spec(None, Some("NULL"), None, 1)
+        })
+        .unwrap()
+        .id;
+
+    std::thread::sleep(std::time::Duration::from_millis(10));
+    expectations.record("tcp", &peer, &message("1234", "NULL", None));
+
+    let report = expectations.get(id).unwrap();
+    assert_eq!(ExpectationStatus::Unmet, report.status);
+    assert_eq!(0, report.matched);
+}
+
+#[test]
+fn manage_expectations_test() {
+    let expectations = Expectations::default();
+    assert!(expectations.add(spec(None, None, None, 0)).is_err());
+    assert!(expectations.add(spec(None, None, Some("(unclosed"), 1)).is_err());
+
+    let first = expectations.add(spec(None, None, None, 1)).unwrap().id;
+    let second = expectations.add(spec(None, None, None, 1)).unwrap().id;
+    assert_ne!(first, second);
+    assert_eq!(2, expectations.list().len());
+
+    assert!(expectations.remove(first));
+    assert!(!expectations.remove(first));
+    assert!(expectations.get(first).is_none());
+    assert_eq!(1, expectations.clear());
+    assert!(expectations.list().is_empty());
+}
+
+#[test]
+fn unbounded_window_test() {
+    let peer = "127.0.0.1:40000".parse().unwrap();
+    let expectations = Expectations::default();
+    let ids = [u64::MAX, i64::MAX as u64, 1 << 62].map(|within| {
+        expectations
+            .add(ExpectationSpec {
+                within: Some(within),
+                ..spec(None, Some("NULL"), None, 2)
+            })
+            .unwrap()
+            .id
+    });
+
+    expectations.record("tcp", &peer, &message("1234", "NULL", None));
+    for id in ids {
+        let report = expectations.get(id).unwrap();
+        assert_eq!(ExpectationStatus::Pending, report.status);
+        assert_eq!(1, report.matched);
+    }
+}
@@ -0,0 +1,99 @@
+use common::capture::Transport;
+use common::dc09::DC09Message;
+use common::keys::{CachedKeyProvider, KeyProvider, KeyQuery, SecretKey};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use time::OffsetDateTime;
+
+use crate::server::{ResponseMode, ResponseModes, SharedServerConfig};
+use crate::utils::get_response_mode;
+
+#[cfg(test)]
+#[path = "./handler.tests.rs"]
+mod handler_tests;
+
+/// Future returned by the [`EventHandler`], boxed so the handler can be used as a trait object.
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Decoded message passed to the [`EventHandler`].
+#[derive(Debug, Clone, Copy)]
+pub struct MessageEvent<'a> {
+    pub transport: Transport,
+    pub peer: SocketAddr,
+    /// Frame as received, possibly encrypted.
+    pub frame: &'a str,
+    pub message: &'a DC09Message,
+}
+
+/// Decides how the receiver responds to decoded messages.
+pub trait EventHandler: Send + Sync {
+    /// Called once per decoded message, returns the response mode or `None` to drop the message without a response.\
+    /// **Note** that the response is sent after the returned future completes, so the handler can delay it.
+    fn on_message<'a>(&'a self, event: MessageEvent<'a>) -> BoxFuture<'a, Option<ResponseMode>>;
+}
+
+/// Key provider using keys of the server configuration, falling back to the key sources for accounts without a key.
+pub struct ConfigKeys {
+    config: SharedServerConfig,
+    sources: Option<Arc<CachedKeyProvider>>,
+}
+
+impl ConfigKeys {
+    /// Creates new [`ConfigKeys`] instance.
+    pub fn new(config: SharedServerConfig) -> Self {
+        Self { config, sources: None }
+    }
+
+    /// Sets key sources consulted for accounts without a key in the registry.
+    pub fn with_sources(mut self, sources: Option<Arc<CachedKeyProvider>>) -> Self {
+        self.sources = sources;
+        self
+    }
+}
+
+impl KeyProvider for ConfigKeys {
+    /// Returns the current key of the panel, see [`ConfigKeys::keys`].
+    fn key(&self, query: &KeyQuery) -> Option<SecretKey> {
+        self.keys(query).into_iter().next()
+    }
+
+    /// Returns keys from the account registry, the key sources or the default key, in this order.\
+    /// **Note** that for a rotated registry key the previous key follows the current one within the grace window.
+    fn keys(&self, query: &KeyQuery) -> Vec<SecretKey> {
+        let config = self.config.load();
+        let active = config
+            .find_account(query)
+            .map(|(_, a)| a.active_keys(OffsetDateTime::now_utc(), config.key_grace));
+        if let Some((Some(current), previous)) = active {
+            return [Some(current), previous].into_iter().flatten().cloned().collect();
+        }
+
+        if let Some(key) = self.sources.as_ref().and_then(|s| s.key(query)) {
+            return vec![key];
+        }
+
+        config.get_key(query).cloned().into_iter().collect()
+    }
+}
+
+/// Event handler responding according to the response modes, account settings and the unknown account policy.
+pub struct DefaultEventHandler {
+    config: SharedServerConfig,
+    modes: Arc<ResponseModes>,
+}
+
+impl DefaultEventHandler {
+    /// Creates new [`DefaultEventHandler`] instance.
+    pub fn new(config: SharedServerConfig, modes: Arc<ResponseModes>) -> Self {
+        Self { config, modes }
+    }
+}
+
+impl EventHandler for DefaultEventHandler {
+    fn on_message<'a>(&'a self, event: MessageEvent<'a>) -> BoxFuture<'a, Option<ResponseMode>> {
+        let transport = event.transport.to_string();
+        let mode = get_response_mode(&transport, event.message, &self.config.load(), &self.modes);
+        Box::pin(std::future::ready(mode))
+    }
+}
@@ -0,0 +1,94 @@
+use super::*;
+use crate::server::{AccountConfig, KeySource, ServerConfig};
+use arc_swap::ArcSwap;
+use std::collections::HashMap;
+
+const KEY: &str = "0123456789abcdef";
+const SOURCE_KEY: &str = "1111111111111111";
+const ACCOUNT_KEY: &str = "2222222222222222";
+
+fn config(key: Option<&str>) -> SharedServerConfig {
+    let keys = key.map(|k| HashMap::from([(0, SecretKey::from(k))])).unwrap_or_default();
+    Arc::new(ArcSwap::from_pointee(ServerConfig::new(&[], Arc::new(keys))))
+}
+
+fn event<'a>(message: &'a DC09Message) -> MessageEvent<'a> {
+    MessageEvent {
+        transport: Transport::Tcp,
+        peer: "127.0.0.1:40000".parse().unwrap(),
+        frame: "",
+        message,
+    }
+}
+
+#[tokio::test]
+async fn config_keys_test() {
+    let query = KeyQuery::new("1234", Some("R1"), Some("L2"));
+    assert_eq!(Some(SecretKey::from(KEY)), ConfigKeys::new(config(Some(KEY))).key(&query));
+    assert_eq!(None, ConfigKeys::new(config(None)).key(&query));
+
+    let path = std::env::temp_dir().join(format!("receiver-keys-{}.txt", std::process::id()));
+    std::fs::write(&path, format!("R1 L2 1234 {SOURCE_KEY}\n")).unwrap();
+    let sources = CachedKeyProvider::load(vec![KeySource::File(path.clone())]).await.unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let keys = ConfigKeys::new(config(Some(KEY))).with_sources(Some(Arc::new(sources)));
+    assert_eq!(Some(SecretKey::from(SOURCE_KEY)), keys.key(&query));
+    assert_eq!(Some(SecretKey::from(KEY)), keys.key(&KeyQuery::new("1234", None, None)));
+
+    let config = config(None);
+    config.rcu(|c| {
+        let mut c = ServerConfig::clone(c);
+        c.set_account(
+            "1234".parse().unwrap(),
+            AccountConfig::new(Some(SecretKey::from(ACCOUNT_KEY))),
+        );
+        c
+    });
+    let keys = ConfigKeys::new(config).with_sources(keys.sources);
+    assert_eq!(Some(SecretKey::from(ACCOUNT_KEY)), keys.key(&query));
+}
+
+#[test]
+fn config_keys_selectors_test() {
+    let config = config(Some(KEY));
+    config.rcu(|c| {
+        let mut c = ServerConfig::clone(c);
+        c.set_account("R1 *".parse().unwrap(), AccountConfig::new(Some(SecretKey::from(SOURCE_KEY))));
+        c.set_account(
+            "L2 1234".parse().unwrap(),
+            AccountConfig::new(Some(SecretKey::from(ACCOUNT_KEY))),
+        );
+        c
+    });
+
+    let keys = ConfigKeys::new(config);
+    assert_eq!(
+        Some(SecretKey::from(ACCOUNT_KEY)),
+        keys.key(&KeyQuery::new("1234", Some("R1"), Some("L2")))
+    );
+    assert_eq!(
+        Some(SecretKey::from(SOURCE_KEY)),
+        keys.key(&KeyQuery::new("1234", Some("R1"), Some("L3")))
+    );
+    assert_eq!(
+        Some(SecretKey::from(SOURCE_KEY)),
+        keys.key(&KeyQuery::new("5678", Some("R1"), None))
+    );
+    assert_eq!(
+        Some(SecretKey::from(KEY)),
+        keys.key(&KeyQuery::new("5678", Some("R2"), Some("L2")))
+    );
+}
+
+#[tokio::test]
+async fn default_event_handler_test() {
+    let modes = Arc::new(ResponseModes::new(ResponseMode::Nak, ResponseMode::Ack));
+    let handler = DefaultEventHandler::new(config(None), modes);
+
+    let message = DC09Message::new("SIA-DCS".to_owned(), "1234".to_owned(), 1, None);
+    assert_eq!(Some(ResponseMode::Nak), handler.on_message(event(&message)).await);
+
+    let heartbeat = DC09Message::new("NULL".to_owned(), "1234".to_owned(), 1, None);
+    assert_eq!(Some(ResponseMode::Ack), handler.on_message(event(&heartbeat)).await);
+}
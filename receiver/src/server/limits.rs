@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::server::{ConnectionLimits, LimitPolicy};
+
+#[cfg(test)]
+#[path = "./limits.tests.rs"]
+mod limits_tests;
+
+/// Reason for rejecting a new connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectReason {
+    GlobalLimit,
+    IpLimit,
+    Shutdown,
+}
+
+impl RejectReason {
+    /// Returns static str for the reject reason.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            RejectReason::GlobalLimit => "GlobalLimit",
+            RejectReason::IpLimit => "IpLimit",
+            RejectReason::Shutdown => "Shutdown",
+        }
+    }
+}
+
+/// Holds connection slots for the lifetime of a connection.
+pub struct ConnectionPermit {
+    _global: Option<OwnedSemaphorePermit>,
+    _ip: Option<OwnedSemaphorePermit>,
+}
+
+/// Enforces global and per-IP connection limits.
+pub struct ConnectionLimiter {
+    policy: LimitPolicy,
+    global: Option<Arc<Semaphore>>,
+    per_ip_limit: usize,
+    per_ip: Mutex<HashMap<IpAddr, Arc<Semaphore>>>,
+}
+
+impl ConnectionLimiter {
+    /// Creates new [`ConnectionLimiter`] instance.
+    pub fn new(limits: &ConnectionLimits) -> Self {
+        Self {
+            policy: limits.policy,
+            global: (limits.max_connections > 0).then(|| Arc::new(Semaphore::new(limits.max_connections))),
+            per_ip_limit: limits.max_connections_per_ip,
+            per_ip: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Acquires connection slots for the specified `ip`.\
+    /// **Note** that with [`LimitPolicy::Queue`] it waits until slots are available.
+    pub async fn acquire(&self, ip: IpAddr) -> Result<ConnectionPermit, RejectReason> {
+        let ip_permit = match self.ip_semaphore(ip) {
+            Some(semaphore) => Some(self.acquire_from(semaphore, RejectReason::IpLimit).await?),
+            None => None,
+        };
+
+        let global_permit = match &self.global {
+            Some(semaphore) => Some(self.acquire_from(Arc::clone(semaphore), RejectReason::GlobalLimit).await?),
+            None => None,
+        };
+
+        Ok(ConnectionPermit {
+            _global: global_permit,
+            _ip: ip_permit,
+        })
+    }
+
+    async fn acquire_from(&self, semaphore: Arc<Semaphore>, reason: RejectReason) -> Result<OwnedSemaphorePermit, RejectReason> {
+        match self.policy {
+            LimitPolicy::Reject => semaphore.try_acquire_owned().map_err(|_| reason),
+            LimitPolicy::Queue => semaphore.acquire_owned().await.map_err(|_| reason),
+        }
+    }
+
+    fn ip_semaphore(&self, ip: IpAddr) -> Option<Arc<Semaphore>> {
+        if self.per_ip_limit == 0 {
+            return None;
+        }
+
+        let mut per_ip = self.per_ip.lock().expect("lock is not poisoned");
+        if per_ip.len() > 1_000 {
+            per_ip.retain(|_, s| Arc::strong_count(s) > 1 || s.available_permits() < self.per_ip_limit);
+        }
+
+        let semaphore = per_ip
+            .entry(ip)
+            .or_insert_with(|| Arc::new(Semaphore::new(self.per_ip_limit)));
+        Some(Arc::clone(semaphore))
+    }
+}
@@ -0,0 +1,46 @@
+use super::*;
+use std::time::Duration;
+
+fn limiter(max_connections: usize, max_connections_per_ip: usize, policy: LimitPolicy) -> ConnectionLimiter {
+    ConnectionLimiter::new(&ConnectionLimits {
+        max_connections,
+        max_connections_per_ip,
+        policy,
+        ..ConnectionLimits::default()
+    })
+}
+
+fn ip(last: u8) -> IpAddr {
+    IpAddr::from([127, 0, 0, last])
+}
+
+#[tokio::test]
+async fn unlimited_test() {
+    let limiter = limiter(0, 0, LimitPolicy::Reject);
+    let permits = [limiter.acquire(ip(1)).await, limiter.acquire(ip(1)).await];
+    assert!(permits.iter().all(Result::is_ok));
+}
+
+#[tokio::test]
+async fn reject_policy_test() {
+    let limiter = limiter(2, 1, LimitPolicy::Reject);
+    let first = limiter.acquire(ip(1)).await.unwrap();
+    assert_eq!(Some(RejectReason::IpLimit), limiter.acquire(ip(1)).await.err());
+
+    let _second = limiter.acquire(ip(2)).await.unwrap();
+    assert_eq!(Some(RejectReason::GlobalLimit), limiter.acquire(ip(3)).await.err());
+
+    drop(first);
+    assert!(limiter.acquire(ip(1)).await.is_ok());
+}
+
+#[tokio::test]
+async fn queue_policy_test() {
+    let limiter = limiter(1, 0, LimitPolicy::Queue);
+    let first = limiter.acquire(ip(1)).await.unwrap();
+    let waiting = tokio::time::timeout(Duration::from_millis(50), limiter.acquire(ip(2))).await;
+    assert!(waiting.is_err());
+
+    let (second, ()) = tokio::join!(limiter.acquire(ip(2)), async move { drop(first) });
+    assert!(second.is_ok());
+}
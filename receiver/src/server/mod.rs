@@ -1,22 +1,68 @@
-pub use self::config::{ResponseMode, ResponseModes, ServerConfig};
+pub use self::config::{
+    AccountConfig, AccountLabels, ConnectionLimits, DEFAULT_ACCOUNT_LABEL_LIMIT, DEFAULT_KEY_GRACE, DEFAULT_MAX_FRAME_SIZE,
+    DEFAULT_SHUTDOWN_TIMEOUT, DEFAULT_UDP_QUEUE_SIZE, DEFAULT_UDP_RETRANSMIT_CACHE_SIZE, DEFAULT_UDP_RETRANSMIT_TTL,
+    DEFAULT_UDP_WORKERS, KeyState, KeyStatus, KeyVersion, LimitPolicy, MIN_MAX_FRAME_SIZE, QueuePolicy, ResponseMode,
+    ResponseModes, ServerConfig, SharedServerConfig, UdpPipeline, UnknownAccountPolicy,
+};
+pub use self::decode::decode_frame;
+pub use self::delay::ResponseDelays;
+pub use self::expectations::{ExpectationReport, ExpectationSpec, Expectations};
+pub use self::handler::{BoxFuture, ConfigKeys, DefaultEventHandler, EventHandler, MessageEvent};
+pub use self::retransmit::ResponseCache;
+pub use self::state::ServerState;
+pub use self::supervision::Supervision;
 pub use self::tcp::TcpServer;
+pub use self::trial::{TrialMatch, TrialMatches};
 pub use self::udp::UdpServer;
+pub use common::keys::{CachedKeyProvider, KeyProvider, KeyQuery, KeySource, SecretKey};
 
 mod config;
+mod decode;
+mod delay;
+mod expectations;
+mod handler;
+mod limits;
+mod retransmit;
+mod state;
+mod supervision;
 mod tcp;
+mod trial;
 mod udp;
 
 use anyhow::Result;
+use std::time::Duration;
 use tokio::net::ToSocketAddrs;
-
-use crate::metrics::AppState;
+use tokio::task::JoinHandle;
 
 /// Represents type that can be treated as a server.
 pub trait Server: Sized {
     /// Creates new [`Server`] instance.\
-    /// **Note** that `key` can be provided to decrypt encrypted DC09 messages.
-    async fn new(address: impl ToSocketAddrs, config: ServerConfig, state: AppState) -> Result<Self>;
+    /// **Note** that keys to decrypt DC09 messages are taken from the [`KeyProvider`] of the `state`.
+    ///
+    /// [`KeyProvider`]: crate::server::KeyProvider
+    fn new(
+        address: impl ToSocketAddrs + Send,
+        config: SharedServerConfig,
+        state: ServerState,
+    ) -> impl Future<Output = Result<Self>> + Send;
+
+    /// Runs the server until the shutdown token of the [`ServerState`] is cancelled.
+    fn run(&mut self) -> impl Future<Output = Result<()>> + Send;
+}
+
+/// Waits for all `tasks` to finish, aborting the ones still running after `timeout`.
+async fn wait_for_tasks(tasks: Vec<JoinHandle<()>>, timeout: Duration) {
+    let abort_handles = tasks.iter().map(JoinHandle::abort_handle).collect::<Vec<_>>();
+    let wait = async {
+        for task in tasks {
+            let _ = task.await;
+        }
+    };
 
-    /// Runs the server.
-    async fn run(&mut self) -> Result<()>;
+    if tokio::time::timeout(timeout, wait).await.is_err() {
+        log::warn!("shutdown deadline of {timeout:?} exceeded, aborting remaining tasks");
+        for handle in abort_handles {
+            handle.abort();
+        }
+    }
 }
@@ -0,0 +1,105 @@
+use common::dc09::parse_dc09_account_and_sequence;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::server::UdpPipeline;
+
+#[cfg(test)]
+#[path = "./retransmit.tests.rs"]
+mod retransmit_tests;
+
+/// Identifies an exact retransmission of a DC09 frame from the same peer.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FrameKey {
+    pub peer: SocketAddr,
+    pub account: String,
+    pub sequence: u16,
+    frame: u64,
+}
+
+impl FrameKey {
+    /// Creates new [`FrameKey`] instance, `None` if the frame header cannot be parsed.
+    pub fn new(peer: SocketAddr, frame: &str) -> Option<Self> {
+        let (account, sequence) = parse_dc09_account_and_sequence(frame).ok()?;
+        let mut hasher = DefaultHasher::new();
+        frame.trim().hash(&mut hasher);
+
+        Some(Self {
+            peer,
+            account,
+            sequence,
+            frame: hasher.finish(),
+        })
+    }
+}
+
+/// Short-lived cache of responses sent for received UDP frames.\
+/// **Note** that entries are evicted in insertion order once expired or when the cache is full.
+pub struct ResponseCache {
+    ttl: Duration,
+    capacity: usize,
+    entries: Mutex<CacheEntries>,
+}
+
+#[derive(Default)]
+struct CacheEntries {
+    responses: HashMap<FrameKey, (Instant, String)>,
+    order: VecDeque<(Instant, FrameKey)>,
+}
+
+impl ResponseCache {
+    /// Creates new [`ResponseCache`] instance.
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            capacity,
+            entries: Mutex::new(CacheEntries::default()),
+        }
+    }
+
+    /// Creates new [`ResponseCache`] instance for the UDP pipeline, `None` if the cache is disabled.
+    pub fn for_pipeline(pipeline: &UdpPipeline) -> Option<Self> {
+        pipeline
+            .retransmit_ttl
+            .map(|ttl| Self::new(ttl, pipeline.retransmit_cache_size))
+    }
+
+    /// Returns response previously sent for the frame, if it has not expired yet.
+    pub fn get(&self, key: &FrameKey) -> Option<String> {
+        let entries = self.entries.lock().expect("lock is not poisoned");
+        entries
+            .responses
+            .get(key)
+            .filter(|(inserted, _)| inserted.elapsed() < self.ttl)
+            .map(|(_, response)| response.clone())
+    }
+
+    /// Stores response sent for the frame, evicting expired and the oldest entries.
+    pub fn insert(&self, key: FrameKey, response: String) {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().expect("lock is not poisoned");
+        while let Some((inserted, _)) = entries.order.front()
+            && (now.duration_since(*inserted) >= self.ttl || entries.order.len() >= self.capacity)
+        {
+            let (inserted, key) = entries.order.pop_front().expect("front entry exists");
+            if entries.responses.get(&key).is_some_and(|(time, _)| *time == inserted) {
+                entries.responses.remove(&key);
+            }
+        }
+
+        entries.order.push_back((now, key.clone()));
+        entries.responses.insert(key, (now, response));
+    }
+
+    /// Removes all cached responses and returns their number.
+    pub fn clear(&self) -> usize {
+        let mut entries = self.entries.lock().expect("lock is not poisoned");
+        let cleared = entries.responses.len();
+        *entries = CacheEntries::default();
+        cleared
+    }
+}
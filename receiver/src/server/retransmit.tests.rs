@@ -0,0 +1,84 @@
+use super::*;
+use common::dc09::DC09Message;
+
+const PEER: &str = "127.0.0.1:5000";
+
+fn frame(account: &str, sequence: u16) -> FrameKey {
+    let message = DC09Message::new("SIA-DCS".to_owned(), account.to_owned(), sequence, None);
+    FrameKey::new(PEER.parse().unwrap(), &message.to_string()).unwrap()
+}
+
+#[test]
+fn frame_key_test() {
+    let message = DC09Message::new("SIA-DCS".to_owned(), "1234".to_owned(), 7, None).to_string();
+    let key = FrameKey::new(PEER.parse().unwrap(), &message).unwrap();
+    assert_eq!(("1234", 7), (key.account.as_str(), key.sequence));
+    assert_eq!(
+        Some(&key),
+        FrameKey::new(PEER.parse().unwrap(), &format!("{message}  ")).as_ref()
+    );
+    assert_ne!(Some(key), FrameKey::new("127.0.0.1:5001".parse().unwrap(), &message));
+
+    let other = DC09Message::new("NULL".to_owned(), "1234".to_owned(), 7, None).to_string();
+    assert_ne!(
+        FrameKey::new(PEER.parse().unwrap(), &message),
+        FrameKey::new(PEER.parse().unwrap(), &other)
+    );
+    assert_eq!(None, FrameKey::new(PEER.parse().unwrap(), "garbage"));
+}
+
+#[test]
+fn insert_get_test() {
+    let cache = ResponseCache::new(Duration::from_secs(60), 10);
+    assert_eq!(None, cache.get(&frame("1234", 1)));
+
+    cache.insert(frame("1234", 1), "ACK 1".to_owned());
+    cache.insert(frame("1234", 2), "ACK 2".to_owned());
+    assert_eq!(Some("ACK 1".to_owned()), cache.get(&frame("1234", 1)));
+    assert_eq!(Some("ACK 2".to_owned()), cache.get(&frame("1234", 2)));
+    assert_eq!(None, cache.get(&frame("5678", 1)));
+
+    cache.insert(frame("1234", 1), "NAK 1".to_owned());
+    assert_eq!(Some("NAK 1".to_owned()), cache.get(&frame("1234", 1)));
+}
+
+#[test]
+fn expire_test() {
+    let cache = ResponseCache::new(Duration::from_millis(50), 10);
+    cache.insert(frame("1234", 1), "ACK 1".to_owned());
+    std::thread::sleep(Duration::from_millis(60));
+    assert_eq!(None, cache.get(&frame("1234", 1)));
+
+    // Expired entries are evicted by the next insert.
+    cache.insert(frame("1234", 2), "ACK 2".to_owned());
+    assert_eq!(1, cache.clear());
+}
+
+#[test]
+fn evict_test() {
+    let cache = ResponseCache::new(Duration::from_secs(60), 2);
+    cache.insert(frame("1234", 1), "ACK 1".to_owned());
+    cache.insert(frame("1234", 2), "ACK 2".to_owned());
+    cache.insert(frame("1234", 3), "ACK 3".to_owned());
+
+    assert_eq!(None, cache.get(&frame("1234", 1)));
+    assert_eq!(Some("ACK 2".to_owned()), cache.get(&frame("1234", 2)));
+    assert_eq!(Some("ACK 3".to_owned()), cache.get(&frame("1234", 3)));
+
+    // Replacing an entry keeps it until its newest insertion is evicted.
+    cache.insert(frame("1234", 2), "ACK 2".to_owned());
+    cache.insert(frame("1234", 4), "ACK 4".to_owned());
+    assert_eq!(Some("ACK 2".to_owned()), cache.get(&frame("1234", 2)));
+    assert_eq!(None, cache.get(&frame("1234", 3)));
+}
+
+#[test]
+fn clear_test() {
+    let cache = ResponseCache::new(Duration::from_secs(60), 10);
+    cache.insert(frame("1234", 1), "ACK 1".to_owned());
+    cache.insert(frame("5678", 1), "ACK 1".to_owned());
+
+    assert_eq!(2, cache.clear());
+    assert_eq!(None, cache.get(&frame("1234", 1)));
+    assert_eq!(0, cache.clear());
+}
@@ -0,0 +1,81 @@
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use tokio_util::sync::CancellationToken;
+
+use crate::capture::Capture;
+use crate::metrics::AccountLabeler;
+use crate::server::{
+    ConfigKeys, DefaultEventHandler, EventHandler, Expectations, KeyProvider, ResponseCache, ResponseDelays, ResponseModes,
+    SharedServerConfig, Supervision, TrialMatches,
+};
+
+/// Runtime state shared by the TCP and UDP servers.
+#[derive(Clone)]
+pub struct ServerState {
+    pub tcp_ready: Arc<AtomicBool>,
+    pub udp_ready: Arc<AtomicBool>,
+    pub response_modes: Arc<ResponseModes>,
+    pub response_delays: Arc<ResponseDelays>,
+    pub supervision: Arc<Supervision>,
+    pub expectations: Arc<Expectations>,
+    pub retransmit_cache: Option<Arc<ResponseCache>>,
+    pub account_labels: Arc<AccountLabeler>,
+    pub trial_matches: Arc<TrialMatches>,
+    pub handler: Arc<dyn EventHandler>,
+    pub keys: Arc<dyn KeyProvider>,
+    pub shutdown: CancellationToken,
+    pub capture: Option<Capture>,
+}
+
+impl ServerState {
+    /// Creates new [`ServerState`] instance responding with the `response_modes` and using keys from the `config`.\
+    /// **Note** that the UDP retransmission cache is created from the UDP pipeline settings of the `config`.
+    pub fn new(config: &SharedServerConfig, response_modes: ResponseModes) -> Self {
+        let response_modes = Arc::new(response_modes);
+        Self {
+            tcp_ready: Arc::new(AtomicBool::new(false)),
+            udp_ready: Arc::new(AtomicBool::new(false)),
+            handler: Arc::new(DefaultEventHandler::new(Arc::clone(config), Arc::clone(&response_modes))),
+            response_modes,
+            response_delays: Arc::new(ResponseDelays::default()),
+            supervision: Arc::new(Supervision::default()),
+            expectations: Arc::new(Expectations::default()),
+            retransmit_cache: ResponseCache::for_pipeline(&config.load().udp).map(Arc::new),
+            account_labels: Arc::new(AccountLabeler::default()),
+            trial_matches: Arc::new(TrialMatches::default()),
+            keys: Arc::new(ConfigKeys::new(Arc::clone(config))),
+            shutdown: CancellationToken::new(),
+            capture: None,
+        }
+    }
+
+    /// Sets response delays.
+    pub fn with_response_delays(mut self, delays: ResponseDelays) -> Self {
+        self.response_delays = Arc::new(delays);
+        self
+    }
+
+    /// Sets handler deciding responses to received messages.
+    pub fn with_handler(mut self, handler: Arc<dyn EventHandler>) -> Self {
+        self.handler = handler;
+        self
+    }
+
+    /// Sets provider of keys to decrypt messages and encrypt responses.
+    pub fn with_keys(mut self, keys: Arc<dyn KeyProvider>) -> Self {
+        self.keys = keys;
+        self
+    }
+
+    /// Sets token cancelled to shut the servers down.
+    pub fn with_shutdown(mut self, shutdown: CancellationToken) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// Sets capture recording all inbound and outbound frames.
+    pub fn with_capture(mut self, capture: Option<Capture>) -> Self {
+        self.capture = capture;
+        self
+    }
+}
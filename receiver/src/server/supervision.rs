@@ -0,0 +1,43 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+use time::OffsetDateTime;
+
+/// Tracks time of the last message received per account.
+#[derive(Debug, Default)]
+pub struct Supervision {
+    last_seen: RwLock<HashMap<String, OffsetDateTime>>,
+}
+
+impl Supervision {
+    /// Records that a message was just received from the `account`.
+    pub fn record(&self, account: &str) {
+        let now = OffsetDateTime::now_utc();
+        let mut last_seen = self.last_seen.write().expect("lock is not poisoned");
+        match last_seen.get_mut(account) {
+            Some(time) => *time = now,
+            None => {
+                last_seen.insert(account.to_owned(), now);
+            },
+        }
+    }
+
+    /// Returns time of the last message received from the `account`.
+    pub fn last_seen(&self, account: &str) -> Option<OffsetDateTime> {
+        self.last_seen.read().expect("lock is not poisoned").get(account).copied()
+    }
+
+    /// Returns `true` if the `account` did not send any message within the supervision `interval` (in seconds).\
+    /// **Note** that accounts that never sent a message are not considered expired.
+    pub fn is_expired(&self, account: &str, interval: u32) -> bool {
+        self.last_seen(account)
+            .is_some_and(|time| (OffsetDateTime::now_utc() - time).whole_seconds() > i64::from(interval))
+    }
+
+    /// Forgets all recorded messages and returns the number of accounts that were tracked.
+    pub fn clear(&self) -> usize {
+        let mut last_seen = self.last_seen.write().expect("lock is not poisoned");
+        let cleared = last_seen.len();
+        last_seen.clear();
+        cleared
+    }
+}
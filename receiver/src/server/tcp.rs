@@ -1,17 +1,26 @@
-use anyhow::Result;
-use common::dc09::DC09Message;
+use anyhow::{Result, anyhow};
+use common::capture::{Direction, Transport};
+use common::dc09::DC09Error;
 use std::sync::atomic::Ordering;
+use std::time::Instant;
 use std::{net::SocketAddr, sync::Arc};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
 use tokio::task::JoinHandle;
 
-use crate::metrics::AppState;
-use crate::server::{ResponseMode, ResponseModes};
-use crate::utils::{build_response_message, get_received_message};
+use crate::server::limits::{ConnectionLimiter, RejectReason};
+use crate::server::{ConnectionLimits, ResponseMode, SharedServerConfig};
+use crate::server::{KeyQuery, MessageEvent, Server, ServerConfig, ServerState, decode_frame, wait_for_tasks};
+use crate::utils::{build_response_message, observe_response_delay};
+use crate::utils::{capture_frame, increase_rejected_connections, increase_timed_out_connections};
 use crate::utils::{decrease_active_connections, increase_active_connections, increase_total_connections};
+use crate::utils::{increase_responses_sent, observe_processing_duration};
+use crate::utils::{log_invalid_message, log_received_message, log_sent_response};
 use crate::utils::{process_invalid_message_metrics, process_valid_message_metrics};
-use crate::{Server, ServerConfig};
+
+#[cfg(test)]
+#[path = "./tcp.tests.rs"]
+mod tcp_tests;
 
 static TRANSPORT_NAME: &str = "TCP";
 
@@ -19,19 +28,31 @@ static TRANSPORT_NAME: &str = "TCP";
 pub struct TcpServer {
     listener: TcpListener,
     connections: Vec<JoinHandle<()>>,
-    config: Arc<ServerConfig>,
-    state: AppState,
+    config: SharedServerConfig,
+    limiter: Arc<ConnectionLimiter>,
+    state: ServerState,
+}
+
+impl TcpServer {
+    /// Returns address the server is bound to, useful when it was bound to port `0`.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
 }
 
 impl Server for TcpServer {
-    /// Creates new [`TcpServer`] instance.\
-    /// **Note** that `key` can be provided to decrypt encrypted DC09 messages.
-    async fn new(address: impl ToSocketAddrs, config: ServerConfig, state: AppState) -> Result<Self> {
+    /// Creates new [`TcpServer`] instance, returns an error if the connection limits are invalid.\
+    /// **Note** that keys to decrypt DC09 messages are taken from the [`KeyProvider`] of the `state`.
+    ///
+    /// [`KeyProvider`]: crate::server::KeyProvider
+    async fn new(address: impl ToSocketAddrs + Send, config: SharedServerConfig, state: ServerState) -> Result<Self> {
+        config.load().limits.validate().map_err(|e| anyhow!("limits.{e}"))?;
         let listener = TcpListener::bind(address).await?;
         Ok(Self {
             listener,
             connections: Vec::new(),
-            config: Arc::new(config),
+            limiter: Arc::new(ConnectionLimiter::new(&config.load().limits)),
+            config,
             state,
         })
     }
@@ -41,13 +62,19 @@ impl Server for TcpServer {
         self.state.tcp_ready.store(true, Ordering::Relaxed);
 
         loop {
-            match self.listener.accept().await {
+            let accepted = tokio::select! {
+                accepted = self.listener.accept() => accepted,
+                () = self.state.shutdown.cancelled() => break,
+            };
+
+            match accepted {
                 Ok((stream, addr)) => {
                     let task = tokio::spawn(process_connection(
                         stream,
                         addr,
                         Arc::clone(&self.config),
-                        Arc::clone(&self.state.response_modes),
+                        Arc::clone(&self.limiter),
+                        self.state.clone(),
                     ));
                     self.connections.push(task);
                 },
@@ -58,48 +85,160 @@ impl Server for TcpServer {
                 self.connections.retain(|t| !t.is_finished());
             }
         }
+
+        self.state.tcp_ready.store(false, Ordering::Relaxed);
+        self.connections.retain(|t| !t.is_finished());
+        log::info!("tcp: stopped accepting, waiting for {} connections", self.connections.len());
+        let timeout = self.config.load().shutdown_timeout;
+        wait_for_tasks(std::mem::take(&mut self.connections), timeout).await;
+
+        Ok(())
     }
 }
 
-async fn process_connection(mut socket: TcpStream, addr: SocketAddr, config: Arc<ServerConfig>, mode: Arc<ResponseModes>) {
+async fn process_connection(
+    mut socket: TcpStream,
+    addr: SocketAddr,
+    config: SharedServerConfig,
+    limiter: Arc<ConnectionLimiter>,
+    state: ServerState,
+) {
     log::debug!("accepted new connection from {addr}");
     increase_total_connections(TRANSPORT_NAME);
+
+    let permit = tokio::select! {
+        permit = limiter.acquire(addr.ip()) => permit,
+        () = state.shutdown.cancelled() => Err(RejectReason::Shutdown),
+    };
+
+    let _permit = match permit {
+        Ok(permit) => permit,
+        Err(reason) => {
+            log::warn!("connection from {addr} rejected: {}", reason.reason());
+            increase_rejected_connections(TRANSPORT_NAME, reason.reason());
+            let _ = socket.shutdown().await;
+            return;
+        },
+    };
+
     increase_active_connections();
+    read_frames(&mut socket, &addr, &config, &state).await;
+    decrease_active_connections();
+
+    match socket.shutdown().await {
+        Ok(()) => log::debug!("connection closed for {addr}"),
+        Err(e) => log::warn!("error while socket shutdown: {e}"),
+    }
+}
 
-    let mut buffer = [0; 2048];
+/// Reads DC09 frames (terminated by `\r` or complete by their declared length) from the socket until the connection is
+/// closed or fails.
+async fn read_frames(socket: &mut TcpStream, addr: &SocketAddr, config: &SharedServerConfig, state: &ServerState) {
+    let ConnectionLimits {
+        idle_timeout,
+        max_frame_size,
+        ..
+    } = config.load().limits;
+    let mut buffer = vec![0; max_frame_size];
+    let mut len = 0;
     loop {
-        match socket.read(&mut buffer).await {
+        let read = async {
+            tokio::select! {
+                read = socket.read(&mut buffer[len..]) => Some(read),
+                () = state.shutdown.cancelled() => None,
+            }
+        };
+        let read = match idle_timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, read).await {
+                Ok(read) => read,
+                Err(_) => {
+                    log::warn!("connection from {addr} idle for {timeout:?}, closing");
+                    increase_timed_out_connections(TRANSPORT_NAME);
+                    return;
+                },
+            },
+            None => read.await,
+        };
+
+        let Some(read) = read else {
+            log::debug!("closing connection from {addr} due to shutdown");
+            return;
+        };
+
+        let read_at = Instant::now();
+        match read {
             Ok(0) => {
-                match socket.shutdown().await {
-                    Ok(()) => log::debug!("connection closed by {addr}"),
-                    Err(e) => log::warn!("error while socket shutdown: {e}"),
+                log::debug!("connection closed by {addr}");
+                // The peer may close its side right after an unterminated frame and still wait for the response.
+                if len > 0 {
+                    process_frame(socket, addr, &buffer[..len], false, read_at, config, state).await;
                 }
-
-                decrease_active_connections();
                 return;
             },
-            Ok(n) => match str::from_utf8(&buffer[..n]) {
-                Ok(msg) => {
-                    if !process_message(&mut socket, &addr, msg, &config, mode.message(), mode.heartbeat()).await {
-                        break;
-                    }
-                },
-                Err(err) => {
-                    log::error!("received invalid UTF-8 sequence: {err}");
-                    break;
-                },
-            },
+            Ok(n) => len += n,
             Err(e) => {
                 log::error!("failed to read from socket: {e}");
-                break;
+                return;
             },
         }
+
+        while let Some((end, terminated)) = split_frame(&buffer[..len]) {
+            if &buffer[..end] != b"\r" && !process_frame(socket, addr, &buffer[..end], terminated, read_at, config, state).await {
+                return;
+            }
+
+            buffer.copy_within(end..len, 0);
+            len -= end;
+        }
+
+        if len == buffer.len() {
+            let received_message = String::from_utf8_lossy(&buffer[..len]);
+            log_invalid_message(TRANSPORT_NAME, addr, &format!("{len} bytes"), &DC09Error::FrameTooLarge);
+            process_invalid_message_metrics(TRANSPORT_NAME, &received_message, &DC09Error::FrameTooLarge);
+            return;
+        }
     }
+}
 
-    decrease_active_connections();
-    match socket.shutdown().await {
-        Ok(()) => log::debug!("connection closed for {addr}"),
-        Err(e) => log::warn!("error while socket shutdown: {e}"),
+/// Returns length of the first complete frame in the `buffer` and `true` if it is terminated by `\r`.\
+/// **Note** that a frame without `\r` is complete once the buffer holds the length declared in its header.
+pub(crate) fn split_frame(buffer: &[u8]) -> Option<(usize, bool)> {
+    // [\n] + [4 (crc)] + [4 (len)] = 9
+    let declared = buffer
+        .get(5..9)
+        .filter(|_| buffer[0] == b'\n')
+        .and_then(|len| str::from_utf8(len).ok())
+        .and_then(|len| usize::from_str_radix(len, 16).ok())
+        .map(|len| len + 9);
+
+    match (buffer.iter().position(|b| *b == b'\r'), declared) {
+        // The next frame follows the declared length without the terminating `\r`.
+        (Some(end), Some(len)) if len < end && buffer[len] == b'\n' => Some((len, false)),
+        (Some(end), _) => Some((end + 1, true)),
+        (None, Some(len)) if len <= buffer.len() => Some((len, false)),
+        _ => None,
+    }
+}
+
+/// Processes a single received `frame`, returns `false` if the connection should be closed.
+async fn process_frame(
+    socket: &mut TcpStream,
+    addr: &SocketAddr,
+    frame: &[u8],
+    terminated: bool,
+    read_at: Instant,
+    config: &SharedServerConfig,
+    state: &ServerState,
+) -> bool {
+    capture_frame(state, Transport::Tcp, Direction::Inbound, *addr, frame);
+    match str::from_utf8(frame) {
+        // The parser expects the terminating `\r`, so it is added to frames completed by their declared length.
+        Ok(msg) if terminated => process_message(socket, addr, msg, read_at, &config.load(), state).await,
+        Ok(msg) => process_message(socket, addr, &format!("{msg}\r"), read_at, &config.load(), state).await,
+        Err(err) => {
+            log::error!("received invalid UTF-8 sequence: {err}");
+            false
+        },
     }
 }
 
@@ -107,27 +246,50 @@ async fn process_message(
     socket: &mut TcpStream,
     addr: &SocketAddr,
     received_message: &str,
+    read_at: Instant,
     config: &ServerConfig,
-    message_mode: ResponseMode,
-    heartbeat_mode: ResponseMode,
+    state: &ServerState,
 ) -> bool {
-    let key = config.get_key_for_message(received_message);
-    match DC09Message::try_from(received_message, key) {
-        Ok(msg) => {
-            log::info!("{} -> {}", addr, get_received_message(received_message, &msg, config.mode));
-            process_valid_message_metrics(TRANSPORT_NAME, received_message, &msg);
+    match decode_frame(received_message, config, state) {
+        Ok((msg, key)) => {
+            log_received_message(TRANSPORT_NAME, addr, received_message, &msg, config.mode);
+            state.expectations.record(TRANSPORT_NAME, addr, &msg);
+            let event = MessageEvent {
+                transport: Transport::Tcp,
+                peer: *addr,
+                frame: received_message,
+                message: &msg,
+            };
+            let mode = state.handler.on_message(event).await;
 
-            let mode = if msg.is_heartbeat() { heartbeat_mode } else { message_mode };
+            // Dropped messages are counted like messages answered with no response.
+            let query = KeyQuery::from_message(&msg);
+            let account = state.account_labels.label(&query, config);
+            process_valid_message_metrics(TRANSPORT_NAME, received_message, &msg, account);
+            state.supervision.record(&msg.account);
+            increase_responses_sent(TRANSPORT_NAME, account, mode.unwrap_or(ResponseMode::None));
+            let Some(mode) = mode else {
+                return true;
+            };
             if mode != ResponseMode::None {
-                let response = build_response_message(msg, key, mode);
-                log::info!("{} <- {}", addr, response.trim());
+                let delay = state.response_delays.sample(&msg.account, config);
+                let (account, sequence) = (msg.account.clone(), msg.sequence);
+                let response = build_response_message(msg, key.as_ref(), mode);
+                observe_response_delay(TRANSPORT_NAME, delay);
+                if !delay.is_zero() {
+                    tokio::time::sleep(delay).await;
+                }
+
+                log_sent_response(TRANSPORT_NAME, addr, &account, sequence, &response);
+                capture_frame(state, Transport::Tcp, Direction::Outbound, *addr, response.as_bytes());
                 let _ = socket.write_all(response.as_bytes()).await;
+                observe_processing_duration(TRANSPORT_NAME, read_at);
             }
 
             true
         },
         Err(e) => {
-            log::error!("{} -> {}: {}", addr, e, received_message.trim());
+            log_invalid_message(TRANSPORT_NAME, addr, received_message, &e);
             process_invalid_message_metrics(TRANSPORT_NAME, received_message, &e);
 
             false
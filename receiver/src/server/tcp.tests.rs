@@ -0,0 +1,81 @@
+use super::*;
+use crate::server::UnknownAccountPolicy;
+use crate::testing::TestReceiver;
+use common::dc09::DC09Message;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn frame() -> String {
+    DC09Message::new("SIA-DCS".to_owned(), "1234".to_owned(), 1, Some("#1234|NRR".to_owned())).to_string()
+}
+
+#[test]
+fn split_frame_test() {
+    let frame = frame();
+    let unterminated = frame.trim_end_matches('\r');
+    assert_eq!(Some((frame.len(), true)), split_frame(frame.as_bytes()));
+    assert_eq!(Some((unterminated.len(), false)), split_frame(unterminated.as_bytes()));
+    assert_eq!(None, split_frame(&unterminated.as_bytes()[..unterminated.len() - 1]));
+    assert_eq!(None, split_frame(b"\n0000"));
+    assert_eq!(None, split_frame(b""));
+    assert_eq!(Some((1, true)), split_frame(b"\r"));
+
+    let pipelined = format!("{unterminated}{frame}");
+    assert_eq!(Some((unterminated.len(), false)), split_frame(pipelined.as_bytes()));
+}
+
+async fn read_response(socket: &mut TcpStream) -> DC09Message {
+    let mut buffer = vec![0; 1024];
+    let len = tokio::time::timeout(TIMEOUT, socket.read(&mut buffer))
+        .await
+        .unwrap()
+        .unwrap();
+    DC09Message::try_from(str::from_utf8(&buffer[..len]).unwrap(), None).unwrap()
+}
+
+#[tokio::test]
+async fn unterminated_frame_test() {
+    let receiver = TestReceiver::start().await.unwrap();
+    let frame = frame();
+
+    let mut socket = TcpStream::connect(receiver.tcp_addr()).await.unwrap();
+    socket.write_all(frame.trim_end_matches('\r').as_bytes()).await.unwrap();
+    assert_eq!("ACK", read_response(&mut socket).await.token);
+
+    // Frame cut before its declared length is processed as invalid once the peer closes its side of the connection.
+    let mut socket = TcpStream::connect(receiver.tcp_addr()).await.unwrap();
+    socket.write_all(&frame.as_bytes()[..frame.len() - 2]).await.unwrap();
+    socket.shutdown().await.unwrap();
+    let read = tokio::time::timeout(TIMEOUT, socket.read(&mut [0; 16])).await.unwrap();
+    assert_eq!(0, read.unwrap());
+}
+
+#[tokio::test]
+async fn invalid_limits_test() {
+    let limits = ConnectionLimits {
+        max_frame_size: 0,
+        ..ConnectionLimits::default()
+    };
+    let config = ServerConfig::new(&[], Arc::default()).with_limits(limits);
+    let result = TestReceiver::start_with(config).await;
+    assert_eq!(
+        "limits.max_frame_size: value must be at least 64",
+        result.err().unwrap().to_string()
+    );
+}
+
+#[tokio::test]
+async fn dropped_message_metrics_test() {
+    let config = ServerConfig::new(&[], Arc::default()).with_unknown_account_policy(UnknownAccountPolicy::Drop);
+    let receiver = TestReceiver::start_with(config).await.unwrap();
+    let message = DC09Message::new("SIA-DCS".to_owned(), "7001".to_owned(), 1, None);
+    let dialler = receiver.dialler(Transport::Tcp).with_timeout(Duration::from_millis(200));
+    assert!(dialler.send(&message).await.unwrap().is_none());
+
+    let received = crate::metrics::messages_received().with_label_values(&["SIA-DCS", "7001"]);
+    let responses = crate::metrics::responses_sent().with_label_values(&[TRANSPORT_NAME, "none", "7001"]);
+    assert_eq!(1, received.get());
+    assert_eq!(1, responses.get());
+    assert!(receiver.state().supervision.last_seen("7001").is_some());
+}
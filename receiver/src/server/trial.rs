@@ -0,0 +1,76 @@
+use common::keys::{AccountSelector, KeyQuery, SecretKey};
+use std::collections::HashMap;
+use std::sync::RwLock;
+use time::OffsetDateTime;
+
+/// Panel whose key was found by trial decryption.
+#[derive(Debug, Clone)]
+pub struct TrialMatch {
+    /// Index of the matching key in the trial keys at the time of the match.
+    pub candidate: usize,
+    pub matched_at: OffsetDateTime,
+    /// Number of messages decrypted with the learned key, including the one that matched.
+    pub messages: u64,
+    key: SecretKey,
+}
+
+/// Keys learned by trial decryption for panels missing in the accounts registry.
+#[derive(Debug, Default)]
+pub struct TrialMatches {
+    matches: RwLock<HashMap<AccountSelector, TrialMatch>>,
+}
+
+impl TrialMatches {
+    /// Returns key learned for the panel identified by the `query`.
+    pub fn key(&self, query: &KeyQuery) -> Option<SecretKey> {
+        let matches = self.matches.read().expect("lock is not poisoned");
+        matches.get(&selector(query)).map(|m| m.key.clone())
+    }
+
+    /// Returns all panels matched by trial decryption, ordered by receiver, line and account.
+    pub fn list(&self) -> Vec<(AccountSelector, TrialMatch)> {
+        let matches = self.matches.read().expect("lock is not poisoned");
+        let mut matches = matches.iter().map(|(s, m)| (s.clone(), m.clone())).collect::<Vec<_>>();
+        matches.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        matches
+    }
+
+    /// Forgets all learned keys and returns the number of panels that were matched.
+    pub fn clear(&self) -> usize {
+        let mut matches = self.matches.write().expect("lock is not poisoned");
+        let cleared = matches.len();
+        matches.clear();
+        cleared
+    }
+
+    /// Remembers that the `candidate` trial `key` decrypted a message of the panel identified by the `query`.
+    pub(super) fn learn(&self, query: &KeyQuery, candidate: usize, key: &str) {
+        let matched = TrialMatch {
+            candidate,
+            matched_at: OffsetDateTime::now_utc(),
+            messages: 1,
+            key: SecretKey::from(key),
+        };
+        self.matches
+            .write()
+            .expect("lock is not poisoned")
+            .insert(selector(query), matched);
+    }
+
+    /// Counts a message decrypted with the key learned for the panel identified by the `query`.
+    pub(super) fn record(&self, query: &KeyQuery) {
+        if let Some(matched) = self.matches.write().expect("lock is not poisoned").get_mut(&selector(query)) {
+            matched.messages += 1;
+        }
+    }
+
+    /// Forgets key learned for the panel identified by the `query`.
+    pub(super) fn forget(&self, query: &KeyQuery) {
+        self.matches.write().expect("lock is not poisoned").remove(&selector(query));
+    }
+}
+
+/// Returns selector of exactly the panel identified by the `query`.
+pub(super) fn selector(query: &KeyQuery) -> AccountSelector {
+    AccountSelector::new(Some(&query.account), query.receiver.as_deref(), query.line.as_deref())
+}
@@ -1,29 +1,71 @@
-use anyhow::Result;
-use common::dc09::DC09Message;
+use anyhow::{Result, anyhow, bail};
+use common::capture::{Direction, Transport};
 use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
 use std::{net::SocketAddr, sync::Arc};
 use tokio::net::{ToSocketAddrs, UdpSocket};
-use tokio::sync::mpsc::{UnboundedSender, unbounded_channel};
+use tokio::sync::Mutex;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::{Receiver, Sender, channel};
+use tokio::task::JoinSet;
 
-use crate::metrics::AppState;
-use crate::server::ResponseMode;
-use crate::utils::{build_response_message, get_received_message};
-use crate::utils::{increase_total_connections, process_invalid_message_metrics, process_valid_message_metrics};
-use crate::{Server, ServerConfig};
+use crate::capture::Capture;
+use crate::server::retransmit::{FrameKey, ResponseCache};
+use crate::server::{KeyQuery, MessageEvent, Server, ServerConfig, ServerState, decode_frame, wait_for_tasks};
+use crate::server::{QueuePolicy, ResponseMode, SharedServerConfig};
+use crate::utils::{MESSAGES_TARGET, log_invalid_message, log_received_message, log_sent_response};
+use crate::utils::{build_response_message, observe_response_delay};
+use crate::utils::{
+    capture_frame, increase_dropped_datagrams, increase_total_connections, increase_udp_retransmits, set_udp_queue_depth,
+};
+use crate::utils::{increase_responses_sent, observe_processing_duration};
+use crate::utils::{process_invalid_message_metrics, process_valid_message_metrics};
+
+#[cfg(test)]
+#[path = "./udp.tests.rs"]
+mod udp_tests;
 
 static TRANSPORT_NAME: &str = "UDP";
 
+/// Raw datagram, address of its sender and time it was received.
+type Datagram = (Vec<u8>, SocketAddr, Instant);
+
+/// Response waiting to be sent to a dialler.
+struct Response {
+    frame: String,
+    addr: SocketAddr,
+    account: String,
+    sequence: u16,
+    /// Delay to apply before sending the response.
+    delay: Duration,
+    /// Time the answered datagram was received.
+    received_at: Instant,
+}
+
 /// Represents DC09 messages UDP receiver.
 pub struct UdpServer {
     socket: Arc<UdpSocket>,
-    config: ServerConfig,
-    state: AppState,
+    config: SharedServerConfig,
+    state: ServerState,
+}
+
+impl UdpServer {
+    /// Returns address the server is bound to, useful when it was bound to port `0`.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
 }
 
 impl Server for UdpServer {
-    /// Creates new [`UdpServer`] instance.\
-    /// **Note** that `key` can be provided to decrypt encrypted DC09 messages.
-    async fn new(address: impl ToSocketAddrs, config: ServerConfig, state: AppState) -> Result<Self> {
+    /// Creates new [`UdpServer`] instance, returns an error if the connection limits or UDP pipeline settings are invalid.\
+    /// **Note** that keys to decrypt DC09 messages are taken from the [`KeyProvider`] of the `state`.
+    ///
+    /// [`KeyProvider`]: crate::server::KeyProvider
+    async fn new(address: impl ToSocketAddrs + Send, config: SharedServerConfig, state: ServerState) -> Result<Self> {
+        let settings = config.load();
+        settings.limits.validate().map_err(|e| anyhow!("limits.{e}"))?;
+        settings.udp.validate().map_err(|e| anyhow!("udp.{e}"))?;
+        drop(settings);
         let socket = UdpSocket::bind(address).await?;
         Ok(Self {
             socket: Arc::new(socket),
@@ -32,63 +74,268 @@ impl Server for UdpServer {
         })
     }
 
-    /// Starts listening on configured UDP address and port for incoming DC09 messages.
+    /// Starts listening on configured UDP address and port for incoming DC09 messages.\
+    /// **Note** that datagrams are parsed by a pool of workers and responses are sent by a separate task, workers that
+    /// panic are restarted.
     async fn run(&mut self) -> Result<()> {
-        let (tx, mut _rx) = unbounded_channel::<(String, SocketAddr)>();
-        let _s = Arc::clone(&self.socket);
+        let pipeline = self.config.load().udp.clone();
+        let (datagrams_tx, datagrams_rx) = channel::<Datagram>(pipeline.queue_size);
+        let (responses_tx, responses_rx) = channel::<Response>(pipeline.queue_size);
 
-        tokio::spawn(async move {
-            while let Some((response, addr)) = _rx.recv().await {
-                if let Err(error) = _s.send_to(response.as_bytes(), &addr).await {
-                    log::error!("{addr}: {error}");
-                }
-            }
-        });
+        let datagrams_rx = Arc::new(Mutex::new(datagrams_rx));
+        let cache = self.state.retransmit_cache.clone();
+        let mut tasks = (0..pipeline.workers)
+            .map(|_| {
+                tokio::spawn(supervise_worker(
+                    Arc::clone(&datagrams_rx),
+                    responses_tx.clone(),
+                    cache.clone(),
+                    Arc::clone(&self.config),
+                    self.state.clone(),
+                ))
+            })
+            .collect::<Vec<_>>();
+        drop(responses_tx);
+        tasks.push(tokio::spawn(run_sender(
+            Arc::clone(&self.socket),
+            responses_rx,
+            self.state.capture.clone(),
+        )));
 
         self.state.udp_ready.store(true, Ordering::Relaxed);
 
-        let mut buffer = [0; 2048];
+        // Longer datagrams are truncated to the maximum frame size and fail to parse.
+        let mut buffer = vec![0; self.config.load().limits.max_frame_size];
+        let mut closed = false;
         loop {
-            let (n, addr) = self.socket.recv_from(&mut buffer).await?;
+            let (n, addr) = tokio::select! {
+                received = self.socket.recv_from(&mut buffer) => received?,
+                () = self.state.shutdown.cancelled() => break,
+            };
+            let received_at = Instant::now();
             increase_total_connections(TRANSPORT_NAME);
+            capture_frame(&self.state, Transport::Udp, Direction::Inbound, addr, &buffer[..n]);
 
-            match str::from_utf8(&buffer[..n]) {
-                Ok(msg) => {
-                    let mode = &self.state.response_modes;
-                    process_message(&tx, addr, msg, &self.config, mode.message(), mode.heartbeat());
+            let datagram = (buffer[..n].to_vec(), addr, received_at);
+            closed = match pipeline.policy {
+                QueuePolicy::Drop => match datagrams_tx.try_send(datagram) {
+                    Ok(()) => false,
+                    Err(TrySendError::Full(_)) => {
+                        log::warn!("udp: datagram queue is full, dropping datagram from {addr}");
+                        increase_dropped_datagrams("QueueFull");
+                        false
+                    },
+                    Err(TrySendError::Closed(_)) => true,
                 },
-                Err(err) => {
-                    log::error!("received invalid UTF-8 sequence: {err}");
+                QueuePolicy::Block => {
+                    tokio::select! {
+                        sent = datagrams_tx.send(datagram) => sent.is_err(),
+                        () = self.state.shutdown.cancelled() => break,
+                    }
                 },
+            };
+            if closed {
+                log::error!("udp: datagram queue is closed, no worker is left, dropping datagram from {addr}");
+                increase_dropped_datagrams("QueueClosed");
+                break;
             }
+
+            set_udp_queue_depth("datagrams", datagrams_tx.max_capacity() - datagrams_tx.capacity());
+        }
+
+        self.state.udp_ready.store(false, Ordering::Relaxed);
+        log::info!("udp: stopped receiving, waiting for pending responses");
+        drop(datagrams_tx);
+        wait_for_tasks(tasks, self.config.load().shutdown_timeout).await;
+
+        if closed {
+            bail!("udp: datagram queue is closed");
+        }
+
+        Ok(())
+    }
+}
+
+/// Runs a worker and restarts it when it panics, the datagram it was processing is dropped.\
+/// **Note** that the worker is aborted together with the supervisor.
+async fn supervise_worker(
+    datagrams: Arc<Mutex<Receiver<Datagram>>>,
+    responses: Sender<Response>,
+    cache: Option<Arc<ResponseCache>>,
+    config: SharedServerConfig,
+    state: ServerState,
+) {
+    loop {
+        let mut worker = JoinSet::new();
+        worker.spawn(run_worker(
+            Arc::clone(&datagrams),
+            responses.clone(),
+            cache.clone(),
+            Arc::clone(&config),
+            state.clone(),
+        ));
+
+        match worker.join_next().await {
+            Some(Err(e)) if e.is_panic() => {
+                log::error!("udp: worker panicked while processing a datagram, restarting it");
+                increase_dropped_datagrams("WorkerPanic");
+            },
+            _ => break,
+        }
+    }
+}
+
+/// Parses and decrypts queued datagrams until the datagram queue is closed.
+async fn run_worker(
+    datagrams: Arc<Mutex<Receiver<Datagram>>>,
+    responses: Sender<Response>,
+    cache: Option<Arc<ResponseCache>>,
+    config: SharedServerConfig,
+    state: ServerState,
+) {
+    loop {
+        let received = {
+            let mut datagrams = datagrams.lock().await;
+            let received = datagrams.recv().await;
+            set_udp_queue_depth("datagrams", datagrams.len());
+            received
+        };
+
+        let Some((datagram, addr, received_at)) = received else {
+            break;
+        };
+
+        match str::from_utf8(&datagram) {
+            Ok(msg) => {
+                let response = process_message(addr, msg, received_at, &config.load(), &state, cache.as_deref()).await;
+                if let Some(response) = response
+                    && responses.send(response).await.is_err()
+                {
+                    break;
+                }
+            },
+            Err(err) => {
+                log::error!("received invalid UTF-8 sequence: {err}");
+            },
         }
     }
 }
 
-fn process_message(
-    tx: &UnboundedSender<(String, SocketAddr)>,
+/// Sends queued responses until the response queue is closed.\
+/// **Note** that delayed responses are sent from separate tasks, awaited before returning.
+async fn run_sender(socket: Arc<UdpSocket>, mut responses: Receiver<Response>, capture: Option<Capture>) {
+    let mut delayed = JoinSet::new();
+    while let Some(response) = responses.recv().await {
+        set_udp_queue_depth("responses", responses.len());
+        observe_response_delay(TRANSPORT_NAME, response.delay);
+        if response.delay.is_zero() {
+            send_response(&socket, &response, capture.as_ref()).await;
+        } else {
+            let socket = Arc::clone(&socket);
+            let capture = capture.clone();
+            delayed.spawn(async move {
+                tokio::time::sleep(response.delay).await;
+                send_response(&socket, &response, capture.as_ref()).await;
+            });
+        }
+
+        while delayed.try_join_next().is_some() {}
+    }
+
+    delayed.join_all().await;
+}
+
+async fn send_response(socket: &UdpSocket, response: &Response, capture: Option<&Capture>) {
+    let addr = response.addr;
+    log_sent_response(TRANSPORT_NAME, &addr, &response.account, response.sequence, &response.frame);
+    if let Some(capture) = capture {
+        capture.record(Transport::Udp, Direction::Outbound, addr, response.frame.as_bytes());
+    }
+
+    if let Err(error) = socket.send_to(response.frame.as_bytes(), &addr).await {
+        log::error!("{addr}: {error}");
+    }
+
+    observe_processing_duration(TRANSPORT_NAME, response.received_at);
+}
+
+async fn process_message(
     addr: SocketAddr,
     received_message: &str,
+    received_at: Instant,
     config: &ServerConfig,
-    message_mode: ResponseMode,
-    heartbeat_mode: ResponseMode,
-) {
-    let key = config.get_key_for_message(received_message);
-    match DC09Message::try_from(received_message, key) {
-        Ok(msg) => {
-            log::info!("{} -> {}", addr, get_received_message(received_message, &msg, config.mode));
-            process_valid_message_metrics(TRANSPORT_NAME, received_message, &msg);
-
-            let mode = if msg.is_heartbeat() { heartbeat_mode } else { message_mode };
-            if mode != ResponseMode::None {
-                let response = build_response_message(msg, key, mode);
-                log::info!("{} <- {}", addr, response.trim());
-                let _ = tx.send((response, addr));
+    state: &ServerState,
+    cache: Option<&ResponseCache>,
+) -> Option<Response> {
+    let frame = cache.and_then(|_| FrameKey::new(addr, received_message));
+    if let (Some(cache), Some(frame)) = (cache, &frame)
+        && let Some(response) = cache.get(frame)
+    {
+        tracing::info!(
+            target: MESSAGES_TARGET,
+            transport = TRANSPORT_NAME,
+            direction = "inbound",
+            peer = %addr,
+            account = %frame.account,
+            sequence = frame.sequence,
+            "{} -> retransmission of {}",
+            addr,
+            received_message.trim()
+        );
+        // Replayed responses are excluded from `dc09_responses_sent_total`, the frame was answered already.
+        increase_udp_retransmits();
+        return Some(Response {
+            frame: response,
+            addr,
+            account: frame.account.clone(),
+            sequence: frame.sequence,
+            delay: state.response_delays.sample(&frame.account, config),
+            received_at,
+        });
+    }
+
+    match decode_frame(received_message, config, state) {
+        Ok((msg, key)) => {
+            log_received_message(TRANSPORT_NAME, &addr, received_message, &msg, config.mode);
+            state.expectations.record(TRANSPORT_NAME, &addr, &msg);
+            let event = MessageEvent {
+                transport: Transport::Udp,
+                peer: addr,
+                frame: received_message,
+                message: &msg,
+            };
+            // Dropped messages are counted like messages answered with no response.
+            let mode = state.handler.on_message(event).await.unwrap_or(ResponseMode::None);
+
+            let query = KeyQuery::from_message(&msg);
+            let account = state.account_labels.label(&query, config);
+            process_valid_message_metrics(TRANSPORT_NAME, received_message, &msg, account);
+            state.supervision.record(&msg.account);
+            increase_responses_sent(TRANSPORT_NAME, account, mode);
+            if mode == ResponseMode::None {
+                return None;
             }
+
+            let delay = state.response_delays.sample(&msg.account, config);
+            let (account, sequence) = (msg.account.clone(), msg.sequence);
+            let response = build_response_message(msg, key.as_ref(), mode);
+            if let (Some(cache), Some(frame)) = (cache, frame) {
+                cache.insert(frame, response.clone());
+            }
+
+            Some(Response {
+                frame: response,
+                addr,
+                account,
+                sequence,
+                delay,
+                received_at,
+            })
         },
         Err(e) => {
-            log::error!("{} -> {}: {}", addr, e, received_message.trim());
+            log_invalid_message(TRANSPORT_NAME, &addr, received_message, &e);
             process_invalid_message_metrics(TRANSPORT_NAME, received_message, &e);
+            None
         },
     }
 }
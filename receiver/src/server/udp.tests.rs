@@ -0,0 +1,101 @@
+use super::*;
+use crate::server::UdpPipeline;
+use crate::server::{BoxFuture, EventHandler, ResponseModes, UnknownAccountPolicy};
+use crate::testing::{TestDialler, TestReceiver};
+use arc_swap::ArcSwap;
+use common::dc09::DC09Message;
+
+#[tokio::test]
+async fn invalid_pipeline_test() {
+    for (pipeline, error) in [
+        (
+            UdpPipeline {
+                workers: 0,
+                ..UdpPipeline::default()
+            },
+            "udp.workers: value must be at least 1",
+        ),
+        (
+            UdpPipeline {
+                queue_size: 0,
+                ..UdpPipeline::default()
+            },
+            "udp.queue_size: value must be at least 1",
+        ),
+        (
+            UdpPipeline {
+                retransmit_cache_size: 0,
+                ..UdpPipeline::default()
+            },
+            "udp.retransmit_cache_size: value must be at least 1",
+        ),
+    ] {
+        let config = ServerConfig::new(&[], Arc::default()).with_udp_pipeline(pipeline);
+        let result = TestReceiver::start_with(config).await;
+        assert_eq!(error, result.err().unwrap().to_string());
+    }
+
+    let pipeline = UdpPipeline {
+        retransmit_ttl: None,
+        retransmit_cache_size: 0,
+        ..UdpPipeline::default()
+    };
+    let config = ServerConfig::new(&[], Arc::default()).with_udp_pipeline(pipeline);
+    assert!(TestReceiver::start_with(config).await.is_ok());
+}
+
+#[tokio::test]
+async fn dropped_message_metrics_test() {
+    let config = ServerConfig::new(&[], Arc::default()).with_unknown_account_policy(UnknownAccountPolicy::Drop);
+    let receiver = TestReceiver::start_with(config).await.unwrap();
+    let message = DC09Message::new("SIA-DCS".to_owned(), "7002".to_owned(), 1, None);
+    let dialler = receiver.dialler(Transport::Udp).with_timeout(Duration::from_millis(200));
+    assert!(dialler.send(&message).await.unwrap().is_none());
+
+    let received = crate::metrics::messages_received().with_label_values(&["SIA-DCS", "7002"]);
+    let responses = crate::metrics::responses_sent().with_label_values(&[TRANSPORT_NAME, "none", "7002"]);
+    assert_eq!(1, received.get());
+    assert_eq!(1, responses.get());
+    assert!(receiver.state().supervision.last_seen("7002").is_some());
+}
+
+/// Event handler panicking on messages from the `6666` account.
+struct PanickingHandler;
+
+impl EventHandler for PanickingHandler {
+    fn on_message<'a>(&'a self, event: MessageEvent<'a>) -> BoxFuture<'a, Option<ResponseMode>> {
+        assert_ne!("6666", event.message.account, "crafted datagram");
+        Box::pin(std::future::ready(Some(ResponseMode::Ack)))
+    }
+}
+
+#[tokio::test]
+async fn worker_panic_test() {
+    let pipeline = UdpPipeline {
+        workers: 1,
+        ..UdpPipeline::default()
+    };
+    let config = Arc::new(ArcSwap::from_pointee(
+        ServerConfig::new(&[], Arc::default()).with_udp_pipeline(pipeline),
+    ));
+    let state = ServerState::new(&config, ResponseModes::default()).with_handler(Arc::new(PanickingHandler));
+    let mut server = UdpServer::new("127.0.0.1:0", Arc::clone(&config), state.clone())
+        .await
+        .unwrap();
+    let dialler = TestDialler::new(Transport::Udp, server.local_addr().unwrap());
+    let task = tokio::spawn(async move { server.run().await });
+
+    let dropped = crate::metrics::udp_datagrams_dropped().with_label_values(&["WorkerPanic"]);
+    let crafted = dialler.clone().with_timeout(std::time::Duration::from_millis(200));
+    for sequence in 1..=2 {
+        let message = DC09Message::new("SIA-DCS".to_owned(), "6666".to_owned(), sequence, None);
+        assert!(crafted.send(&message).await.unwrap().is_none());
+    }
+    assert_eq!(2, dropped.get());
+
+    let message = DC09Message::new("SIA-DCS".to_owned(), "1234".to_owned(), 1, None);
+    assert_eq!("ACK", dialler.send(&message).await.unwrap().unwrap().token);
+
+    state.shutdown.cancel();
+    task.await.unwrap().unwrap();
+}
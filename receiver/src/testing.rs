@@ -0,0 +1,254 @@
+use anyhow::{Result, bail};
+use arc_swap::ArcSwap;
+use common::capture::Transport;
+use common::dc09::DC09Message;
+use common::keys::SecretKey;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+use crate::server::{
+    BoxFuture, EventHandler, MessageEvent, ResponseMode, ResponseModes, Server, ServerConfig, ServerState, SharedServerConfig,
+    TcpServer, UdpServer,
+};
+
+#[cfg(test)]
+#[path = "./testing.tests.rs"]
+mod testing_tests;
+
+/// Address the test servers are bound to, the OS assigns free ports.
+const BIND_ADDRESS: &str = "127.0.0.1:0";
+
+/// Time the [`TestDialler`] waits for a response by default.
+const DEFAULT_RESPONSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Message received by the [`TestReceiver`] together with the response decided for it.
+#[derive(Debug, Clone)]
+pub struct ReceivedEvent {
+    pub transport: Transport,
+    pub peer: SocketAddr,
+    /// Frame as received, possibly encrypted.
+    pub frame: String,
+    pub message: DC09Message,
+    /// Response mode decided by the handler, `None` if the message was dropped.
+    pub response: Option<ResponseMode>,
+}
+
+/// Event handler that records received messages and delegates responses to the wrapped handler.
+struct RecordingHandler {
+    inner: Arc<dyn EventHandler>,
+    events: Mutex<Vec<ReceivedEvent>>,
+    received: watch::Sender<usize>,
+}
+
+impl EventHandler for RecordingHandler {
+    fn on_message<'a>(&'a self, event: MessageEvent<'a>) -> BoxFuture<'a, Option<ResponseMode>> {
+        Box::pin(async move {
+            let response = self.inner.on_message(event).await;
+            let mut events = self.events.lock().unwrap_or_else(|e| e.into_inner());
+            events.push(ReceivedEvent {
+                transport: event.transport,
+                peer: event.peer,
+                frame: event.frame.to_owned(),
+                message: event.message.clone(),
+                response,
+            });
+            self.received.send_replace(events.len());
+            response
+        })
+    }
+}
+
+/// Receiver running TCP and UDP servers on OS-assigned ports inside the current tokio runtime.\
+/// **Note** that the servers are shut down when the instance is dropped.
+pub struct TestReceiver {
+    tcp_addr: SocketAddr,
+    udp_addr: SocketAddr,
+    config: SharedServerConfig,
+    state: ServerState,
+    recorder: Arc<RecordingHandler>,
+    tasks: Vec<JoinHandle<Result<()>>>,
+}
+
+impl TestReceiver {
+    /// Starts receiver accepting messages from all accounts, without encryption.
+    pub async fn start() -> Result<Self> {
+        Self::start_with(ServerConfig::new(&[], Arc::default())).await
+    }
+
+    /// Starts receiver with the provided server configuration.
+    pub async fn start_with(config: ServerConfig) -> Result<Self> {
+        let config = Arc::new(ArcSwap::from_pointee(config));
+        let state = ServerState::new(&config, ResponseModes::default());
+        let recorder = Arc::new(RecordingHandler {
+            inner: Arc::clone(&state.handler),
+            events: Mutex::default(),
+            received: watch::Sender::new(0),
+        });
+        let state = state.with_handler(Arc::clone(&recorder) as Arc<dyn EventHandler>);
+
+        let mut tcp = TcpServer::new(BIND_ADDRESS, Arc::clone(&config), state.clone()).await?;
+        let mut udp = UdpServer::new(BIND_ADDRESS, Arc::clone(&config), state.clone()).await?;
+        let tcp_addr = tcp.local_addr()?;
+        let udp_addr = udp.local_addr()?;
+
+        Ok(Self {
+            tcp_addr,
+            udp_addr,
+            config,
+            state,
+            recorder,
+            tasks: vec![
+                tokio::spawn(async move { tcp.run().await }),
+                tokio::spawn(async move { udp.run().await }),
+            ],
+        })
+    }
+
+    /// Returns address of the TCP server.
+    pub fn tcp_addr(&self) -> SocketAddr {
+        self.tcp_addr
+    }
+
+    /// Returns address of the UDP server.
+    pub fn udp_addr(&self) -> SocketAddr {
+        self.udp_addr
+    }
+
+    /// Returns shared server configuration, changes are applied to the following messages.
+    pub fn config(&self) -> &SharedServerConfig {
+        &self.config
+    }
+
+    /// Returns state shared by the servers: response modes, delays, expectations and supervision.
+    pub fn state(&self) -> &ServerState {
+        &self.state
+    }
+
+    /// Sets response modes for messages and heartbeats.
+    pub fn set_response_modes(&self, message: ResponseMode, heartbeat: ResponseMode) {
+        self.state.response_modes.set_message(message);
+        self.state.response_modes.set_heartbeat(heartbeat);
+    }
+
+    /// Returns all messages received so far.
+    pub fn events(&self) -> Vec<ReceivedEvent> {
+        self.recorder.events.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Waits until at least `count` messages are received and returns all of them.
+    pub async fn wait_for_events(&self, count: usize, timeout: Duration) -> Result<Vec<ReceivedEvent>> {
+        let mut received = self.recorder.received.subscribe();
+        match tokio::time::timeout(timeout, received.wait_for(|n| *n >= count)).await {
+            Ok(Ok(_)) => Ok(self.events()),
+            _ => bail!("received {} of {count} messages within {timeout:?}", self.events().len()),
+        }
+    }
+
+    /// Creates dialler sending messages to this receiver over the `transport`.
+    pub fn dialler(&self, transport: Transport) -> TestDialler {
+        let address = match transport {
+            Transport::Tcp => self.tcp_addr,
+            Transport::Udp => self.udp_addr,
+        };
+        TestDialler::new(transport, address)
+    }
+
+    /// Shuts the servers down and waits for them to drain.
+    pub async fn shutdown(mut self) -> Result<()> {
+        self.state.shutdown.cancel();
+        for task in std::mem::take(&mut self.tasks) {
+            task.await??;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for TestReceiver {
+    fn drop(&mut self) {
+        self.state.shutdown.cancel();
+    }
+}
+
+/// Minimal dialler sending single DC09 messages and returning the receiver responses.
+#[derive(Debug, Clone)]
+pub struct TestDialler {
+    transport: Transport,
+    address: SocketAddr,
+    key: Option<SecretKey>,
+    timeout: Duration,
+}
+
+impl TestDialler {
+    /// Creates new [`TestDialler`] instance.
+    pub fn new(transport: Transport, address: SocketAddr) -> Self {
+        Self {
+            transport,
+            address,
+            key: None,
+            timeout: DEFAULT_RESPONSE_TIMEOUT,
+        }
+    }
+
+    /// Sets key to encrypt messages and decrypt responses.
+    pub fn with_key(mut self, key: Option<SecretKey>) -> Self {
+        self.key = key;
+        self
+    }
+
+    /// Sets time to wait for a response.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sends the `message` and returns the parsed response, `None` if the receiver did not respond in time.
+    pub async fn send(&self, message: &DC09Message) -> Result<Option<DC09Message>> {
+        let frame = match &self.key {
+            Some(key) => match message.to_encrypted(key.expose()) {
+                Some(frame) => frame,
+                None => bail!("unable to encrypt message with the provided key"),
+            },
+            None => message.to_string(),
+        };
+
+        let response = match self.send_frame(&frame).await? {
+            Some(response) => response,
+            None => return Ok(None),
+        };
+
+        Ok(Some(DC09Message::try_from(
+            &response,
+            self.key.as_ref().map(SecretKey::expose),
+        )?))
+    }
+
+    /// Sends raw `frame` and returns the raw response, `None` if the receiver did not respond in time.
+    pub async fn send_frame(&self, frame: &str) -> Result<Option<String>> {
+        let mut buffer = [0; 1024];
+        let received = match self.transport {
+            Transport::Tcp => {
+                let mut stream = TcpStream::connect(self.address).await?;
+                stream.write_all(frame.as_bytes()).await?;
+                tokio::time::timeout(self.timeout, stream.read(&mut buffer)).await
+            },
+            Transport::Udp => {
+                let socket = UdpSocket::bind(BIND_ADDRESS).await?;
+                socket.connect(self.address).await?;
+                socket.send(frame.as_bytes()).await?;
+                tokio::time::timeout(self.timeout, socket.recv(&mut buffer)).await
+            },
+        };
+
+        match received {
+            Ok(Ok(0)) | Err(_) => Ok(None),
+            Ok(Ok(n)) => Ok(Some(String::from_utf8_lossy(&buffer[..n]).into_owned())),
+            Ok(Err(e)) => Err(e.into()),
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use super::*;
+
+const KEY: &str = "0123456789abcdef";
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn message(token: &str, data: Option<&str>) -> DC09Message {
+    DC09Message::new(token.to_owned(), "1234".to_owned(), 1, data.map(str::to_owned))
+}
+
+#[tokio::test]
+async fn tcp_and_udp_messages_test() {
+    let receiver = TestReceiver::start().await.unwrap();
+    assert_ne!(0, receiver.tcp_addr().port());
+    assert_ne!(0, receiver.udp_addr().port());
+
+    let response = receiver
+        .dialler(Transport::Tcp)
+        .send(&message("SIA-DCS", Some("#1234|NRR|AStart")))
+        .await
+        .unwrap();
+    assert_eq!("ACK", response.unwrap().token);
+
+    let response = receiver.dialler(Transport::Udp).send(&message("NULL", None)).await.unwrap();
+    assert_eq!("ACK", response.unwrap().token);
+
+    let events = receiver.wait_for_events(2, TIMEOUT).await.unwrap();
+    assert_eq!(Transport::Tcp, events[0].transport);
+    assert_eq!(Some("#1234|NRR|AStart"), events[0].message.data.as_deref());
+    assert_eq!(Transport::Udp, events[1].transport);
+    assert_eq!(Some(ResponseMode::Ack), events[1].response);
+
+    receiver.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn response_controls_test() {
+    let receiver = TestReceiver::start().await.unwrap();
+    receiver.set_response_modes(ResponseMode::Duh, ResponseMode::None);
+
+    let dialler = receiver.dialler(Transport::Tcp).with_timeout(Duration::from_millis(200));
+    let response = dialler.send(&message("SIA-DCS", Some("#1234|NRR"))).await.unwrap();
+    assert_eq!("DUH", response.unwrap().token);
+    assert_eq!(None, dialler.send(&message("NULL", None)).await.unwrap());
+
+    let events = receiver.wait_for_events(2, TIMEOUT).await.unwrap();
+    assert_eq!(Some(ResponseMode::None), events[1].response);
+}
+
+#[tokio::test]
+async fn encrypted_messages_test() {
+    let keys = Arc::new([(0, SecretKey::from(KEY))].into());
+    let receiver = TestReceiver::start_with(ServerConfig::new(&[], keys)).await.unwrap();
+
+    let dialler = receiver.dialler(Transport::Udp).with_key(Some(SecretKey::from(KEY)));
+    let response = dialler.send(&message("SIA-DCS", Some("#1234|NRR"))).await.unwrap();
+    assert_eq!("*ACK", response.unwrap().token);
+
+    let events = receiver.wait_for_events(1, TIMEOUT).await.unwrap();
+    assert!(events[0].message.was_encrypted());
+}
+
+#[tokio::test]
+async fn shutdown_on_drop_test() {
+    let receiver = TestReceiver::start().await.unwrap();
+    let address = receiver.tcp_addr();
+    let shutdown = receiver.state().shutdown.clone();
+    assert!(receiver.wait_for_events(1, Duration::from_millis(50)).await.is_err());
+
+    drop(receiver);
+    assert!(shutdown.is_cancelled());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(TcpStream::connect(address).await.is_err());
+}
@@ -1,12 +1,20 @@
+use common::capture::{Direction, Transport};
 use common::dc09::{DC09Error, DC09Message};
+use common::keys::{KeyQuery, SecretKey};
 use common::logging::DisplayMode;
 use std::borrow::Cow;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
 use time::OffsetDateTime;
 
-use crate::{metrics, server::ResponseMode};
+use crate::metrics;
+use crate::server::{ResponseMode, ResponseModes, ServerConfig, ServerState, UnknownAccountPolicy};
 
-pub fn build_response_message(msg: DC09Message, key: Option<&str>, mode: ResponseMode) -> String {
+/// Builds response to the `msg`, an encrypted message is acknowledged with a response encrypted with the `key`.\
+/// **Note** that it falls back to `NAK` if the response can't be encrypted with the `key`.
+pub fn build_response_message(msg: DC09Message, key: Option<&SecretKey>, mode: ResponseMode) -> String {
     let was_encrypted = msg.was_encrypted();
+    let account = msg.account.clone();
     let response = match mode {
         ResponseMode::Ack => Some(
             DC09Message::ack(msg.account, msg.sequence)
@@ -25,9 +33,10 @@ pub fn build_response_message(msg: DC09Message, key: Option<&str>, mode: Respons
 
     if was_encrypted && mode == ResponseMode::Ack {
         if let Some(key) = key {
-            response
-                .to_encrypted(key)
-                .expect("Cannot encrypt DC09 message with the provided key")
+            response.to_encrypted(key.expose()).unwrap_or_else(|| {
+                log::error!("unable to encrypt response for account {account} with its key, responding with NAK");
+                DC09Message::nak().to_string()
+            })
         } else {
             response.to_string()
         }
@@ -36,23 +45,70 @@ pub fn build_response_message(msg: DC09Message, key: Option<&str>, mode: Respons
     }
 }
 
-pub fn process_valid_message_metrics(transport: &str, raw_message: &str, parsed_message: &DC09Message) {
+/// Returns response mode for the message taking account settings and the unknown account policy into account.\
+/// **Note** that it returns `None` if the message should be dropped.
+pub fn get_response_mode(
+    transport: &str,
+    msg: &DC09Message,
+    config: &ServerConfig,
+    modes: &ResponseModes,
+) -> Option<ResponseMode> {
+    let mode = if msg.is_heartbeat() {
+        modes.heartbeat()
+    } else {
+        modes.message()
+    };
+    if let Some((_, account)) = config.find_account(&KeyQuery::from_message(msg)) {
+        return Some(account.response.unwrap_or(mode));
+    }
+
+    let policy = config.unknown_account;
+    if config.diallers.is_empty() && policy == UnknownAccountPolicy::Accept {
+        return Some(mode);
+    }
+
+    log::warn!("message from unknown account {} ({policy})", msg.account);
+    metrics::unknown_account_messages()
+        .with_label_values(&[transport, &policy.to_string()])
+        .inc();
+
+    policy.response_mode(mode)
+}
+
+/// Updates metrics of a successfully parsed message, `account` is the label value returned by [`AccountLabeler`].
+///
+/// [`AccountLabeler`]: crate::metrics::AccountLabeler
+pub fn process_valid_message_metrics(transport: &str, raw_message: &str, parsed_message: &DC09Message, account: &str) {
     metrics::messages_received()
-        .with_label_values(&[&parsed_message.token, &parsed_message.account])
+        .with_label_values(&[&parsed_message.token, account])
         .inc();
 
     metrics::message_size_bytes()
         .with_label_values(&[transport])
         .observe(raw_message.len() as f64);
 
+    let now = OffsetDateTime::now_utc();
     metrics::last_message_timestamp()
-        .with_label_values(&[&parsed_message.account])
-        .set(OffsetDateTime::now_utc().unix_timestamp() as f64);
+        .with_label_values(&[account])
+        .set(now.unix_timestamp() as f64);
+
+    let encryption = if parsed_message.was_encrypted() {
+        "encrypted"
+    } else {
+        "plain"
+    };
+    metrics::messages_by_encryption()
+        .with_label_values(&[transport, encryption])
+        .inc();
+
+    if let Some(timestamp) = parsed_message.parsed_timestamp() {
+        metrics::timestamp_skew_seconds()
+            .with_label_values(&[transport])
+            .observe((now - timestamp).as_seconds_f64());
+    }
 
     if parsed_message.is_heartbeat() {
-        metrics::heartbeats_received()
-            .with_label_values(&[&parsed_message.account])
-            .inc();
+        metrics::heartbeats_received().with_label_values(&[account]).inc();
     }
 }
 
@@ -66,11 +122,69 @@ pub fn process_invalid_message_metrics(transport: &str, raw_message: &str, error
         .inc();
 }
 
+#[inline]
+pub fn observe_response_delay(transport: &str, delay: Duration) {
+    metrics::response_delay_seconds()
+        .with_label_values(&[transport])
+        .observe(delay.as_secs_f64());
+}
+
+#[inline]
+pub fn increase_responses_sent(transport: &str, account: &str, mode: ResponseMode) {
+    metrics::responses_sent()
+        .with_label_values(&[transport, &mode.to_string(), account])
+        .inc();
+}
+
+#[inline]
+pub fn observe_processing_duration(transport: &str, started: Instant) {
+    metrics::processing_duration_seconds()
+        .with_label_values(&[transport])
+        .observe(started.elapsed().as_secs_f64());
+}
+
+#[inline]
+pub fn capture_frame(state: &ServerState, transport: Transport, direction: Direction, peer: SocketAddr, frame: &[u8]) {
+    if let Some(capture) = &state.capture {
+        capture.record(transport, direction, peer, frame);
+    }
+}
+
 #[inline]
 pub fn increase_total_connections(transport: &str) {
     metrics::connections_total().with_label_values(&[transport]).inc();
 }
 
+#[inline]
+pub fn increase_rejected_connections(transport: &str, reason: &str) {
+    metrics::connections_rejected().with_label_values(&[transport, reason]).inc();
+}
+
+#[inline]
+pub fn increase_timed_out_connections(transport: &str) {
+    metrics::connections_timed_out().with_label_values(&[transport]).inc();
+}
+
+#[inline]
+pub fn set_udp_queue_depth(queue: &str, depth: usize) {
+    metrics::udp_queue_depth().with_label_values(&[queue]).set(depth as i64);
+}
+
+#[inline]
+pub fn increase_dropped_datagrams(reason: &str) {
+    metrics::udp_datagrams_dropped().with_label_values(&[reason]).inc();
+}
+
+#[inline]
+pub fn increase_udp_retransmits() {
+    metrics::udp_retransmits().inc();
+}
+
+#[inline]
+pub fn increase_capture_records_dropped() {
+    metrics::capture_records_dropped().inc();
+}
+
 #[inline]
 pub fn increase_active_connections() {
     metrics::active_connections().inc();
@@ -81,6 +195,55 @@ pub fn decrease_active_connections() {
     metrics::active_connections().dec();
 }
 
+/// Log target of received messages and sent responses, allows to control their level separately.
+pub const MESSAGES_TARGET: &str = "receiver::messages";
+
+/// Logs a message received from the `peer` with structured fields.
+pub fn log_received_message(transport: &str, peer: &SocketAddr, raw_message: &str, msg: &DC09Message, mode: DisplayMode) {
+    tracing::info!(
+        target: MESSAGES_TARGET,
+        transport,
+        direction = "inbound",
+        peer = %peer,
+        account = %msg.account,
+        token = %msg.token,
+        sequence = msg.sequence,
+        "{} -> {}",
+        peer,
+        get_received_message(raw_message, msg, mode)
+    );
+}
+
+/// Logs a response sent to the `peer` for the message with the `account` and `sequence`.
+pub fn log_sent_response(transport: &str, peer: &SocketAddr, account: &str, sequence: u16, response: &str) {
+    tracing::info!(
+        target: MESSAGES_TARGET,
+        transport,
+        direction = "outbound",
+        peer = %peer,
+        account,
+        sequence,
+        "{} <- {}",
+        peer,
+        response.trim()
+    );
+}
+
+/// Logs a message from the `peer` that failed processing.
+pub fn log_invalid_message(transport: &str, peer: &SocketAddr, raw_message: &str, error: &DC09Error) {
+    tracing::error!(
+        target: MESSAGES_TARGET,
+        transport,
+        direction = "inbound",
+        peer = %peer,
+        reason = error.reason(),
+        "{} -> {}: {}",
+        peer,
+        error,
+        raw_message.trim()
+    );
+}
+
 pub fn get_received_message<'a>(unmodified: &'a str, msg: &DC09Message, mode: DisplayMode) -> Cow<'a, str> {
     match mode {
         DisplayMode::Target => unmodified.trim().into(),